@@ -12,6 +12,7 @@ use primitive_types::H256;
 use prost::Message;
 use rand::Rng;
 use rocks::prelude::*;
+use storage::{KeyValueDB, MemoryDB, RocksDB, WriteBatch};
 
 use chain::{BlockHeader, IndexedBlock, IndexedBlockHeader, IndexedTransaction, Transaction};
 use proto::chain::ContractType;
@@ -25,12 +26,22 @@ pub enum CheckResult {
     BreakAt(u64),
 }
 
+// * Column family indices.
+const COL_DEFAULT: usize = 0;
+/// block_hash => BlockHeader
+const COL_BLOCK_HEADER: usize = 1;
+/// [block_hash, transaction_index: u64, transaction_hash] => Transaction
+const COL_TRANSACTION: usize = 2;
+/// transaction_hash => [block_hash, transaction_index: u64]
+const COL_TRANSACTION_BLOCK: usize = 3;
+const NUM_OF_COLUMNS: usize = 4;
+
+fn not_found(msg: &str) -> BoxError {
+    Box::new(io::Error::new(io::ErrorKind::NotFound, msg))
+}
+
 pub struct ChainDB {
-    db: DB,
-    default: ColumnFamily,
-    block_header: ColumnFamily,
-    transaction: ColumnFamily,
-    transaction_block: ColumnFamily,
+    db: Box<dyn KeyValueDB>,
 }
 
 impl Drop for ChainDB {
@@ -39,87 +50,118 @@ impl Drop for ChainDB {
     }
 }
 
+fn col_descs_for_chain_db() -> Vec<ColumnFamilyDescriptor> {
+    vec![
+        ColumnFamilyDescriptor::new(
+            DEFAULT_COLUMN_FAMILY_NAME,
+            ColumnFamilyOptions::default()
+                .optimize_for_small_db()
+                .optimize_for_point_lookup(32)
+                .num_levels(2)
+                .compression(CompressionType::NoCompression),
+        ),
+        // block_hash => BlockHeader
+        ColumnFamilyDescriptor::new(
+            "block-header",
+            ColumnFamilyOptions::default().max_write_buffer_number(6),
+        ),
+        // [block_hash, transaction_index: u64, transaction_hash] => Transaction
+        ColumnFamilyDescriptor::new(
+            "transaction",
+            ColumnFamilyOptions::default()
+                .prefix_extractor_fixed(32)
+                .optimize_level_style_compaction(512 * 1024 * 1024)
+                .max_write_buffer_number(6),
+        ),
+        // transaction_hash => [block_hash, transaction_index: u64]
+        // Key and value lengths are fixed
+        ColumnFamilyDescriptor::new(
+            "transaction-block",
+            ColumnFamilyOptions::default()
+                .table_factory_cuckoo(CuckooTableOptions::default())
+                // .optimize_level_style_compaction(512 * 1024 * 1024)
+                // .optimize_for_point_lookup(32)
+                .max_write_buffer_number(6),
+        ),
+    ]
+}
+
 impl ChainDB {
+    /// Open the ChainDB of RocksDB engine.
     pub fn new<P: AsRef<Path>>(db_path: P) -> ChainDB {
-        let db_options = DBOptions::default()
-            .create_if_missing(true)
-            .create_missing_column_families(true)
-            .increase_parallelism(num_cpus::get() as _)
-            .allow_mmap_reads(true) // for Cuckoo table
-            .max_open_files(1024);
-
-        let column_families = vec![
-            ColumnFamilyDescriptor::new(
-                DEFAULT_COLUMN_FAMILY_NAME,
-                ColumnFamilyOptions::default()
-                    .optimize_for_small_db()
-                    .optimize_for_point_lookup(32)
-                    .num_levels(2)
-                    .compression(CompressionType::NoCompression),
-            ),
-            // block_hash => BlockHeader
-            ColumnFamilyDescriptor::new(
-                "block-header",
-                ColumnFamilyOptions::default().max_write_buffer_number(6),
-            ),
-            // [block_hash, transaction_index: u64, transaction_hash] => Transaction
-            ColumnFamilyDescriptor::new(
-                "transaction",
-                ColumnFamilyOptions::default()
-                    .prefix_extractor_fixed(32)
-                    .optimize_level_style_compaction(512 * 1024 * 1024)
-                    .max_write_buffer_number(6),
-            ),
-            // transaction_hash => [block_hash, transaction_index: u64]
-            // Key and value lengths are fixed
-            ColumnFamilyDescriptor::new(
-                "transaction-block",
-                ColumnFamilyOptions::default()
-                    .table_factory_cuckoo(CuckooTableOptions::default())
-                    // .optimize_level_style_compaction(512 * 1024 * 1024)
-                    // .optimize_for_point_lookup(32)
-                    .max_write_buffer_number(6),
-            ),
-        ];
-
-        let (db, mut handles) = DB::open_with_column_families(&db_options, db_path, column_families).unwrap();
-        let txn_blk = handles.pop().unwrap();
-        let txn = handles.pop().unwrap();
-        let blk = handles.pop().unwrap();
-        let default = handles.pop().unwrap();
-
-        assert!(handles.is_empty());
+        let db = RocksDB::open(db_path, col_descs_for_chain_db()).unwrap();
+        ChainDB { db: Box::new(db) }
+    }
+
+    /// Open the ChainDB of a running node as a RocksDB secondary, which is read-only.
+    ///
+    /// `secondary_path` keeps the info logs of the secondary, and must not be shared with other instances.
+    pub fn new_secondary<P1: AsRef<Path>, P2: AsRef<Path>>(
+        db_path: P1,
+        secondary_path: P2,
+    ) -> Result<ChainDB, BoxError> {
+        let db = RocksDB::open_as_secondary(db_path, secondary_path, col_descs_for_chain_db())?;
+        Ok(ChainDB { db: Box::new(db) })
+    }
 
+    /// An empty ChainDB in memory.
+    pub fn new_in_memory() -> ChainDB {
         ChainDB {
-            db: db,
-            default: default,
-            block_header: blk,
-            transaction: txn,
-            transaction_block: txn_blk,
+            db: Box::new(MemoryDB::new(NUM_OF_COLUMNS)),
         }
     }
 
+    /// Catch up with the primary, for a ChainDB opened by `new_secondary`.
+    pub fn catch_up_with_primary(&self) -> Result<(), BoxError> {
+        self.db.try_catch_up_with_primary()?;
+        Ok(())
+    }
+
+    fn put(&self, col: usize, key: &[u8], value: &[u8]) -> io::Result<()> {
+        let mut wb = WriteBatch::with_capacity(1);
+        wb.put(col, key, value);
+        self.db.write(&wb)
+    }
+
+    fn must_get(&self, col: usize, key: &[u8]) -> Result<Vec<u8>, BoxError> {
+        self.db.get(col, key)?.ok_or_else(|| not_found("not found"))
+    }
+
+    /// Visit all key/value pairs where the key starts with the given prefix.
+    fn for_each_by_prefix<F>(&self, col: usize, prefix: &[u8], mut func: F)
+    where
+        F: FnMut(&[u8], &[u8]) -> (),
+    {
+        self.db.for_each_from(col, prefix, &mut |key, value| {
+            if !key.starts_with(prefix) {
+                return false;
+            }
+            func(key, value);
+            true
+        });
+    }
+
     pub fn reset_node_id(&self) -> Vec<u8> {
         let mut rng = rand::thread_rng();
         let mut node_id = vec![b'A'; 64];
         rng.fill(&mut node_id[32..]);
-        self.default
-            .put(WriteOptions::default_instance(), b"NODE_ID", &node_id)
-            .unwrap();
+        self.put(COL_DEFAULT, b"NODE_ID", &node_id).unwrap();
         node_id
     }
 
     pub fn get_node_id(&self) -> Vec<u8> {
-        if let Ok(node_id) = self.default.get(ReadOptions::default_instance(), b"NODE_ID") {
-            node_id.to_vec()
+        if let Ok(Some(node_id)) = self.db.get(COL_DEFAULT, b"NODE_ID") {
+            node_id
         } else {
             self.reset_node_id()
         }
     }
 
     pub fn get_block_height(&self) -> i64 {
-        self.default
-            .get(ReadOptions::default_instance(), b"BLOCK_HEIGHT")
+        self.db
+            .get(COL_DEFAULT, b"BLOCK_HEIGHT")
+            .ok()
+            .flatten()
             .map(|val| BE::read_u64(&*val) as i64)
             .unwrap_or(0)
     }
@@ -129,18 +171,14 @@ impl ChainDB {
         if height > self.get_block_height() {
             let mut val = [0u8; 8];
             BE::write_u64(&mut val, height as u64);
-            self.default
-                .put(WriteOptions::default_instance(), b"BLOCK_HEIGHT", &val)
-                .unwrap();
+            self.put(COL_DEFAULT, b"BLOCK_HEIGHT", &val).unwrap();
         }
     }
 
     pub fn force_update_block_height(&self, height: i64) -> Result<(), BoxError> {
         let mut val = [0u8; 8];
         BE::write_u64(&mut val, height as u64);
-        self.default
-            .put(WriteOptions::default_instance(), b"BLOCK_HEIGHT", &val)
-            .map_err(From::from)
+        self.put(COL_DEFAULT, b"BLOCK_HEIGHT", &val).map_err(From::from)
     }
 
     /// Highest block id, counted from 0
@@ -149,11 +187,11 @@ impl ChainDB {
     }
 
     pub fn insert_block(&self, block: &IndexedBlock) -> Result<(), Box<dyn Error>> {
-        let mut batch = WriteBatch::with_reserved_bytes(1024);
+        let mut batch = WriteBatch::with_capacity(1 + 2 * block.transactions.len());
 
         let mut buf = BytesMut::with_capacity(block.header.raw.encoded_len());
         block.header.raw.encode(&mut buf)?;
-        batch.put_cf(&self.block_header, block.header.hash.as_bytes(), &buf);
+        batch.put(COL_BLOCK_HEADER, block.header.hash.as_bytes(), &buf);
 
         for (index, txn) in block.transactions.iter().enumerate() {
             buf.clear();
@@ -163,28 +201,26 @@ impl ChainDB {
             let mut idx_key = [0u8; 8];
             BE::write_u64(&mut idx_key[..], index as u64);
 
-            batch.putv_cf(
-                &self.transaction,
+            batch.putv(
+                COL_TRANSACTION,
                 &[block.hash().as_bytes(), &idx_key, txn.hash.as_bytes()],
                 &[&buf],
             );
             // reverse index
             // transaction_hash => [block_hash, transaction_index: u64]
-            batch.putv_cf(
-                &self.transaction_block,
+            batch.putv(
+                COL_TRANSACTION_BLOCK,
                 &[txn.hash.as_bytes()],
                 &[block.hash().as_bytes(), &idx_key],
             );
         }
 
-        self.db.write(WriteOptions::default_instance(), &batch)?;
+        self.db.write(&batch)?;
         Ok(())
     }
 
     pub fn has_block_id(&self, id: &H256) -> bool {
-        self.block_header
-            .get(ReadOptions::default_instance(), id.as_bytes())
-            .is_ok()
+        matches!(self.db.get(COL_BLOCK_HEADER, id.as_bytes()), Ok(Some(_)))
     }
 
     pub fn has_block(&self, block: &IndexedBlock) -> bool {
@@ -192,92 +228,51 @@ impl ChainDB {
     }
 
     pub fn has_block_number(&self, num: u64) -> bool {
-        let mut lower_bound = [0u8; 32];
-        BE::write_u64(&mut lower_bound[..8], num);
-        let mut upper_bound = [0xffu8; 32];
-        BE::write_u64(&mut upper_bound[..8], num);
-
-        let it = self.block_header.new_iterator(
-            &ReadOptions::default()
-                .iterate_lower_bound(&lower_bound[..])
-                .iterate_upper_bound(&upper_bound[..]),
-        );
-        it.count() > 0
+        let mut prefix = [0u8; 8];
+        BE::write_u64(&mut prefix[..], num);
+
+        let mut found = false;
+        self.db.for_each_from(COL_BLOCK_HEADER, &prefix, &mut |key, _| {
+            found = key.starts_with(&prefix);
+            false
+        });
+        found
     }
 
     pub fn get_block_from_header(&self, header: IndexedBlockHeader) -> Result<IndexedBlock, BoxError> {
-        let mut upper_bound = header.hash.as_bytes().to_vec();
-        upper_bound.push(0xFF); // [0xcafebabe00 .. 0xcafebabeff]
-
-        let transactions = self
-            .transaction
-            .new_iterator(
-                &ReadOptions::default()
-                    .iterate_lower_bound(&header.hash.as_bytes())
-                    .iterate_upper_bound(&upper_bound),
-            )
-            .map(|(key, val)| {
-                let txn = Transaction::decode(val)?;
-                Ok(IndexedTransaction::new(H256::from_slice(&key[32 + 8..]), txn))
-            })
-            .collect::<Result<Vec<_>, BoxError>>();
-
-        transactions.map(|txns| IndexedBlock::new(header, txns))
+        self.get_block_transactions(&header.hash)
+            .map(|txns| IndexedBlock::new(header, txns))
     }
 
     pub fn get_block_transactions(&self, hash: &H256) -> Result<Vec<IndexedTransaction>, BoxError> {
-        let mut upper_bound = hash.as_bytes().to_vec();
-        upper_bound.push(0xFF); // [0xcafebabe00 .. 0xcafebabeff]
-
-        let ropts = ReadOptions::default()
-            .iterate_lower_bound(&hash.as_bytes())
-            .iterate_upper_bound(&upper_bound);
-        let txns = self
-            .transaction
-            .new_iterator(&ropts)
-            .map(|(key, val)| {
-                let txn = Transaction::decode(val)?;
-                Ok(IndexedTransaction::new(H256::from_slice(&key[32 + 8..]), txn))
-            })
-            .collect::<Result<Vec<_>, BoxError>>();
-        drop(ropts);
-        txns
+        let mut txns = vec![];
+        self.for_each_by_prefix(COL_TRANSACTION, hash.as_bytes(), |key, val| {
+            txns.push(
+                Transaction::decode(val).map(|txn| IndexedTransaction::new(H256::from_slice(&key[32 + 8..]), txn)),
+            );
+        });
+        txns.into_iter()
+            .map(|txn| txn.map_err(From::from))
+            .collect::<Result<Vec<_>, BoxError>>()
     }
 
     pub fn get_transaction_hashes_by_block_number(&self, num: i64) -> Result<Vec<H256>, BoxError> {
-        let mut lower_bound = [0u8; 8];
-        BE::write_u64(&mut lower_bound[..], num as u64);
-        let mut upper_bound = [0u8; 8];
-        BE::write_u64(&mut upper_bound[..], num as u64 + 1);
-
-        let ropts = ReadOptions::default()
-            .iterate_lower_bound(&lower_bound)
-            .iterate_upper_bound(&upper_bound);
-        let txn_hashes = self
-            .transaction
-            .new_iterator(&ropts)
-            .keys()
-            .map(|key| Ok(H256::from_slice(&key[32 + 8..])))
-            .collect::<Result<Vec<_>, BoxError>>();
-        drop(ropts);
-        txn_hashes
+        let mut prefix = [0u8; 8];
+        BE::write_u64(&mut prefix[..], num as u64);
+
+        let mut txn_hashes = vec![];
+        self.for_each_by_prefix(COL_TRANSACTION, &prefix, |key, _| {
+            txn_hashes.push(H256::from_slice(&key[32 + 8..]));
+        });
+        Ok(txn_hashes)
     }
 
     pub fn get_transaction_hashes_by_block_hash(&self, hash: &H256) -> Result<Vec<H256>, BoxError> {
-        let mut upper_bound = hash.as_bytes().to_vec();
-        upper_bound.push(0xFF); // [0xcafebabe00 .. 0xcafebabeff]
-
-        let ropts = ReadOptions::default()
-            .iterate_lower_bound(&hash.as_bytes())
-            .iterate_upper_bound(&upper_bound);
-        let txn_hashes = self
-            .transaction
-            .new_iterator(&ropts)
-            .keys()
-            .map(|key| Ok(H256::from_slice(&key[32 + 8..])))
-            .collect::<Result<Vec<_>, BoxError>>();
-        drop(ropts);
-        txn_hashes
+        let mut txn_hashes = vec![];
+        self.for_each_by_prefix(COL_TRANSACTION, hash.as_bytes(), |key, _| {
+            txn_hashes.push(H256::from_slice(&key[32 + 8..]));
+        });
+        Ok(txn_hashes)
     }
 
     pub fn get_block_header_by_number(&self, num: i64) -> Result<IndexedBlockHeader, BoxError> {
@@ -293,52 +288,37 @@ impl ChainDB {
     }
 
     pub fn get_block_header(&self, hash: &H256) -> Result<IndexedBlockHeader, BoxError> {
-        self.block_header
-            .get(ReadOptions::default_instance(), hash.as_bytes())
-            .map_err(From::from)
+        self.must_get(COL_BLOCK_HEADER, hash.as_bytes())
             .and_then(|raw_header| BlockHeader::decode(&*raw_header).map_err(From::from))
             .map(|header| IndexedBlockHeader::new(hash.clone(), header))
     }
 
     /// handles fork
     pub fn get_block_headers_by_number(&self, num: u64) -> Vec<IndexedBlockHeader> {
-        let mut lower_bound = [0u8; 32];
-        BE::write_u64(&mut lower_bound[..8], num);
-        let mut upper_bound = [0xff_u8; 32];
-        BE::write_u64(&mut upper_bound[..8], num);
-        let ropt = ReadOptions::default()
-            .iterate_lower_bound(&lower_bound[..])
-            .iterate_upper_bound(&upper_bound[..])
-            .pin_data(true);
-
-        self.block_header
-            .new_iterator(&ropt)
-            .map(|(key, val)| IndexedBlockHeader::new(H256::from_slice(key), BlockHeader::decode(val).unwrap()))
-            .collect()
+        let mut prefix = [0u8; 8];
+        BE::write_u64(&mut prefix[..], num);
+
+        let mut headers = vec![];
+        self.for_each_by_prefix(COL_BLOCK_HEADER, &prefix, |key, val| {
+            headers.push(IndexedBlockHeader::new(
+                H256::from_slice(key),
+                BlockHeader::decode(val).unwrap(),
+            ));
+        });
+        headers
     }
 
     pub fn get_block_by_number(&self, num: u64) -> Result<IndexedBlock, BoxError> {
-        let mut lower_bound = [0u8; 32];
-        BE::write_u64(&mut lower_bound[..8], num);
-        let mut upper_bound = [0xff_u8; 32];
-        BE::write_u64(&mut upper_bound[..8], num);
-
-        let ropts = ReadOptions::default()
-            .iterate_lower_bound(&lower_bound[..])
-            .iterate_upper_bound(&upper_bound[..])
-            .pin_data(true);
-        let it = self.block_header.new_iterator(&ropts);
-
-        // FIXME: iterator key lifetime leaks, key might becomes same key
-        // ref: https://github.com/bh1xuw/rust-rocks/issues/15
-        let found = it
-            // .take_while(|(key, _)| &key[..8] == lower_bound)
-            .map(|(key, val)| (key.to_vec(), val.to_vec()))
-            .collect::<Vec<_>>();
-        drop(ropts); // holds lifetime of bound slice.
+        let mut prefix = [0u8; 8];
+        BE::write_u64(&mut prefix[..], num);
+
+        let mut found = vec![];
+        self.for_each_by_prefix(COL_BLOCK_HEADER, &prefix, |key, val| {
+            found.push((key.to_vec(), val.to_vec()));
+        });
 
         if found.is_empty() {
-            return Err(Box::new(io::Error::new(io::ErrorKind::NotFound, "block not found")));
+            return Err(not_found("block not found"));
         }
         if found.len() > 1 {
             eprintln!("multiple blocks found for same number: {}", num);
@@ -358,11 +338,7 @@ impl ChainDB {
     }
 
     pub fn get_block_by_id(&self, id: &H256) -> Result<IndexedBlock, BoxError> {
-        self.block_header
-            .get(ReadOptions::default_instance(), id.as_bytes())
-            .map_err(From::from)
-            .and_then(|raw_header| BlockHeader::decode(&*raw_header).map_err(From::from))
-            .map(|header| IndexedBlockHeader::new(id.clone(), header))
+        self.get_block_header(id)
             .and_then(|header| self.get_block_from_header(header))
     }
 
@@ -371,55 +347,37 @@ impl ChainDB {
     }
 
     pub fn get_transaction_by_id(&self, id: &H256) -> Result<IndexedTransaction, BoxError> {
-        let mut key = self
-            .transaction_block
-            .get(ReadOptions::default_instance(), id.as_bytes())?
-            .to_vec();
+        let mut key = self.must_get(COL_TRANSACTION_BLOCK, id.as_bytes())?;
         key.extend_from_slice(id.as_bytes());
         let txn = self
-            .transaction
-            .get(ReadOptions::default_instance(), &key)
+            .must_get(COL_TRANSACTION, &key)
             .map(|raw| Transaction::decode(&*raw).unwrap())
             .map(|txn| IndexedTransaction::new(id.clone(), txn))?;
         Ok(txn)
     }
 
     pub fn get_transaction_index(&self, id: &H256) -> Result<i32, BoxError> {
-        let key = self
-            .transaction_block
-            .get(ReadOptions::default_instance(), id.as_bytes())?;
+        let key = self.must_get(COL_TRANSACTION_BLOCK, id.as_bytes())?;
         Ok(BE::read_u64(&key[32..]) as i32)
     }
 
     pub fn get_transaction_block_hash(&self, id: &H256) -> Result<H256, BoxError> {
-        let key = self
-            .transaction_block
-            .get(ReadOptions::default_instance(), id.as_bytes())?;
+        let key = self.must_get(COL_TRANSACTION_BLOCK, id.as_bytes())?;
         Ok(H256::from_slice(&key[..32]))
     }
 
     pub fn get_block_header_by_transaction_hash(&self, txn_hash: &H256) -> Result<IndexedBlockHeader, BoxError> {
-        let block_key = self
-            .transaction_block
-            .get(ReadOptions::default_instance(), txn_hash.as_bytes())?;
-        self.block_header
-            .get(ReadOptions::default_instance(), &block_key[..32])
-            .map(|raw| BlockHeader::decode(&*raw).unwrap())
-            .map(|header| IndexedBlockHeader::new(H256::from_slice(&block_key[..32]), header))
-            .map_err(From::from)
+        let block_key = self.must_get(COL_TRANSACTION_BLOCK, txn_hash.as_bytes())?;
+        self.get_block_header(&H256::from_slice(&block_key[..32]))
     }
 
     pub fn delete_transaction(&self, txn: &IndexedTransaction, wb: &mut WriteBatch) -> Result<(), BoxError> {
-        let block_key = self
-            .transaction_block
-            .get(ReadOptions::default_instance(), txn.hash.as_bytes())?;
-
-        if let Err(e) = self.block_header.get(ReadOptions::default_instance(), &block_key[..32]) {
-            if e.is_not_found() {
-                wb.deletev_cf(&self.transaction, &[&*block_key, txn.hash.as_bytes()]);
-                wb.delete_cf(&self.transaction_block, txn.hash.as_bytes());
-                return Ok(());
-            }
+        let block_key = self.must_get(COL_TRANSACTION_BLOCK, txn.hash.as_bytes())?;
+
+        if self.db.get(COL_BLOCK_HEADER, &block_key[..32])?.is_none() {
+            wb.delete(COL_TRANSACTION, &[&*block_key, txn.hash.as_bytes()].concat());
+            wb.delete(COL_TRANSACTION_BLOCK, txn.hash.as_bytes());
+            return Ok(());
         }
 
         Err(Box::new(io::Error::new(
@@ -429,63 +387,45 @@ impl ChainDB {
     }
 
     pub fn delete_block_by_number(&self, num: u64) -> Result<(), BoxError> {
-        let mut lower_bound = [0u8; 8];
-        BE::write_u64(&mut lower_bound[..], num);
-
-        let mut wb = WriteBatch::with_reserved_bytes(1024);
-
-        self.block_header
-            .new_iterator(&ReadOptions::default().iterate_lower_bound(&lower_bound))
-            .keys()
-            .take_while(|key| &key[..8] == &lower_bound)
-            .for_each(|key| {
-                info!("delete block {}", hex::encode(key));
-                wb.delete_cf(&self.block_header, key);
-            });
-        self.transaction
-            .new_iterator(&ReadOptions::default().iterate_lower_bound(&lower_bound))
-            .keys()
-            .take_while(|key| &key[..8] == &lower_bound)
-            .for_each(|key| {
-                info!("delete transaction {}", hex::encode(&key[32 + 8..]));
-                wb.delete_cf(&self.transaction, key);
-                wb.delete_cf(&self.transaction_block, &key[32 + 8..]);
-            });
+        let mut prefix = [0u8; 8];
+        BE::write_u64(&mut prefix[..], num);
+
+        let mut wb = WriteBatch::with_capacity(1024);
 
-        self.db.write(WriteOptions::default_instance(), &wb)?;
+        self.for_each_by_prefix(COL_BLOCK_HEADER, &prefix, |key, _| {
+            info!("delete block {}", hex::encode(key));
+            wb.delete(COL_BLOCK_HEADER, key);
+        });
+        self.for_each_by_prefix(COL_TRANSACTION, &prefix, |key, _| {
+            info!("delete transaction {}", hex::encode(&key[32 + 8..]));
+            wb.delete(COL_TRANSACTION, key);
+            wb.delete(COL_TRANSACTION_BLOCK, &key[32 + 8..]);
+        });
+
+        self.db.write(&wb)?;
 
         Ok(())
     }
 
     pub fn delete_block(&self, block: &IndexedBlock) -> bool {
-        let mut wb = WriteBatch::with_reserved_bytes(1024);
-
-        wb.delete_cf(&self.block_header, block.hash().as_bytes());
-
-        let header = &block.header;
-        self.transaction
-            .new_iterator(&ReadOptions::default().iterate_lower_bound(&header.hash.as_bytes()))
-            .keys()
-            .take_while(|key| &key[..32] == header.hash.as_bytes())
-            .for_each(|key| {
-                wb.delete_cf(&self.transaction, &key);
-                wb.delete_cf(&self.transaction_block, &key[32 + 8..]);
-            });
+        let mut wb = WriteBatch::with_capacity(1 + 2 * block.transactions.len());
+
+        wb.delete(COL_BLOCK_HEADER, block.hash().as_bytes());
 
-        self.db.write(WriteOptions::default_instance(), &wb).is_ok()
+        self.for_each_by_prefix(COL_TRANSACTION, block.hash().as_bytes(), |key, _| {
+            wb.delete(COL_TRANSACTION, key);
+            wb.delete(COL_TRANSACTION_BLOCK, &key[32 + 8..]);
+        });
+
+        self.db.write(&wb).is_ok()
     }
 
     fn delete_block_without_reverse_index(&self, block: &IndexedBlock, wb: &mut WriteBatch) {
-        wb.delete_cf(&self.block_header, block.hash().as_bytes());
-
-        let header = &block.header;
-        self.transaction
-            .new_iterator(&ReadOptions::default().iterate_lower_bound(&header.hash.as_bytes()))
-            .keys()
-            .take_while(|key| &key[..32] == header.hash.as_bytes())
-            .for_each(|key| {
-                wb.delete_cf(&self.transaction, &key);
-            });
+        wb.delete(COL_BLOCK_HEADER, block.hash().as_bytes());
+
+        self.for_each_by_prefix(COL_TRANSACTION, block.hash().as_bytes(), |key, _| {
+            wb.delete(COL_TRANSACTION, key);
+        });
     }
 
     fn relink_transactions_to_block(&self, block: &IndexedBlock, wb: &mut WriteBatch) {
@@ -498,30 +438,31 @@ impl ChainDB {
             (&mut corrent_reverse_index[..32]).copy_from_slice(block.hash().as_bytes());
             BE::write_u64(&mut corrent_reverse_index[32..], i as u64);
 
-            let reverse_index = self
-                .transaction_block
-                .get(ReadOptions::default_instance(), txn.hash.as_bytes())
-                .unwrap();
+            let reverse_index = self.must_get(COL_TRANSACTION_BLOCK, txn.hash.as_bytes()).unwrap();
 
-            if corrent_reverse_index != &*reverse_index {
+            if corrent_reverse_index != reverse_index {
                 println!(
                     "! wrong reverse index {:?}\n=> {}\n=> {}",
                     txn.hash,
-                    hex::encode(&*reverse_index),
+                    hex::encode(&reverse_index),
                     hex::encode(&corrent_reverse_index),
                 );
-                wb.put_cf(&self.transaction_block, txn.hash.as_ref(), &corrent_reverse_index);
+                wb.put(COL_TRANSACTION_BLOCK, txn.hash.as_ref(), &corrent_reverse_index);
             }
         });
     }
 
     pub fn block_hashes_from(&self, start_block_hash: &[u8], count: usize) -> Vec<Vec<u8>> {
-        self.block_header
-            .new_iterator(&ReadOptions::default().iterate_lower_bound(start_block_hash))
-            .keys()
-            .take(count)
-            .map(|key| key.to_vec())
-            .collect()
+        let mut hashes = Vec::with_capacity(count);
+        if count == 0 {
+            return hashes;
+        }
+        self.db
+            .for_each_from(COL_BLOCK_HEADER, start_block_hash, &mut |key, _| {
+                hashes.push(key.to_vec());
+                hashes.len() < count
+            });
+        hashes
     }
 
     pub fn handle_chain_fork_at(&self, mut num: u64, dry_run: bool) -> Result<(), BoxError> {
@@ -569,7 +510,7 @@ impl ChainDB {
             }
         }
 
-        let mut wb = WriteBatch::with_reserved_bytes(1024);
+        let mut wb = WriteBatch::with_capacity(1024);
 
         let mut txn_whitelist = HashSet::new();
         let mut orphan_txns = HashSet::new();
@@ -584,7 +525,7 @@ impl ChainDB {
 
         for fork in tobe_purged_forks {
             for header in fork.iter() {
-                // wb.delete_cf(&self.block_header, header.hash.as_bytes());
+                // wb.delete(COL_BLOCK_HEADER, header.hash.as_bytes());
                 let block = self.get_block_from_header(header.clone()).unwrap();
                 self.delete_block_without_reverse_index(&block, &mut wb);
                 println!("! delete block {:?}", header.hash);
@@ -599,7 +540,7 @@ impl ChainDB {
         if dry_run {
             return Ok(());
         }
-        self.db.write(WriteOptions::default_instance(), &wb)?;
+        self.db.write(&wb)?;
 
         if !orphan_txns.is_empty() {
             let mut f = OpenOptions::new()
@@ -616,10 +557,15 @@ impl ChainDB {
     }
 
     pub fn visit(&self) -> Result<(), Box<dyn Error>> {
-        let it = self.transaction.new_iterator(ReadOptions::default_instance());
-
-        for (key, raw) in it {
-            let txn = Transaction::decode(raw)?;
+        let mut result: Result<(), Box<dyn Error>> = Ok(());
+        self.db.for_each_from(COL_TRANSACTION, &[], &mut |key, raw| {
+            let txn = match Transaction::decode(raw) {
+                Ok(txn) => txn,
+                Err(e) => {
+                    result = Err(e.into());
+                    return false;
+                }
+            };
             match ContractType::from_i32(txn.raw_data.as_ref().unwrap().contract.as_ref().unwrap().r#type) {
                 Some(ContractType::TransferContract) => {
                     println!("txn id: {} => {:?}", hex::encode(key), txn.result);
@@ -629,32 +575,16 @@ impl ChainDB {
                 }
                 None => unreachable!(),
             }
-        }
-        Ok(())
-    }
-
-    pub fn block_headers<'a>(&'a self) -> impl Iterator<Item = IndexedBlockHeader> + 'a {
-        self.block_header
-            .new_iterator(ReadOptions::default_instance())
-            .map(|(blk_id, raw_header)| {
-                IndexedBlockHeader::new(H256::from_slice(blk_id), BlockHeader::decode(raw_header).unwrap())
-            })
-    }
-
-    pub fn blocks<'a>(&'a self) -> impl Iterator<Item = IndexedBlock> + 'a {
-        self.block_header
-            .new_iterator(ReadOptions::default_instance())
-            .map(|(blk_id, raw_header)| {
-                IndexedBlockHeader::new(H256::from_slice(blk_id), BlockHeader::decode(raw_header).unwrap())
-            })
-            .map(move |header| self.get_block_from_header(header).unwrap())
+            true
+        });
+        result
     }
 
     pub fn ref_block_hashes_of_block_num(&self, num: i64) -> Vec<H256> {
         if num < 65536 {
-            self.block_headers()
-                .take(num as usize + 1)
-                .map(|head| head.hash)
+            self.block_hashes_from(&[], num as usize + 1)
+                .iter()
+                .map(|raw_hash| H256::from_slice(raw_hash))
                 .collect()
         } else {
             let mut lower_bound = [0u8; 32];
@@ -662,16 +592,15 @@ impl ChainDB {
             let mut upper_bound = [0xff_u8; 32];
             BE::write_u64(&mut upper_bound[..8], num as u64);
 
-            let mut ref_hashes = self
-                .block_header
-                .new_iterator(
-                    &ReadOptions::default()
-                        .iterate_lower_bound(&lower_bound[..])
-                        .iterate_upper_bound(&upper_bound[..]),
-                )
-                .keys()
-                .map(|raw_hash| H256::from_slice(raw_hash))
-                .collect::<Vec<_>>();
+            let mut ref_hashes = vec![];
+            self.db
+                .for_each_from(COL_BLOCK_HEADER, &lower_bound, &mut |raw_hash, _| {
+                    if raw_hash >= &upper_bound[..] {
+                        return false;
+                    }
+                    ref_hashes.push(H256::from_slice(raw_hash));
+                    true
+                });
 
             let wrap_pos = 65536 - (num + 1) % 65536;
             let mut new_ref_hashes = ref_hashes.split_off(wrap_pos as usize);
@@ -681,8 +610,10 @@ impl ChainDB {
     }
 
     pub fn get_parent_hash_verified_block_number(&self) -> u64 {
-        self.default
-            .get(ReadOptions::default_instance(), b"PARENT_HASH_VERIFIED")
+        self.db
+            .get(COL_DEFAULT, b"PARENT_HASH_VERIFIED")
+            .ok()
+            .flatten()
             .map(|raw| BE::read_u64(&*raw))
             .unwrap_or(0)
     }
@@ -690,9 +621,7 @@ impl ChainDB {
     pub fn update_parent_hash_verified_block_number(&self, num: u64) -> Result<(), BoxError> {
         let mut raw = [0u8; 8];
         BE::write_u64(&mut raw[..], num);
-        self.default
-            .put(WriteOptions::default_instance(), b"PARENT_HASH_VERIFIED", &raw)
-            .map_err(From::from)
+        self.put(COL_DEFAULT, b"PARENT_HASH_VERIFIED", &raw).map_err(From::from)
     }
 
     pub fn verify_parent_hashes(&self) -> Result<CheckResult, BoxError> {
@@ -707,39 +636,46 @@ impl ChainDB {
             hex::encode(&parent_hash)
         );
 
-        for header in self
-            .block_header
-            .new_iterator(&ReadOptions::default().iterate_lower_bound(start_block.hash().as_bytes()))
-            .map(|(blk_id, raw_header)| {
-                IndexedBlockHeader::new(H256::from_slice(blk_id), BlockHeader::decode(raw_header).unwrap())
-            })
-        {
-            if header.raw.raw_data.as_ref().unwrap().parent_hash != parent_hash {
-                let parent_block_number = BE::read_u64(&parent_hash[..8]);
-                // block_number - 1 to handle multiple forks
-                self.update_parent_hash_verified_block_number(parent_block_number - 1)?;
-
-                error!("❌ parent_hash verification error");
-                warn!(
-                    "parent block {}, hash = {}",
-                    parent_block_number,
-                    hex::encode(parent_hash)
-                );
-                warn!(
-                    "current block {}, parent_hash = {}",
-                    header.number(),
-                    hex::encode(&header.raw.raw_data.as_ref().unwrap().parent_hash)
-                );
-                if parent_block_number == header.number() as u64 {
-                    return Ok(CheckResult::ForkAt(parent_block_number));
-                } else {
-                    return Ok(CheckResult::BreakAt(parent_block_number));
+        let mut broken_header = None;
+        self.db.for_each_from(
+            COL_BLOCK_HEADER,
+            start_block.hash().as_bytes(),
+            &mut |blk_id, raw_header| {
+                let header =
+                    IndexedBlockHeader::new(H256::from_slice(blk_id), BlockHeader::decode(raw_header).unwrap());
+                if header.raw.raw_data.as_ref().unwrap().parent_hash != parent_hash {
+                    broken_header = Some(header);
+                    return false;
                 }
+                if header.number() % 10000 == 0 {
+                    info!("block => {} parent_hash => {:?}", header.number(), header.hash);
+                }
+                parent_hash = header.hash.as_bytes().to_vec();
+                true
+            },
+        );
+
+        if let Some(header) = broken_header {
+            let parent_block_number = BE::read_u64(&parent_hash[..8]);
+            // block_number - 1 to handle multiple forks
+            self.update_parent_hash_verified_block_number(parent_block_number - 1)?;
+
+            error!("❌ parent_hash verification error");
+            warn!(
+                "parent block {}, hash = {}",
+                parent_block_number,
+                hex::encode(parent_hash)
+            );
+            warn!(
+                "current block {}, parent_hash = {}",
+                header.number(),
+                hex::encode(&header.raw.raw_data.as_ref().unwrap().parent_hash)
+            );
+            if parent_block_number == header.number() as u64 {
+                return Ok(CheckResult::ForkAt(parent_block_number));
+            } else {
+                return Ok(CheckResult::BreakAt(parent_block_number));
             }
-            if header.number() % 10000 == 0 {
-                info!("block => {} parent_hash => {:?}", header.number(), header.hash);
-            }
-            parent_hash = header.hash.as_bytes().to_vec();
         }
 
         let block_number = BE::read_u64(&parent_hash[..8]);
@@ -750,8 +686,10 @@ impl ChainDB {
     }
 
     pub fn get_merkle_tree_verified_block_number(&self) -> u64 {
-        self.default
-            .get(ReadOptions::default_instance(), b"MERKLE_TREE_VERIFIED")
+        self.db
+            .get(COL_DEFAULT, b"MERKLE_TREE_VERIFIED")
+            .ok()
+            .flatten()
             .map(|raw| BE::read_u64(&*raw))
             .unwrap_or(0)
     }
@@ -759,61 +697,55 @@ impl ChainDB {
     pub fn update_merkle_tree_verified_block_number(&self, num: u64) -> Result<(), BoxError> {
         let mut raw = [0u8; 8];
         BE::write_u64(&mut raw[..], num);
-        self.default
-            .put(WriteOptions::default_instance(), b"MERKLE_TREE_VERIFIED", &raw)
-            .map_err(From::from)
+        self.put(COL_DEFAULT, b"MERKLE_TREE_VERIFIED", &raw).map_err(From::from)
     }
 
     pub fn verify_merkle_tree(&self, patch: &HashMap<H256, H256>) -> Result<bool, Box<dyn Error>> {
         let start_block = self.get_block_by_number(self.get_merkle_tree_verified_block_number())?;
-        let ropt = ReadOptions::default().iterate_lower_bound(start_block.hash().as_bytes());
         info!("verify merkle tree from {}", start_block.number());
 
-        for (blk_id, raw_header) in self.block_header.new_iterator(&ropt) {
-            let header = IndexedBlockHeader::new(H256::from_slice(blk_id), BlockHeader::decode(raw_header).unwrap());
-            let block = self.get_block_from_header(header).unwrap();
-
-            if !block.verify_merkle_root_hash() {
-                if block.verify_merkle_root_hash_with_patch(patch) {
-                    info!("verified block {} with patch", block.number());
-                } else {
-                    error!("verify block {} failed", block.number());
-                    return Ok(false);
+        let mut result = Ok(true);
+        self.db.for_each_from(
+            COL_BLOCK_HEADER,
+            start_block.hash().as_bytes(),
+            &mut |blk_id, raw_header| {
+                let header =
+                    IndexedBlockHeader::new(H256::from_slice(blk_id), BlockHeader::decode(raw_header).unwrap());
+                let block = self.get_block_from_header(header).unwrap();
+
+                if !block.verify_merkle_root_hash() {
+                    if block.verify_merkle_root_hash_with_patch(patch) {
+                        info!("verified block {} with patch", block.number());
+                    } else {
+                        error!("verify block {} failed", block.number());
+                        result = Ok(false);
+                        return false;
+                    }
                 }
-            }
-            if block.number() % 1000 == 0 {
-                println!("block {} {:?}", block.number(), block.hash());
-                self.update_merkle_tree_verified_block_number(block.number() as _)?;
-            }
-        }
-        Ok(true)
+                if block.number() % 1000 == 0 {
+                    println!("block {} {:?}", block.number(), block.hash());
+                    if let Err(e) = self.update_merkle_tree_verified_block_number(block.number() as _) {
+                        result = Err(e);
+                        return false;
+                    }
+                }
+                true
+            },
+        );
+        result
     }
 
     pub fn get_db_property(&self, key: &str) -> u64 {
-        self.db.get_int_property(key).unwrap_or_default()
+        self.db.int_property(key)
     }
 
     pub fn get_accumulated_db_property(&self, key: &str) -> u64 {
-        [
-            &self.default,
-            &self.block_header,
-            &self.transaction,
-            &self.transaction_block,
-        ]
-        .iter()
-        .map(|cf| cf.get_int_property(key).unwrap_or_default())
-        .sum()
+        self.db.accumulated_int_property(key)
     }
 
     pub fn report_status(&self) {
-        let n_compactions = self
-            .db
-            .get_int_property("rocksdb.num-running-compactions")
-            .unwrap_or_default();
-        let n_flushes = self
-            .db
-            .get_int_property("rocksdb.num-running-flushes")
-            .unwrap_or_default();
+        let n_compactions = self.db.int_property("rocksdb.num-running-compactions");
+        let n_flushes = self.db.int_property("rocksdb.num-running-flushes");
         info!(
             "background db status: compactions={}, flushes={}",
             n_compactions, n_flushes
@@ -822,14 +754,8 @@ impl ChainDB {
 
     pub fn await_background_jobs(&self) {
         loop {
-            let n_compactions = self
-                .db
-                .get_int_property("rocksdb.num-running-compactions")
-                .unwrap_or_default();
-            let n_flushes = self
-                .db
-                .get_int_property("rocksdb.num-running-flushes")
-                .unwrap_or_default();
+            let n_compactions = self.db.int_property("rocksdb.num-running-compactions");
+            let n_flushes = self.db.int_property("rocksdb.num-running-flushes");
             if n_compactions + n_flushes <= 1 {
                 break;
             }
@@ -842,18 +768,11 @@ impl ChainDB {
     }
 
     pub fn compact_db(&self) -> Result<(), BoxError> {
-        self.default.compact_range(&Default::default(), ..)?;
-        self.block_header.compact_range(&Default::default(), ..)?;
-        self.transaction.compact_range(&Default::default(), ..)?;
-        self.transaction_block.compact_range(&Default::default(), ..)?;
+        self.db.compact()?;
         Ok(())
     }
 
     pub unsafe fn prepare_close(&self) {
-        info!("flush db ... {:?}", self.db.flush(&FlushOptions::default()));
-        info!("cancal background work ...");
-        self.db.cancel_background_work(/* wait: */ true);
-        info!("syncing WAL ... {:?}", self.db.sync_wal());
-        // eprintln!("Close DB ... {:?}", self.db.close());
+        self.db.prepare_close();
     }
 }
@@ -0,0 +1,286 @@
+//! Typed transaction builder.
+
+use std::convert::TryFrom;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use keys::{Address, Private};
+use prost::Message;
+use prost_types::Any;
+use proto::chain::transaction::{Contract, Raw as TransactionRaw};
+use proto::chain::{ContractType, Transaction};
+use proto::contract as contract_pb;
+
+use crate::{IndexedBlockHeader, IndexedTransaction};
+
+/// Default expiration, relative to the reference block, same as java-tron.
+pub const DEFAULT_EXPIRATION: i64 = 60_000;
+/// Max expiration, relative to the reference block.
+pub const MAX_EXPIRATION: i64 = 86_400_000;
+
+/// A builtin contract, which can be packed as a transaction parameter.
+pub trait ContractParameter: Message + Default + Sized {
+    fn contract_type() -> ContractType;
+
+    /// Raw owner address of the contract, empty for shielded transfers.
+    fn owner_address(&self) -> &[u8];
+
+    fn type_url() -> String {
+        format!("type.googleapis.com/protocol.{:?}", Self::contract_type())
+    }
+
+    fn to_any(&self) -> Any {
+        let mut buf = Vec::with_capacity(255);
+        self.encode(&mut buf).expect("Vec<u8> has unlimited capacity");
+        Any {
+            type_url: Self::type_url(),
+            value: buf,
+        }
+    }
+}
+
+macro_rules! impl_contract_parameter_for {
+    ($contract_ty:ident) => {
+        impl ContractParameter for contract_pb::$contract_ty {
+            fn contract_type() -> ContractType {
+                ContractType::$contract_ty
+            }
+            fn owner_address(&self) -> &[u8] {
+                &self.owner_address
+            }
+        }
+    };
+    ($contract_ty:ident, $type_name:expr) => {
+        impl ContractParameter for contract_pb::$contract_ty {
+            fn contract_type() -> ContractType {
+                ContractType::$contract_ty
+            }
+            fn owner_address(&self) -> &[u8] {
+                &self.owner_address
+            }
+            fn type_url() -> String {
+                format!("type.googleapis.com/protocol.{}", $type_name)
+            }
+        }
+    };
+}
+
+impl_contract_parameter_for!(AccountCreateContract);
+impl_contract_parameter_for!(AccountUpdateContract);
+impl_contract_parameter_for!(SetAccountIdContract);
+impl_contract_parameter_for!(AccountPermissionUpdateContract);
+impl_contract_parameter_for!(TransferContract);
+impl_contract_parameter_for!(TransferAssetContract);
+impl_contract_parameter_for!(AssetIssueContract);
+impl_contract_parameter_for!(ParticipateAssetIssueContract);
+impl_contract_parameter_for!(UpdateAssetContract);
+impl_contract_parameter_for!(UnfreezeAssetContract);
+impl_contract_parameter_for!(WitnessCreateContract);
+impl_contract_parameter_for!(WitnessUpdateContract);
+impl_contract_parameter_for!(UpdateBrokerageContract);
+impl_contract_parameter_for!(VoteWitnessContract);
+impl_contract_parameter_for!(WithdrawBalanceContract);
+impl_contract_parameter_for!(CreateSmartContract);
+impl_contract_parameter_for!(TriggerSmartContract);
+impl_contract_parameter_for!(UpdateSettingContract);
+impl_contract_parameter_for!(UpdateEnergyLimitContract);
+// prost will rename enum variant to CamelCase.
+impl_contract_parameter_for!(ClearAbiContract, "ClearABIContract");
+impl_contract_parameter_for!(FreezeBalanceContract);
+impl_contract_parameter_for!(UnfreezeBalanceContract);
+impl_contract_parameter_for!(ProposalCreateContract);
+impl_contract_parameter_for!(ProposalApproveContract);
+impl_contract_parameter_for!(ProposalDeleteContract);
+impl_contract_parameter_for!(ExchangeCreateContract);
+impl_contract_parameter_for!(ExchangeInjectContract);
+impl_contract_parameter_for!(ExchangeWithdrawContract);
+impl_contract_parameter_for!(ExchangeTransactionContract);
+impl_contract_parameter_for!(MarketSellAssetContract);
+impl_contract_parameter_for!(MarketCancelOrderContract);
+
+impl ContractParameter for contract_pb::ShieldedTransferContract {
+    fn contract_type() -> ContractType {
+        ContractType::ShieldedTransferContract
+    }
+    fn owner_address(&self) -> &[u8] {
+        &[]
+    }
+}
+
+/// Owner address of a contract in a transaction, None for shielded transfers or malformed contracts.
+pub fn owner_address_of(cntr: &Contract) -> Option<Address> {
+    macro_rules! decode_owner_address {
+        ($cntr_type:expr, $raw:expr, [$($contract_ty:ident),*]) => {
+            match $cntr_type {
+                $(
+                    ContractType::$contract_ty => contract_pb::$contract_ty::decode($raw)
+                        .ok()
+                        .and_then(|cntr| Address::try_from(cntr.owner_address()).ok()),
+                )*
+                _ => None,
+            }
+        };
+    }
+
+    let cntr_type = ContractType::from_i32(cntr.r#type)?;
+    let raw = &cntr.parameter.as_ref()?.value[..];
+    decode_owner_address!(
+        cntr_type,
+        raw,
+        [
+            AccountCreateContract,
+            AccountUpdateContract,
+            SetAccountIdContract,
+            AccountPermissionUpdateContract,
+            TransferContract,
+            TransferAssetContract,
+            AssetIssueContract,
+            ParticipateAssetIssueContract,
+            UpdateAssetContract,
+            UnfreezeAssetContract,
+            WitnessCreateContract,
+            WitnessUpdateContract,
+            UpdateBrokerageContract,
+            VoteWitnessContract,
+            WithdrawBalanceContract,
+            CreateSmartContract,
+            TriggerSmartContract,
+            UpdateSettingContract,
+            UpdateEnergyLimitContract,
+            ClearAbiContract,
+            FreezeBalanceContract,
+            UnfreezeBalanceContract,
+            ProposalCreateContract,
+            ProposalApproveContract,
+            ProposalDeleteContract,
+            ExchangeCreateContract,
+            ExchangeInjectContract,
+            ExchangeWithdrawContract,
+            ExchangeTransactionContract,
+            MarketSellAssetContract,
+            MarketCancelOrderContract
+        ]
+    )
+}
+
+/// Builds a transaction of a builtin contract.
+///
+/// TaPoS fields must be set from a recent block, via `ref_block`.
+#[derive(Debug, Clone)]
+pub struct TransactionBuilder {
+    raw: TransactionRaw,
+}
+
+impl TransactionBuilder {
+    pub fn new<C: ContractParameter>(contract: &C) -> Self {
+        let raw = TransactionRaw {
+            contract: Some(Contract {
+                r#type: C::contract_type() as i32,
+                parameter: Some(contract.to_any()),
+                ..Default::default()
+            }),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as i64)
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+        TransactionBuilder { raw }
+    }
+
+    /// Set the reference block(TaPoS), and the default expiration if not set.
+    pub fn ref_block(mut self, header: &IndexedBlockHeader) -> Self {
+        self.raw.ref_block_bytes = header.hash.as_bytes()[6..8].to_vec();
+        self.raw.ref_block_hash = header.hash.as_bytes()[8..16].to_vec();
+        if self.raw.expiration == 0 {
+            self.raw.expiration = header.timestamp() + DEFAULT_EXPIRATION;
+        }
+        self
+    }
+
+    /// Expiration timestamp, in milliseconds.
+    pub fn expiration(mut self, expiration: i64) -> Self {
+        self.raw.expiration = expiration;
+        self
+    }
+
+    /// Transaction timestamp, in milliseconds. Defaults to now.
+    pub fn timestamp(mut self, timestamp: i64) -> Self {
+        self.raw.timestamp = timestamp;
+        self
+    }
+
+    /// Max TRX burnt for energy, in SUN. Only used by smart contracts.
+    pub fn fee_limit(mut self, fee_limit: i64) -> Self {
+        self.raw.fee_limit = fee_limit;
+        self
+    }
+
+    pub fn memo<T: Into<Vec<u8>>>(mut self, memo: T) -> Self {
+        self.raw.data = memo.into();
+        self
+    }
+
+    /// Permission id of the owner account, 0 for owner, 2 or greater for actives.
+    pub fn permission_id(mut self, permission_id: i32) -> Self {
+        self.raw.contract.as_mut().unwrap().permission_id = permission_id;
+        self
+    }
+
+    /// Build the unsigned transaction.
+    pub fn build(self) -> IndexedTransaction {
+        let txn = Transaction {
+            raw_data: Some(self.raw),
+            ..Default::default()
+        };
+        IndexedTransaction::from_raw(txn).expect("encodable")
+    }
+
+    /// Build the transaction, signed by a private key.
+    pub fn sign(self, private: &Private) -> Result<IndexedTransaction, keys::Error> {
+        self.sign_with(&[private])
+    }
+
+    /// Build the transaction, signed by multiple private keys, for multisig permissions.
+    pub fn sign_with(self, privates: &[&Private]) -> Result<IndexedTransaction, keys::Error> {
+        let mut txn = self.build();
+        for private in privates {
+            txn.sign(private)?;
+        }
+        Ok(txn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keys::KeyPair;
+
+    #[test]
+    fn test_build_and_sign_transaction() {
+        let kp = KeyPair::generate();
+        let to = KeyPair::generate();
+        let transfer = contract_pb::TransferContract {
+            owner_address: kp.address().as_bytes().to_vec(),
+            to_address: to.address().as_bytes().to_vec(),
+            amount: 1_000_000,
+        };
+        let header = IndexedBlockHeader::dummy(0x1234_5678, 1_600_000_000_000);
+
+        let txn = TransactionBuilder::new(&transfer)
+            .ref_block(&header)
+            .memo("opentron")
+            .sign_with(&[kp.private(), to.private()])
+            .unwrap();
+        let raw = txn.raw.raw_data.as_ref().unwrap();
+
+        assert_eq!(raw.ref_block_bytes, vec![0x56, 0x78]);
+        assert_eq!(raw.expiration, 1_600_000_000_000 + DEFAULT_EXPIRATION);
+        assert_eq!(
+            raw.contract.as_ref().unwrap().parameter.as_ref().unwrap().type_url,
+            "type.googleapis.com/protocol.TransferContract"
+        );
+        assert!(txn.verify());
+        assert_eq!(txn.recover_owner().unwrap(), vec![kp.address(), to.address()]);
+        assert_eq!(owner_address_of(raw.contract.as_ref().unwrap()), Some(kp.address()));
+    }
+}
@@ -4,7 +4,7 @@ use std::convert::TryFrom;
 
 use byteorder::{ByteOrder, BE};
 use crypto::sha256;
-use keys::{Address, Public, Signature};
+use keys::{Address, Private, Public, Signature};
 use primitive_types::H256;
 use prost::Message;
 use proto::chain::{Block, BlockHeader, Transaction};
@@ -114,6 +114,14 @@ impl IndexedBlock {
         Ok(Address::from_public(&Public::recover(&buf, &sig)?))
     }
 
+    /// Sign the block header as the witness. Must be called after all fields of the header are filled.
+    pub fn sign(&mut self, private: &Private) -> Result<(), keys::Error> {
+        let mut buf = Vec::with_capacity(255);
+        self.header.raw.raw_data.as_ref().unwrap().encode(&mut buf).unwrap();
+        self.header.raw.witness_signature = private.sign(&buf)?.into();
+        Ok(())
+    }
+
     pub fn timestamp(&self) -> i64 {
         self.header.raw.raw_data.as_ref().unwrap().timestamp
     }
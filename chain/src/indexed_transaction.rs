@@ -3,7 +3,7 @@ use std::convert::TryFrom;
 use std::hash::{Hash, Hasher};
 
 use crypto::sha256;
-use keys::{Address, Public, Signature};
+use keys::{Address, Private, Public, Signature};
 use primitive_types::H256;
 use prost::Message;
 use proto::chain::Transaction;
@@ -49,6 +49,13 @@ impl IndexedTransaction {
             .collect()
     }
 
+    /// Sign the transaction and append the signature. Multiple signatures are for multisig permissions.
+    pub fn sign(&mut self, private: &Private) -> Result<(), keys::Error> {
+        let sig = private.sign_digest(self.hash.as_bytes())?;
+        self.raw.signatures.push(sig.into());
+        Ok(())
+    }
+
     pub fn expiration(&self) -> i64 {
         self.raw.raw_data.as_ref().unwrap().expiration
     }
@@ -1,10 +1,12 @@
 pub use primitive_types::H256;
 pub use proto::chain::{Block, BlockHeader, Transaction};
 
+pub use builder::{ContractParameter, TransactionBuilder};
 pub use indexed_block::IndexedBlock;
 pub use indexed_header::IndexedBlockHeader;
 pub use indexed_transaction::IndexedTransaction;
 
+pub mod builder;
 mod indexed_block;
 mod indexed_header;
 mod indexed_transaction;
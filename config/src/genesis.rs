@@ -2,10 +2,8 @@ use std::error::Error;
 use std::fs;
 use std::path::Path;
 
-use chain::IndexedBlock;
+use chain::{ContractParameter, IndexedBlock};
 use keys::Address;
-use prost::Message;
-use prost_types::Any;
 use proto::chain::{
     block_header::Raw as BlockHeaderRaw, transaction::Contract, transaction::Raw as TransactionRaw, BlockHeader,
     ContractType, Transaction,
@@ -13,6 +11,13 @@ use proto::chain::{
 use proto::contract::TransferContract;
 use serde::{Deserialize, Serialize};
 
+/// Same as mainnet.
+const MANTRA: &str =
+    "A new system must allow existing systems to be linked together without requiring any central control or coordination";
+const CREATOR: &str = "7YxAaK71utTpYJ8u4Zna7muWxd1pQwimpGxy8";
+/// The Blackhole account, which is required by all networks. Same as mainnet.
+pub const BLACKHOLE_ADDRESS: &str = "TLsV52sRDL79HXGGm9yzwKibb6BeruhUzy";
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Witness {
     pub address: String,
@@ -28,23 +33,24 @@ pub struct Alloc {
 }
 
 impl Alloc {
+    pub fn blackhole() -> Self {
+        Alloc {
+            address: BLACKHOLE_ADDRESS.into(),
+            name: "Blackhole".into(),
+            balance: i64::min_value(),
+        }
+    }
+
     fn to_transaction(&self, sender: &[u8]) -> Result<Transaction, Box<dyn Error>> {
         let transfer_contract = TransferContract {
             owner_address: sender.to_owned(),
             to_address: self.address.parse::<Address>()?.as_bytes().to_owned(),
             amount: self.balance,
         };
-        let any = Any {
-            type_url: "type.googleapis.com/protocol.TransferContract".into(),
-            value: {
-                let mut buf: Vec<u8> = Vec::with_capacity(255);
-                transfer_contract.encode(&mut buf)?;
-                buf
-            },
-        };
+        // NOTE: Genesis transactions have no TaPoS, expiration or timestamp, so TransactionBuilder is not used.
         let contract = Contract {
             r#type: ContractType::TransferContract as i32,
-            parameter: Some(any).into(),
+            parameter: Some(transfer_contract.to_any()),
             ..Default::default()
         };
         let raw = TransactionRaw {
@@ -71,6 +77,21 @@ pub struct GenesisConfig {
 }
 
 impl GenesisConfig {
+    /// Genesis of a private network. The Blackhole account is allocated if missing.
+    pub fn new(timestamp: i64, parent_hash: &[u8], witnesses: Vec<Witness>, mut allocs: Vec<Alloc>) -> Self {
+        if !allocs.iter().any(|alloc| alloc.name == "Blackhole") {
+            allocs.push(Alloc::blackhole());
+        }
+        GenesisConfig {
+            timestamp,
+            parent_hash: format!("0x{}", hex::encode(parent_hash)),
+            mantra: MANTRA.into(),
+            creator: CREATOR.into(),
+            witnesses,
+            allocs,
+        }
+    }
+
     pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
         let content = fs::read_to_string(path)?;
         Ok(serde_json::from_str(&content)?)
@@ -80,6 +101,10 @@ impl GenesisConfig {
         Ok(serde_json::from_str(&content)?)
     }
 
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("serializable")
+    }
+
     fn to_block_header(&self) -> BlockHeader {
         let raw_header = BlockHeaderRaw {
             number: 0,
@@ -132,4 +157,19 @@ mod tests {
 
         println!("block_id => {:?}", hex::encode(block.merkle_root_hash()));
     }
+
+    #[test]
+    fn private_net_genesis() {
+        let witnesses = vec![Witness {
+            address: "THKJYuUmMKKARNf7s2VT51g5uPY6KEqnat".into(),
+            url: "http://GR1.com".into(),
+            votes: 100000000,
+        }];
+        let conf = GenesisConfig::new(1_600_000_000_000, &[0u8; 32], witnesses, vec![]);
+        assert_eq!(conf.allocs[0].address, BLACKHOLE_ADDRESS);
+
+        let conf = GenesisConfig::load_from_str(&conf.to_json()).unwrap();
+        assert_eq!(conf.allocs.len(), 1);
+        assert!(conf.to_indexed_block().is_ok());
+    }
 }
@@ -33,12 +33,54 @@ pub struct ChainParameterConfig {
     pub allow_tvm_shielded_upgrade: bool,
     #[serde(default = "Default::default")]
     pub allow_tvm_istanbul_upgrade: bool,
+    #[serde(default = "Default::default")]
+    pub allow_tvm_stake_upgrade: bool,
+    #[serde(default = "Default::default")]
+    pub allow_tvm_asset_issue_upgrade: bool,
     // forbid-transfer-to-contract = false
     /// Default energy price is 100 SUN/unit. While in Mainnet/Testnet, it's 40 SUN/unit.
     #[serde(default = "default_energy_fee")]
     pub energy_fee: i64,
 }
 
+impl ChainParameterConfig {
+    /// Names of flags enabled by proposals, as in config files.
+    pub const FLAGS: &'static [&'static str] = &[
+        "allow-multisig",
+        "allow-adaptive-energy",
+        "allow-delegate-resource",
+        "allow-duplicate-asset-names",
+        "allow-tvm",
+        "allow-tvm-transfer-trc10-upgrade",
+        "allow-tvm-constantinople-upgrade",
+        "allow-tvm-solidity-059-upgrade",
+        "allow-tvm-shielded-upgrade",
+        "allow-tvm-istanbul-upgrade",
+        "allow-tvm-stake-upgrade",
+        "allow-tvm-asset-issue-upgrade",
+    ];
+
+    /// Enable a flag by its name, like `allow-tvm`.
+    pub fn enable(&mut self, name: &str) -> Result<(), String> {
+        match name {
+            "allow-multisig" => self.allow_multisig = true,
+            "allow-adaptive-energy" => self.allow_adaptive_energy = true,
+            "allow-delegate-resource" => self.allow_delegate_resource = true,
+            "allow-duplicate-asset-names" => self.allow_duplicate_asset_names = true,
+            "allow-tvm" => self.allow_tvm = true,
+            "allow-tvm-transfer-trc10-upgrade" => self.allow_tvm_transfer_trc10_upgrade = true,
+            "allow-tvm-constantinople-upgrade" => self.allow_tvm_constantinople_upgrade = true,
+            "allow-tvm-solidity-059-upgrade" => self.allow_tvm_solidity_059_upgrade = true,
+            "allow-tvm-shielded-upgrade" => self.allow_tvm_shielded_upgrade = true,
+            "allow-tvm-istanbul-upgrade" => self.allow_tvm_istanbul_upgrade = true,
+            "allow-tvm-stake-upgrade" => self.allow_tvm_stake_upgrade = true,
+            "allow-tvm-asset-issue-upgrade" => self.allow_tvm_asset_issue_upgrade = true,
+            _ => return Err(format!("unknown chain parameter: {}", name)),
+        }
+        Ok(())
+    }
+}
+
 fn default_maintenance_interval() -> i64 {
     // Args.java: 6h
     21600_000
@@ -58,6 +100,12 @@ pub struct ChainConfig {
     pub p2p_version: i32,
     #[serde(default = "default_proposal_expiration_duration")]
     pub proposal_expiration_duration: i64,
+    /// Single-node developer chain, block producing schedule is not checked.
+    #[serde(default = "Default::default")]
+    pub dev_mode: bool,
+    /// Execute transactions of a block in parallel, optimistically. Conflicting transactions are re-executed.
+    #[serde(default = "Default::default")]
+    pub parallel_execution: bool,
     pub parameter: ChainParameterConfig,
 }
 
@@ -72,9 +120,9 @@ pub struct StorageConfig {
     /// Path to ChainDB.
     #[serde(default = "default_data_dir")]
     pub data_dir: String,
-    // TODO: impl a different engine
+    /// Storage engine of ChainDB and StateDB.
     #[serde(default = "Default::default")]
-    pub engine: String,
+    pub engine: StorageEngine,
     /// Path to StateDB.
     #[serde(default = "default_state_data_dir")]
     pub state_data_dir: String,
@@ -82,6 +130,20 @@ pub struct StorageConfig {
     pub state_cache_dir: String,
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageEngine {
+    RocksDB,
+    /// Nothing is persisted, for tests and developer chains.
+    Memory,
+}
+
+impl Default for StorageEngine {
+    fn default() -> Self {
+        StorageEngine::RocksDB
+    }
+}
+
 fn default_data_dir() -> String {
     "./data/chaindb".into()
 }
@@ -135,6 +197,68 @@ pub struct GraphQLConfig {
     pub endpoint: String,
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct ZtronConfig {
+    /// Path to sapling parameters.
+    #[serde(default = "default_params_dir")]
+    pub params_dir: String,
+    /// Shielded TRC20 contracts, whose note commitment trees are followed block by block.
+    #[serde(default = "Default::default")]
+    pub follow_contracts: Vec<String>,
+    /// Incoming viewing keys in hex. Notes received by them are witnessed in followed trees, for spending.
+    #[serde(default = "Default::default")]
+    pub watch_ivks: Vec<String>,
+}
+
+fn default_params_dir() -> String {
+    "./ztron-params".into()
+}
+
+impl Default for ZtronConfig {
+    fn default() -> Self {
+        ZtronConfig {
+            params_dir: default_params_dir(),
+            follow_contracts: vec![],
+            watch_ivks: vec![],
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct WitnessConfig {
+    /// Plaintext private key in hex. Prefer `keystore`.
+    #[serde(default = "Default::default")]
+    pub private_key: String,
+    /// Path to an encrypted keystore file.
+    #[serde(default = "Default::default")]
+    pub keystore: String,
+    /// File containing the keystore password.
+    #[serde(default = "Default::default")]
+    pub password_file: String,
+    /// Environment variable containing the keystore password, used when `password-file` is not set.
+    #[serde(default = "default_password_env")]
+    pub password_env: String,
+}
+
+fn default_password_env() -> String {
+    "OPENTRON_KEYSTORE_PASSWORD".into()
+}
+
+impl Default for WitnessConfig {
+    fn default() -> Self {
+        WitnessConfig {
+            private_key: Default::default(),
+            keystore: Default::default(),
+            password_file: Default::default(),
+            password_env: default_password_env(),
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "kebab-case")]
 pub struct Config {
@@ -142,6 +266,10 @@ pub struct Config {
     pub storage: StorageConfig,
     pub protocol: ProtocolConfig,
     pub graphql: GraphQLConfig,
+    #[serde(default = "Default::default")]
+    pub ztron: ZtronConfig,
+    #[serde(default = "Default::default")]
+    pub witness: WitnessConfig,
 }
 
 impl Config {
@@ -153,6 +281,10 @@ impl Config {
     pub fn load_from_str(content: &str) -> Result<Self, Box<dyn std::error::Error>> {
         Ok(toml::from_str(content)?)
     }
+
+    pub fn to_toml(&self) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(toml::to_string(self)?)
+    }
 }
 
 #[cfg(test)]
@@ -163,4 +295,16 @@ mod tests {
     fn test_load_default_mainnet_config() {
         assert!(Config::load_from_str(include_str!("../../etc/conf.toml")).is_ok());
     }
+
+    #[test]
+    fn test_enable_chain_parameters() {
+        let mut config = Config::load_from_str(include_str!("../../etc/conf.toml")).unwrap();
+        for name in ChainParameterConfig::FLAGS {
+            config.chain.parameter.enable(name).unwrap();
+        }
+        assert!(config.chain.parameter.enable("allow-everything").is_err());
+
+        let config = Config::load_from_str(&config.to_toml().unwrap()).unwrap();
+        assert!(config.chain.parameter.allow_tvm_asset_issue_upgrade);
+    }
 }
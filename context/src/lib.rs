@@ -1,17 +1,21 @@
 use std::collections::HashSet;
+use std::env;
 use std::error::Error;
+use std::fs;
 use std::path::Path;
 use std::sync::atomic::{AtomicBool, AtomicU32};
-use std::sync::RwLock;
+use std::sync::{Arc, Mutex, RwLock};
 
+use chain::IndexedTransaction;
 use chain_db::ChainDB;
 use config::genesis::GenesisConfig;
-use config::Config;
-use log::info;
+use config::{Config, StorageEngine, WitnessConfig};
+use keys::{KeyPair, Keystore, Private};
+use log::{info, warn};
+use manager::Manager;
 use primitive_types::H256;
 use proto::common::BlockId;
-use tokio::sync::broadcast;
-use manager::Manager;
+use tokio::sync::{broadcast, Notify, RwLock as AsyncRwLock};
 
 pub struct AppContext {
     pub outbound_ip: String,
@@ -28,18 +32,32 @@ pub struct AppContext {
     /// The termination signal is used to close all connections and services.
     pub termination_signal: broadcast::Sender<()>,
     pub manager: RwLock<Manager>,
+    /// Snapshot of the manager at the latest applied block, for queries that must not block block execution.
+    pub state_snapshot: RwLock<Arc<Manager>>,
+    /// Held by queries for reading, and by secondaries for writing while catching up with the primary.
+    ///
+    /// Snapshots of secondaries are not pinned, they read the live db, which only changes on catching up.
+    pub catch_up_lock: AsyncRwLock<()>,
+    /// Key of the witness, if configured.
+    pub witness_key: Option<KeyPair>,
+    /// Transactions to be packed into blocks, only used by the developer chain.
+    pub pending_transactions: Mutex<Vec<IndexedTransaction>>,
+    /// Notified when a transaction is added to `pending_transactions`.
+    pub new_transaction_signal: Notify,
 }
 
 impl AppContext {
     pub fn from_config<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
-        let config = Config::load_from_file(&path)?;
-
-        let genesis_path = path.as_ref().parent().unwrap().join(&config.chain.genesis);
-
-        let genesis_config = GenesisConfig::load_from_file(&genesis_path)?;
+        let (config, genesis_config) = load_config(path)?;
         let genesis_blk = genesis_config.to_indexed_block()?;
 
-        let chain_db = ChainDB::new(&config.storage.data_dir);
+        let chain_db = match config.storage.engine {
+            StorageEngine::RocksDB => ChainDB::new(&config.storage.data_dir),
+            StorageEngine::Memory => {
+                warn!("in-memory storage engine, nothing will be persisted");
+                ChainDB::new_in_memory()
+            }
+        };
         if !chain_db.has_block(&genesis_blk) {
             if let Ok(_) = chain_db.get_genesis_block() {
                 panic!("genesis block config is inconsistent with chain-db");
@@ -49,9 +67,47 @@ impl AppContext {
         }
         chain_db.report_status();
 
+        let witness_key = load_witness_key(&config.witness)?;
+        if let Some(ref key) = witness_key {
+            info!("witness address => {}", key.address());
+        }
+
+        let db_manager = Manager::new(&config, &genesis_config);
+        AppContext::new(config, genesis_config, chain_db, db_manager, witness_key)
+    }
+
+    /// Open ChainDB and StateDB of a running node as RocksDB secondaries, for serving read-only queries.
+    ///
+    /// Secondaries are updated by `catch_up_with_primary`.
+    pub fn from_config_as_secondary<P1: AsRef<Path>, P2: AsRef<Path>>(
+        path: P1,
+        secondary_path: P2,
+    ) -> Result<Self, Box<dyn Error>> {
+        let (config, genesis_config) = load_config(path)?;
+        if config.storage.engine != StorageEngine::RocksDB {
+            return Err("only the rocksdb storage engine can be opened as secondary".into());
+        }
+        let genesis_blk = genesis_config.to_indexed_block()?;
+
+        let chain_db = ChainDB::new_secondary(&config.storage.data_dir, secondary_path.as_ref().join("chaindb"))?;
+        if !chain_db.has_block(&genesis_blk) {
+            return Err("chain-db is not inited by the primary, or genesis block config is inconsistent".into());
+        }
+
+        let db_manager = Manager::new_secondary(&config, &genesis_config, secondary_path.as_ref().join("statedb"))?;
+        AppContext::new(config, genesis_config, chain_db, db_manager, None)
+    }
+
+    fn new(
+        config: Config,
+        genesis_config: GenesisConfig,
+        chain_db: ChainDB,
+        mut db_manager: Manager,
+        witness_key: Option<KeyPair>,
+    ) -> Result<Self, Box<dyn Error>> {
         let genesis_block_id = BlockId {
             number: 0,
-            hash: genesis_blk.header.hash.as_ref().to_owned(),
+            hash: genesis_config.to_indexed_block()?.header.hash.as_ref().to_owned(),
         };
 
         let node_id = chain_db.get_node_id();
@@ -60,9 +116,18 @@ impl AppContext {
         info!("genesis block id => {}", hex::encode(&genesis_block_id.hash));
         info!("chain-db loaded");
 
-        let mut db_manager = Manager::new(&config, &genesis_config);
+        // Shielded precompiles fail with an error until the parameters are provided, corrupted ones are fatal.
+        match ztron::params::init_sapling_parameters(&config.ztron.params_dir) {
+            Ok(()) => info!("sapling parameters loaded"),
+            Err(e @ ztron::params::Error::NotFound(_)) => {
+                warn!("{}, shielded contracts can not be executed", e)
+            }
+            Err(e) => return Err(e.into()),
+        }
+
         let ref_block_hashes = chain_db.ref_block_hashes_of_block_num(db_manager.latest_block_number());
         db_manager.init_ref_blocks(ref_block_hashes);
+        let state_snapshot = RwLock::new(Arc::new(db_manager.snapshot()));
 
         Ok(AppContext {
             chain_db,
@@ -78,6 +143,53 @@ impl AppContext {
             recent_blk_ids: RwLock::new(HashSet::new()),
             termination_signal: broadcast::channel(1024).0,
             manager: RwLock::new(db_manager),
+            state_snapshot,
+            catch_up_lock: AsyncRwLock::new(()),
+            witness_key,
+            pending_transactions: Mutex::new(vec![]),
+            new_transaction_signal: Notify::new(),
         })
     }
+
+    /// The latest state snapshot. Queries against it are consistent, and never hold the manager lock.
+    pub fn state_snapshot(&self) -> Arc<Manager> {
+        self.state_snapshot.read().unwrap().clone()
+    }
+
+    /// Replace the state snapshot, after a block is applied to the manager.
+    pub fn update_state_snapshot(&self, manager: &Manager) {
+        let snapshot = Arc::new(manager.snapshot());
+        *self.state_snapshot.write().unwrap() = snapshot;
+    }
+}
+
+fn load_config<P: AsRef<Path>>(path: P) -> Result<(Config, GenesisConfig), Box<dyn Error>> {
+    let config = Config::load_from_file(&path)?;
+    let genesis_path = path.as_ref().parent().unwrap().join(&config.chain.genesis);
+    let genesis_config = GenesisConfig::load_from_file(&genesis_path)?;
+    Ok((config, genesis_config))
+}
+
+/// Load the witness key from the keystore file, or from the plaintext private key.
+/// The keystore password, from `password_file` if given, or the `password_env` environment variable.
+pub fn read_keystore_password(password_file: Option<&str>, password_env: &str) -> Result<String, Box<dyn Error>> {
+    match password_file {
+        Some(path) => Ok(fs::read_to_string(path)?.trim_end_matches(&['\r', '\n'][..]).to_owned()),
+        None => env::var(password_env).map_err(|_| format!("keystore password not found in ${}", password_env).into()),
+    }
+}
+
+fn load_witness_key(config: &WitnessConfig) -> Result<Option<KeyPair>, Box<dyn Error>> {
+    let private = if !config.keystore.is_empty() {
+        let keystore = Keystore::from_json(&fs::read_to_string(&config.keystore)?)?;
+        let password_file = Some(&*config.password_file).filter(|path| !path.is_empty());
+        let password = read_keystore_password(password_file, &config.password_env)?;
+        keystore.decrypt(password.as_bytes())?
+    } else if !config.private_key.is_empty() {
+        warn!("plaintext witness private key in config, use an encrypted keystore instead");
+        config.private_key.parse::<Private>()?
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(KeyPair::from_private(private)?))
 }
@@ -4,7 +4,7 @@ use std::fmt;
 use secp256k1::Error as Secp256k1Error;
 
 /// Key Errors.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Error {
     /// Public key format error.
     InvalidPublic,
@@ -20,6 +20,14 @@ pub enum Error {
     InvalidAddress,
     /// Unable to generate a key pair.
     FailedKeyGeneration,
+    /// Invalid BIP39 mnemonic phrase or entropy.
+    InvalidMnemonic,
+    /// Invalid BIP32 derivation path.
+    InvalidDerivationPath,
+    /// Malformed or unsupported keystore.
+    InvalidKeystore,
+    /// Wrong keystore password.
+    InvalidPassword,
 }
 
 impl fmt::Display for Error {
@@ -32,6 +40,10 @@ impl fmt::Display for Error {
             Error::InvalidPrivate => "Invalid Private",
             Error::InvalidAddress => "Invalid Address",
             Error::FailedKeyGeneration => "Key generation failed",
+            Error::InvalidMnemonic => "Invalid Mnemonic",
+            Error::InvalidDerivationPath => "Invalid Derivation Path",
+            Error::InvalidKeystore => "Invalid Keystore",
+            Error::InvalidPassword => "Invalid Password",
         };
 
         msg.fmt(f)
@@ -0,0 +1,278 @@
+//! BIP32 hierarchical deterministic keys, with BIP44 paths for Tron.
+
+use std::fmt;
+use std::str::FromStr;
+
+use hmac::{Hmac, Mac, NewMac};
+use ripemd160::Ripemd160;
+use secp256k1::{PublicKey, SecretKey};
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::address::{b58encode_check, Address};
+use crate::error::Error;
+use crate::keypair::KeyPair;
+use crate::private::Private;
+
+/// SLIP-44 coin type of Tron.
+pub const TRON_COIN_TYPE: u32 = 195;
+
+const HARDENED_OFFSET: u32 = 0x8000_0000;
+const XPRV_VERSION: [u8; 4] = [0x04, 0x88, 0xad, 0xe4];
+
+/// A child number in a derivation path. Hardened if greater than or equal to 2^31.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChildNumber(u32);
+
+impl ChildNumber {
+    /// Normal child number.
+    pub fn normal(index: u32) -> Result<Self, Error> {
+        if index >= HARDENED_OFFSET {
+            return Err(Error::InvalidDerivationPath);
+        }
+        Ok(ChildNumber(index))
+    }
+
+    /// Hardened child number.
+    pub fn hardened(index: u32) -> Result<Self, Error> {
+        if index >= HARDENED_OFFSET {
+            return Err(Error::InvalidDerivationPath);
+        }
+        Ok(ChildNumber(index | HARDENED_OFFSET))
+    }
+
+    /// Is a hardened child number.
+    pub fn is_hardened(&self) -> bool {
+        self.0 >= HARDENED_OFFSET
+    }
+
+    /// The index, without the hardened bit.
+    pub fn index(&self) -> u32 {
+        self.0 & !HARDENED_OFFSET
+    }
+}
+
+impl fmt::Display for ChildNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_hardened() {
+            write!(f, "{}'", self.index())
+        } else {
+            write!(f, "{}", self.index())
+        }
+    }
+}
+
+impl FromStr for ChildNumber {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        if let Some(idx) = s.strip_suffix('\'').or_else(|| s.strip_suffix('h')) {
+            ChildNumber::hardened(idx.parse().map_err(|_| Error::InvalidDerivationPath)?)
+        } else {
+            ChildNumber::normal(s.parse().map_err(|_| Error::InvalidDerivationPath)?)
+        }
+    }
+}
+
+/// A derivation path, like `m/44'/195'/0'/0/0`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DerivationPath(Vec<ChildNumber>);
+
+impl DerivationPath {
+    /// BIP44 path of Tron, `m/44'/195'/account'/0/index`.
+    pub fn tron(account: u32, index: u32) -> Result<Self, Error> {
+        Ok(DerivationPath(vec![
+            ChildNumber::hardened(44)?,
+            ChildNumber::hardened(TRON_COIN_TYPE)?,
+            ChildNumber::hardened(account)?,
+            ChildNumber::normal(0)?,
+            ChildNumber::normal(index)?,
+        ]))
+    }
+
+    /// Child numbers of the path.
+    pub fn as_slice(&self) -> &[ChildNumber] {
+        &self.0
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("m")?;
+        for child in &self.0 {
+            write!(f, "/{}", child)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut parts = s.split('/');
+        if parts.next() != Some("m") {
+            return Err(Error::InvalidDerivationPath);
+        }
+        parts.map(str::parse).collect::<Result<_, _>>().map(DerivationPath)
+    }
+}
+
+/// A BIP32 extended private key.
+#[derive(Clone)]
+pub struct ExtendedPrivate {
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: u32,
+    chain_code: [u8; 32],
+    secret_key: SecretKey,
+}
+
+impl ExtendedPrivate {
+    /// Master key from a seed, of 16 to 64 bytes.
+    pub fn from_seed(seed: &[u8]) -> Result<Self, Error> {
+        if seed.len() < 16 || seed.len() > 64 {
+            return Err(Error::InvalidPrivate);
+        }
+        let (key, chain_code) = hmac_sha512(b"Bitcoin seed", &[seed]);
+        Ok(ExtendedPrivate {
+            depth: 0,
+            parent_fingerprint: [0; 4],
+            child_number: 0,
+            chain_code,
+            secret_key: SecretKey::parse(&key)?,
+        })
+    }
+
+    /// Derive a child key.
+    pub fn derive_child(&self, child: ChildNumber) -> Result<Self, Error> {
+        let public_key = PublicKey::from_secret_key(&self.secret_key).serialize_compressed();
+        let (tweak, chain_code) = if child.is_hardened() {
+            hmac_sha512(
+                &self.chain_code,
+                &[&[0], &self.secret_key.serialize(), &child.0.to_be_bytes()],
+            )
+        } else {
+            hmac_sha512(&self.chain_code, &[&public_key, &child.0.to_be_bytes()])
+        };
+
+        // Invalid keys are astronomically unlikely, BIP32 says to proceed with the next index.
+        let mut secret_key = self.secret_key.clone();
+        secret_key.tweak_add_assign(&SecretKey::parse(&tweak)?)?;
+
+        let mut parent_fingerprint = [0u8; 4];
+        parent_fingerprint.copy_from_slice(&Ripemd160::digest(&Sha256::digest(&public_key))[..4]);
+
+        Ok(ExtendedPrivate {
+            depth: self.depth.checked_add(1).ok_or(Error::InvalidDerivationPath)?,
+            parent_fingerprint,
+            child_number: child.0,
+            chain_code,
+            secret_key,
+        })
+    }
+
+    /// Derive a descendant key by path, relative to this key.
+    pub fn derive(&self, path: &DerivationPath) -> Result<Self, Error> {
+        path.as_slice()
+            .iter()
+            .try_fold(self.clone(), |key, &child| key.derive_child(child))
+    }
+
+    /// The private key.
+    pub fn private(&self) -> Private {
+        Private::from(self.secret_key.serialize())
+    }
+
+    /// The key pair.
+    pub fn key_pair(&self) -> KeyPair {
+        KeyPair::from_private(self.private()).expect("valid secret key")
+    }
+
+    /// The Tron address.
+    pub fn address(&self) -> Address {
+        Address::from_private(&self.private())
+    }
+}
+
+/// Serialized as a base58check `xprv` string.
+impl fmt::Display for ExtendedPrivate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut raw = Vec::with_capacity(78);
+        raw.extend_from_slice(&XPRV_VERSION);
+        raw.push(self.depth);
+        raw.extend_from_slice(&self.parent_fingerprint);
+        raw.extend_from_slice(&self.child_number.to_be_bytes());
+        raw.extend_from_slice(&self.chain_code);
+        raw.push(0);
+        raw.extend_from_slice(&self.secret_key.serialize());
+        b58encode_check(raw).fmt(f)
+    }
+}
+
+impl fmt::Debug for ExtendedPrivate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ExtendedPrivate")
+            .field("depth", &self.depth)
+            .field("child_number", &self.child_number)
+            .finish()
+    }
+}
+
+fn hmac_sha512(key: &[u8], data: &[&[u8]]) -> ([u8; 32], [u8; 32]) {
+    let mut mac = Hmac::<Sha512>::new_varkey(key).expect("any key length");
+    for chunk in data {
+        mac.update(chunk);
+    }
+    let result = mac.finalize().into_bytes();
+
+    let mut left = [0u8; 32];
+    let mut right = [0u8; 32];
+    left.copy_from_slice(&result[..32]);
+    right.copy_from_slice(&result[32..]);
+    (left, right)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mnemonic::Mnemonic;
+
+    #[test]
+    fn test_bip32_vector() {
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let master = ExtendedPrivate::from_seed(&seed).unwrap();
+        assert_eq!(
+            master.to_string(),
+            "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi"
+        );
+        let child = master.derive(&"m/0'".parse().unwrap()).unwrap();
+        assert_eq!(
+            child.to_string(),
+            "xprv9uHRZZhk6KAJC1avXpDAp4MDc3sQKNxDiPvvkX8Br5ngLNv1TxvUxt4cV1rGL5hj6KCesnDYUhd7oWgT11eZG7XnxHrnYeSvkzY7d2bhkJ7"
+        );
+        let child = master.derive(&"m/0'/1/2'/2/1000000000".parse().unwrap()).unwrap();
+        assert_eq!(
+            child.to_string(),
+            "xprvA41z7zogVVwxVSgdKUHDy1SKmdb533PjDz7J6N6mV6uS3ze1ai8FHa8kmHScGpWmj4WggLyQjgPie1rFSruoUihUZREPSL39UNdE3BBDu76"
+        );
+    }
+
+    #[test]
+    fn test_tron_derivation() {
+        let path = DerivationPath::tron(0, 0).unwrap();
+        assert_eq!(path.to_string(), "m/44'/195'/0'/0/0");
+        assert_eq!("m/44h/195h/0h/0/0".parse::<DerivationPath>().unwrap(), path);
+        assert!("44'/195'".parse::<DerivationPath>().is_err());
+        assert!("m/2147483648'".parse::<DerivationPath>().is_err());
+
+        let mnemonic = Mnemonic::from_phrase(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+        )
+        .unwrap();
+        let master = ExtendedPrivate::from_seed(&mnemonic.to_seed("")).unwrap();
+        assert_eq!(
+            master.derive(&path).unwrap().address().to_string(),
+            "TUEZSdKsoDHQMeZwihtdoBiN46zxhGWYdH"
+        );
+    }
+}
@@ -0,0 +1,373 @@
+//! Encrypted keystore files, in the Web3 Secret Storage Definition (version 3).
+//!
+//! See-also: https://github.com/ethereum/wiki/wiki/Web3-Secret-Storage-Definition
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use aes_ctr::cipher::generic_array::GenericArray;
+use aes_ctr::cipher::stream::{NewStreamCipher, SyncStreamCipher};
+use aes_ctr::Aes128Ctr;
+use hmac::Hmac;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sha3::{Digest, Keccak256};
+
+use crate::address::Address;
+use crate::error::Error;
+use crate::private::Private;
+
+const KEYSTORE_VERSION: u32 = 3;
+const CIPHER: &str = "aes-128-ctr";
+const DKLEN: u32 = 32;
+
+// Same as geth's StandardScryptN and StandardScryptP.
+const DEFAULT_SCRYPT_LOG_N: u8 = 18;
+const DEFAULT_SCRYPT_R: u32 = 8;
+const DEFAULT_SCRYPT_P: u32 = 1;
+
+// Ceilings of scrypt parameters from untrusted keystore files, each of them costs time or memory.
+const MAX_SCRYPT_LOG_N: u32 = 20;
+const MAX_SCRYPT_R: u32 = 32;
+const MAX_SCRYPT_P: u32 = 16;
+// Memory used by scrypt is `128 * n * r` bytes.
+const MAX_SCRYPT_MEMORY: u64 = 1 << 30;
+
+/// Key derivation function and its parameters.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum KdfParams {
+    /// scrypt.
+    Scrypt {
+        /// Derived key length.
+        dklen: u32,
+        /// CPU/memory cost, a power of 2.
+        n: u32,
+        /// Block size.
+        r: u32,
+        /// Parallelization.
+        p: u32,
+        /// Random salt.
+        #[serde(with = "hex_bytes")]
+        salt: Vec<u8>,
+    },
+    /// PBKDF2 with HMAC-SHA256.
+    Pbkdf2 {
+        /// Iteration count.
+        c: u32,
+        /// Derived key length.
+        dklen: u32,
+        /// Only "hmac-sha256" is supported.
+        prf: String,
+        /// Random salt.
+        #[serde(with = "hex_bytes")]
+        salt: Vec<u8>,
+    },
+}
+
+impl KdfParams {
+    /// scrypt with a random salt. `n` is `2^log_n`.
+    pub fn scrypt(log_n: u8, r: u32, p: u32) -> Self {
+        KdfParams::Scrypt {
+            dklen: DKLEN,
+            n: 1 << log_n,
+            r,
+            p,
+            salt: random_bytes(32),
+        }
+    }
+
+    /// PBKDF2-HMAC-SHA256 with a random salt.
+    pub fn pbkdf2(c: u32) -> Self {
+        KdfParams::Pbkdf2 {
+            c,
+            dklen: DKLEN,
+            prf: "hmac-sha256".into(),
+            salt: random_bytes(32),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            KdfParams::Scrypt { .. } => "scrypt",
+            KdfParams::Pbkdf2 { .. } => "pbkdf2",
+        }
+    }
+
+    fn derive_key(&self, password: &[u8]) -> Result<Vec<u8>, Error> {
+        match *self {
+            KdfParams::Scrypt {
+                dklen,
+                n,
+                r,
+                p,
+                ref salt,
+            } => {
+                if dklen != DKLEN || !n.is_power_of_two() {
+                    return Err(Error::InvalidKeystore);
+                }
+                if n.trailing_zeros() > MAX_SCRYPT_LOG_N ||
+                    r > MAX_SCRYPT_R ||
+                    p > MAX_SCRYPT_P ||
+                    128 * n as u64 * r as u64 > MAX_SCRYPT_MEMORY
+                {
+                    return Err(Error::InvalidKeystore);
+                }
+                let params =
+                    scrypt::ScryptParams::new(n.trailing_zeros() as u8, r, p).map_err(|_| Error::InvalidKeystore)?;
+                let mut key = vec![0u8; dklen as usize];
+                scrypt::scrypt(password, salt, &params, &mut key).map_err(|_| Error::InvalidKeystore)?;
+                Ok(key)
+            }
+            KdfParams::Pbkdf2 {
+                c,
+                dklen,
+                ref prf,
+                ref salt,
+            } => {
+                if dklen != DKLEN || prf != "hmac-sha256" {
+                    return Err(Error::InvalidKeystore);
+                }
+                let mut key = vec![0u8; dklen as usize];
+                pbkdf2::pbkdf2::<Hmac<Sha256>>(password, salt, c, &mut key);
+                Ok(key)
+            }
+        }
+    }
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams::scrypt(DEFAULT_SCRYPT_LOG_N, DEFAULT_SCRYPT_R, DEFAULT_SCRYPT_P)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct CipherParams {
+    #[serde(with = "hex_bytes")]
+    iv: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct CryptoJson {
+    cipher: String,
+    cipherparams: CipherParams,
+    #[serde(with = "hex_bytes")]
+    ciphertext: Vec<u8>,
+    kdf: String,
+    kdfparams: KdfParams,
+    #[serde(with = "hex_bytes")]
+    mac: Vec<u8>,
+}
+
+/// An encrypted private key.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct Keystore {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    address: Option<String>,
+    crypto: CryptoJson,
+    id: String,
+    version: u32,
+}
+
+impl Keystore {
+    /// Encrypt a private key with the default scrypt parameters.
+    pub fn encrypt(private: &Private, password: &[u8]) -> Result<Self, Error> {
+        Keystore::encrypt_with_kdf(private, password, KdfParams::default())
+    }
+
+    /// Encrypt a private key with the given key derivation function.
+    pub fn encrypt_with_kdf(private: &Private, password: &[u8], kdfparams: KdfParams) -> Result<Self, Error> {
+        let derived_key = kdfparams.derive_key(password)?;
+        let iv = random_bytes(16);
+
+        let mut ciphertext = private.as_bytes().to_vec();
+        aes_128_ctr(&derived_key[..16], &iv, &mut ciphertext);
+        let mac = keystore_mac(&derived_key, &ciphertext);
+
+        Ok(Keystore {
+            address: Some(Address::from_private(private).to_string()),
+            crypto: CryptoJson {
+                cipher: CIPHER.into(),
+                cipherparams: CipherParams { iv },
+                ciphertext,
+                kdf: kdfparams.name().into(),
+                kdfparams,
+                mac,
+            },
+            id: random_uuid(),
+            version: KEYSTORE_VERSION,
+        })
+    }
+
+    /// Decrypt the private key.
+    pub fn decrypt(&self, password: &[u8]) -> Result<Private, Error> {
+        let crypto = &self.crypto;
+        if self.version != KEYSTORE_VERSION ||
+            crypto.cipher != CIPHER ||
+            crypto.kdf != crypto.kdfparams.name() ||
+            crypto.cipherparams.iv.len() != 16
+        {
+            return Err(Error::InvalidKeystore);
+        }
+
+        let derived_key = crypto.kdfparams.derive_key(password)?;
+        if keystore_mac(&derived_key, &crypto.ciphertext) != crypto.mac {
+            return Err(Error::InvalidPassword);
+        }
+
+        let mut plaintext = crypto.ciphertext.clone();
+        aes_128_ctr(&derived_key[..16], &crypto.cipherparams.iv, &mut plaintext);
+        let private = Private::try_from(plaintext)?;
+
+        if let Some(ref address) = self.address {
+            if address.parse::<Address>()? != Address::from_private(&private) {
+                return Err(Error::InvalidKeystore);
+            }
+        }
+        Ok(private)
+    }
+
+    /// Address of the key, if recorded in the keystore.
+    pub fn address(&self) -> Option<Address> {
+        self.address.as_ref().and_then(|addr| addr.parse().ok())
+    }
+
+    /// UUID of the keystore.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Parse a keystore JSON.
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        serde_json::from_str(json).map_err(|_| Error::InvalidKeystore)
+    }
+
+    /// Serialize as keystore JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("serializable")
+    }
+}
+
+// Do not leak the ciphertext in logs.
+impl fmt::Debug for Keystore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Keystore")
+            .field("address", &self.address)
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
+fn aes_128_ctr(key: &[u8], iv: &[u8], data: &mut [u8]) {
+    let mut cipher = Aes128Ctr::new(GenericArray::from_slice(key), GenericArray::from_slice(iv));
+    cipher.apply_keystream(data);
+}
+
+fn keystore_mac(derived_key: &[u8], ciphertext: &[u8]) -> Vec<u8> {
+    let mut hasher = Keccak256::new();
+    hasher.update(&derived_key[16..32]);
+    hasher.update(ciphertext);
+    hasher.finalize().to_vec()
+}
+
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut raw = vec![0u8; len];
+    OsRng.fill_bytes(&mut raw);
+    raw
+}
+
+/// A random version 4 UUID.
+fn random_uuid() -> String {
+    let mut raw = random_bytes(16);
+    raw[6] = (raw[6] & 0x0f) | 0x40;
+    raw[8] = (raw[8] & 0x3f) | 0x80;
+    let raw = hex::encode(raw);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &raw[..8],
+        &raw[8..12],
+        &raw[12..16],
+        &raw[16..20],
+        &raw[20..]
+    )
+}
+
+mod hex_bytes {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(value))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        hex::decode(s.trim_start_matches("0x")).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keypair::KeyPair;
+
+    #[test]
+    fn test_keystore_vector() {
+        // From the Web3 Secret Storage Definition test vectors.
+        let json = r#"{
+            "crypto" : {
+                "cipher" : "aes-128-ctr",
+                "cipherparams" : {
+                    "iv" : "6087dab2f9fdbbfaddc31a909735c1e6"
+                },
+                "ciphertext" : "5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46",
+                "kdf" : "pbkdf2",
+                "kdfparams" : {
+                    "c" : 262144,
+                    "dklen" : 32,
+                    "prf" : "hmac-sha256",
+                    "salt" : "ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd"
+                },
+                "mac" : "517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9e51205e9b2"
+            },
+            "id" : "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+            "version" : 3
+        }"#;
+        let keystore = Keystore::from_json(json).unwrap();
+        assert_eq!(
+            keystore.decrypt(b"testpassword").unwrap().to_string(),
+            "7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d"
+        );
+        assert_eq!(keystore.decrypt(b"wrongpassword"), Err(Error::InvalidPassword));
+    }
+
+    #[test]
+    fn test_keystore_roundtrip() {
+        let kp = KeyPair::generate();
+        // Light scrypt parameters, for testing.
+        let keystore = Keystore::encrypt_with_kdf(kp.private(), b"opentron", KdfParams::scrypt(10, 8, 1)).unwrap();
+        assert_eq!(keystore.address(), Some(kp.address()));
+
+        let keystore = Keystore::from_json(&keystore.to_json()).unwrap();
+        assert_eq!(&keystore.decrypt(b"opentron").unwrap(), kp.private());
+    }
+
+    #[test]
+    fn test_scrypt_params_ceiling() {
+        let scrypt = |log_n: u8, r: u32, p: u32| KdfParams::Scrypt {
+            dklen: DKLEN,
+            n: 1 << log_n,
+            r,
+            p,
+            salt: vec![0u8; 32],
+        };
+        // Rejected before deriving, or the test would not finish.
+        assert_eq!(scrypt(31, 8, 1).derive_key(b""), Err(Error::InvalidKeystore));
+        assert_eq!(scrypt(10, 1 << 30, 1).derive_key(b""), Err(Error::InvalidKeystore));
+        assert_eq!(scrypt(10, 8, 1 << 30).derive_key(b""), Err(Error::InvalidKeystore));
+        assert_eq!(scrypt(20, 16, 1).derive_key(b""), Err(Error::InvalidKeystore));
+        assert!(scrypt(10, 8, 1).derive_key(b"").is_ok());
+    }
+}
@@ -4,14 +4,22 @@
 
 mod address;
 mod error;
+mod hd;
 mod keypair;
+mod keystore;
+mod message;
+mod mnemonic;
 mod private;
 mod public;
 mod signature;
 
 pub use address::{b58decode_check, b58encode_check, Address};
 pub use error::Error;
+pub use hd::{ChildNumber, DerivationPath, ExtendedPrivate, TRON_COIN_TYPE};
 pub use keypair::KeyPair;
+pub use keystore::{KdfParams, Keystore};
+pub use message::{hash_message, hash_message_v1};
+pub use mnemonic::Mnemonic;
 pub use private::Private;
 pub use public::Public;
 pub use signature::Signature;
@@ -0,0 +1,57 @@
+//! TIP-191 signed messages.
+//!
+//! See-also: https://github.com/tronprotocol/tips/blob/master/tip-191.md
+
+use sha3::{Digest, Keccak256};
+
+const TRON_MESSAGE_PREFIX: &[u8] = b"\x19TRON Signed Message:\n";
+
+/// Digest of a message, as in TronWeb's `signMessageV2`.
+///
+/// `keccak256("\x19TRON Signed Message:\n" + len(message) + message)`
+pub fn hash_message(message: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(TRON_MESSAGE_PREFIX);
+    hasher.update(message.len().to_string().as_bytes());
+    hasher.update(message);
+    hasher.finalize().into()
+}
+
+/// Digest of a message, as in the legacy TronWeb `trx.sign` of a hex string.
+///
+/// `keccak256("\x19TRON Signed Message:\n32" + message)`, the length is always 32, whatever the message is.
+pub fn hash_message_v1(message: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(TRON_MESSAGE_PREFIX);
+    hasher.update(b"32");
+    hasher.update(message);
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Address, Private, Public};
+
+    #[test]
+    fn test_sign_and_recover_message() {
+        let priv_key: Private = "d705fc17c82942f85848ab522e42d986279028d09d12ad881bdc0e1327031976"
+            .parse()
+            .unwrap();
+        let address = Address::from_private(&priv_key);
+        let message = b"Hello, OpenTron!";
+
+        let sig = priv_key.sign_message(message).unwrap();
+        let public = Public::recover_message(message, &sig).unwrap();
+        assert_eq!(Address::from_public(&public), address);
+
+        let sig_v1 = priv_key.sign_message_v1(message).unwrap();
+        let public = Public::recover_message_v1(message, &sig_v1).unwrap();
+        assert_eq!(Address::from_public(&public), address);
+
+        // Schemes are not interchangeable.
+        assert_ne!(sig, sig_v1);
+        assert!(Public::recover_message_v1(message, &sig)
+            .map(|public| Address::from_public(&public) != address)
+            .unwrap_or(true));
+    }
+}
@@ -0,0 +1,188 @@
+//! BIP39 mnemonic code, English wordlist only.
+
+use std::fmt;
+use std::str::FromStr;
+
+use hmac::Hmac;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::{Digest, Sha256, Sha512};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::error::Error;
+
+const WORDLIST: &str = include_str!("wordlist/english.txt");
+const PBKDF2_ROUNDS: u32 = 2048;
+
+fn wordlist() -> Vec<&'static str> {
+    WORDLIST.lines().collect()
+}
+
+/// A BIP39 mnemonic phrase.
+#[derive(Clone, PartialEq)]
+pub struct Mnemonic {
+    phrase: String,
+    entropy: Vec<u8>,
+}
+
+impl Mnemonic {
+    /// Generates a random mnemonic of 12, 15, 18, 21 or 24 words.
+    pub fn generate(word_count: usize) -> Result<Self, Error> {
+        if !matches!(word_count, 12 | 15 | 18 | 21 | 24) {
+            return Err(Error::InvalidMnemonic);
+        }
+        let mut entropy = vec![0u8; word_count / 3 * 4];
+        OsRng.fill_bytes(&mut entropy);
+        Self::from_entropy(&entropy)
+    }
+
+    /// Mnemonic of 128 to 256 bits of entropy.
+    pub fn from_entropy(entropy: &[u8]) -> Result<Self, Error> {
+        if !matches!(entropy.len(), 16 | 20 | 24 | 28 | 32) {
+            return Err(Error::InvalidMnemonic);
+        }
+        let checksum = Sha256::digest(entropy);
+        let num_bits = entropy.len() * 8 + entropy.len() / 4;
+        let bit_at = |i: usize| {
+            let byte = if i < entropy.len() * 8 {
+                entropy[i / 8]
+            } else {
+                checksum[i / 8 - entropy.len()]
+            };
+            (byte >> (7 - i % 8)) & 1
+        };
+
+        let words = wordlist();
+        let phrase = (0..num_bits / 11)
+            .map(|n| {
+                let idx = (0..11).fold(0usize, |acc, i| (acc << 1) | bit_at(n * 11 + i) as usize);
+                words[idx]
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        Ok(Mnemonic {
+            phrase,
+            entropy: entropy.to_vec(),
+        })
+    }
+
+    /// Parse and validate a mnemonic phrase, including its checksum.
+    pub fn from_phrase(phrase: &str) -> Result<Self, Error> {
+        let phrase: String = phrase.nfkd().collect();
+        let words = wordlist();
+        let indices = phrase
+            .split_whitespace()
+            .map(|word| words.binary_search(&word).map_err(|_| Error::InvalidMnemonic))
+            .collect::<Result<Vec<_>, _>>()?;
+        if !matches!(indices.len(), 12 | 15 | 18 | 21 | 24) {
+            return Err(Error::InvalidMnemonic);
+        }
+
+        let num_bits = indices.len() * 11;
+        let mut entropy = vec![0u8; num_bits / 33 * 4];
+        let num_entropy_bits = entropy.len() * 8;
+        let mut checksum_bits = 0u8;
+        for i in 0..num_bits {
+            let bit = (indices[i / 11] >> (10 - i % 11)) & 1;
+            if i < num_entropy_bits {
+                entropy[i / 8] |= (bit as u8) << (7 - i % 8);
+            } else {
+                checksum_bits = (checksum_bits << 1) | bit as u8;
+            }
+        }
+
+        let num_checksum_bits = num_bits - num_entropy_bits;
+        if Sha256::digest(&entropy)[0] >> (8 - num_checksum_bits) != checksum_bits {
+            return Err(Error::InvalidMnemonic);
+        }
+        Self::from_entropy(&entropy)
+    }
+
+    /// The mnemonic phrase, words separated by a single space.
+    pub fn phrase(&self) -> &str {
+        &self.phrase
+    }
+
+    /// The entropy of the mnemonic.
+    pub fn entropy(&self) -> &[u8] {
+        &self.entropy
+    }
+
+    /// The 64-byte seed, for BIP32 master key generation.
+    pub fn to_seed(&self, passphrase: &str) -> [u8; 64] {
+        let salt = format!("mnemonic{}", passphrase).nfkd().collect::<String>();
+        let mut seed = [0u8; 64];
+        pbkdf2::pbkdf2::<Hmac<Sha512>>(self.phrase.as_bytes(), salt.as_bytes(), PBKDF2_ROUNDS, &mut seed);
+        seed
+    }
+}
+
+impl FromStr for Mnemonic {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Mnemonic::from_phrase(s)
+    }
+}
+
+impl fmt::Display for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.phrase.fmt(f)
+    }
+}
+
+// Do not leak the phrase in logs.
+impl fmt::Debug for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Mnemonic(***)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex::ToHex;
+
+    #[test]
+    fn test_mnemonic_vectors() {
+        // From https://github.com/trezor/python-mnemonic/blob/master/vectors.json
+        let vectors = [
+            (
+                "00000000000000000000000000000000",
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+                "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04",
+            ),
+            (
+                "7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+                "legal winner thank year wave sausage worth useful legal winner thank yellow",
+                "2e8905819b8723fe2c1d161860e5ee1830318dbf49a83bd451cfb8440c28bd6fa457fe1296106559a3c80937a1c1069be3a3a5bd381ee6260e8d9739fce1f607",
+            ),
+            (
+                "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+                "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo vote",
+                "dd48c104698c30cfe2b6142103248622fb7bb0ff692eebb00089b32d22484e1613912f0a5b694407be899ffd31ed3992c456cdf60f5d4564b8ba3f05a69890ad",
+            ),
+        ];
+        for &(entropy, phrase, seed) in &vectors {
+            let mnemonic = Mnemonic::from_entropy(&hex::decode(entropy).unwrap()).unwrap();
+            assert_eq!(mnemonic.phrase(), phrase);
+            assert_eq!(Mnemonic::from_phrase(phrase).unwrap(), mnemonic);
+            assert_eq!(mnemonic.to_seed("TREZOR").encode_hex::<String>(), seed);
+        }
+    }
+
+    #[test]
+    fn test_invalid_mnemonic() {
+        // bad checksum
+        assert!(Mnemonic::from_phrase(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon"
+        )
+        .is_err());
+        // not in wordlist
+        assert!(Mnemonic::from_phrase("abandon abandon abandon abandon abandon abandon opentron").is_err());
+
+        let mnemonic = Mnemonic::generate(24).unwrap();
+        assert_eq!(Mnemonic::from_phrase(mnemonic.phrase()).unwrap(), mnemonic);
+    }
+}
@@ -9,6 +9,7 @@ use secp256k1::{Message, SecretKey};
 use sha2::{Digest, Sha256};
 
 use crate::error::Error;
+use crate::message::{hash_message, hash_message_v1};
 use crate::signature::Signature;
 
 /// Private key of Secp256k1.
@@ -37,6 +38,18 @@ impl Private {
         self.sign_digest(&digest)
     }
 
+    /// Sign a message with TIP-191 prefix, as in TronWeb's `signMessageV2`.
+    pub fn sign_message(&self, message: &[u8]) -> Result<Signature, Error> {
+        self.sign_digest(&hash_message(message))
+    }
+
+    /// Sign a message with the legacy fixed-length TIP-191 prefix, as in TronWeb's `trx.sign`.
+    ///
+    /// `message` is the decoded bytes of the hex string.
+    pub fn sign_message_v1(&self, message: &[u8]) -> Result<Signature, Error> {
+        self.sign_digest(&hash_message_v1(message))
+    }
+
     /// As raw bytes.
     pub fn as_bytes(&self) -> &[u8] {
         &self.0[..]
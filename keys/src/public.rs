@@ -10,6 +10,7 @@ use secp256k1::{Message, PublicKey, PublicKeyFormat, RecoveryId, SecretKey};
 use sha2::{Digest, Sha256};
 
 use crate::error::Error;
+use crate::message::{hash_message, hash_message_v1};
 use crate::private::Private;
 use crate::signature::Signature;
 
@@ -58,6 +59,16 @@ impl Public {
         Public::recover_digest(&digest, signature)
     }
 
+    /// Recovers a public key from signature and a TIP-191 message, signed by `Private::sign_message`.
+    pub fn recover_message(message: &[u8], signature: &Signature) -> Result<Public, Error> {
+        Public::recover_digest(&hash_message(message), signature)
+    }
+
+    /// Recovers a public key from signature and a legacy TIP-191 message, signed by `Private::sign_message_v1`.
+    pub fn recover_message_v1(message: &[u8], signature: &Signature) -> Result<Public, Error> {
+        Public::recover_digest(&hash_message_v1(message), signature)
+    }
+
     /// Public key from private key.
     pub fn from_private(private: &Private) -> Result<Public, Error> {
         let secret_key = SecretKey::parse_slice(private.as_bytes())?;
@@ -136,10 +136,13 @@ impl BuiltinContractExecutorExt for contract_pb::AccountPermissionUpdateContract
             });
         }
 
+        // Active permission ids are counted from 2, in order.
         owner_acct.active_permissions = self
             .actives
             .iter()
-            .map(|perm| ActivePermission {
+            .enumerate()
+            .map(|(i, perm)| ActivePermission {
+                id: i as i32 + 2,
                 threshold: perm.threshold,
                 keys: perm
                     .keys
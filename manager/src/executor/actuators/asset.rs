@@ -215,7 +215,7 @@ impl BuiltinContractExecutorExt for contract_pb::AssetIssueContract {
             .put_key(keys::Account(owner_address), owner_acct)
             .map_err(|_| "db insert error")?;
 
-        // NOTE: `assetIssueID` of TransactionResult is not filled.
+        ctx.asset_created_token_id = token_id;
         Ok(TransactionResult::success())
     }
 
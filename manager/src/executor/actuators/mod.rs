@@ -18,7 +18,7 @@ mod exchange;
 mod proposal;
 mod resource;
 #[cfg(feature = "nile")]
-mod shielded;
+pub mod shielded;
 pub mod smart_contract;
 mod transfer;
 mod witness;
@@ -374,39 +374,45 @@ impl BuiltinContractExecutorExt for contract_pb::UnfreezeBalanceContract {
     }
 }
 
+/// Index the delegation, by from_address and by to_address.
 fn add_to_delegation_index(manager: &mut Manager, from: Address, to: Address) -> Result<(), String> {
-    let maybe_indexed_addrs = manager
-        .state_db
-        .get(&keys::ResourceDelegationIndex(from))
-        .map_err(|_| "db query error")?;
+    add_to_address_list(manager, keys::ResourceDelegationIndex(from), to)?;
+    add_to_address_list(manager, keys::ResourceDelegationReverseIndex(to), from)
+}
+
+fn remove_from_delegation_index(manager: &mut Manager, from: Address, to: Address) -> Result<(), String> {
+    remove_from_address_list(manager, keys::ResourceDelegationIndex(from), to)?;
+    remove_from_address_list(manager, keys::ResourceDelegationReverseIndex(to), from)
+}
+
+fn add_to_address_list<K: keys::Key<Vec<Address>>>(manager: &mut Manager, key: K, addr: Address) -> Result<(), String> {
+    let maybe_indexed_addrs = manager.state_db.get(&key).map_err(|_| "db query error")?;
     let mut indexed_addrs = maybe_indexed_addrs.unwrap_or_default();
-    if !indexed_addrs.contains(&to) {
-        indexed_addrs.push(to);
+    if !indexed_addrs.contains(&addr) {
+        indexed_addrs.push(addr);
         manager
             .state_db
-            .put_key(keys::ResourceDelegationIndex(from), indexed_addrs)
+            .put_key(key, indexed_addrs)
             .map_err(|_| "db insert error")?;
     }
     Ok(())
 }
 
-fn remove_from_delegation_index(manager: &mut Manager, from: Address, to: Address) -> Result<(), String> {
-    let maybe_indexed_addrs = manager
-        .state_db
-        .get(&keys::ResourceDelegationIndex(from))
-        .map_err(|_| "db query error")?;
+fn remove_from_address_list<K: keys::Key<Vec<Address>>>(
+    manager: &mut Manager,
+    key: K,
+    addr: Address,
+) -> Result<(), String> {
+    let maybe_indexed_addrs = manager.state_db.get(&key).map_err(|_| "db query error")?;
     let indexed_addrs = maybe_indexed_addrs.unwrap_or_default();
-    let indexed_addrs: Vec<_> = indexed_addrs.into_iter().filter(|addr| addr != &to).collect();
+    let indexed_addrs: Vec<_> = indexed_addrs.into_iter().filter(|a| a != &addr).collect();
     if !indexed_addrs.is_empty() {
         manager
             .state_db
-            .put_key(keys::ResourceDelegationIndex(from), indexed_addrs)
+            .put_key(key, indexed_addrs)
             .map_err(|_| "db insert error")?;
     } else {
-        manager
-            .state_db
-            .delete_key(&keys::ResourceDelegationIndex(from))
-            .map_err(|_| "db delete eerror")?;
+        manager.state_db.delete_key(&key).map_err(|_| "db delete error")?;
     }
     Ok(())
 }
@@ -553,3 +559,94 @@ fn freeze_resource(
         .map_err(|_| "db insert error")?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource::{BandwidthUtil, EnergyUtil};
+    use crate::testing::{address_of, TestChainBuilder};
+
+    #[test]
+    fn test_delegation_index() {
+        let mut chain = TestChainBuilder::new().build();
+        let manager = &mut chain.manager;
+        manager.new_layer();
+        let (alice, bob, carol) = (address_of("alice"), address_of("bob"), address_of("carol"));
+        let index_of = |manager: &Manager, addr| manager.state_db.get(&keys::ResourceDelegationIndex(addr)).unwrap();
+        let reverse_index_of = |manager: &Manager, addr| {
+            manager
+                .state_db
+                .get(&keys::ResourceDelegationReverseIndex(addr))
+                .unwrap()
+        };
+
+        add_to_delegation_index(manager, alice, carol).unwrap();
+        add_to_delegation_index(manager, bob, carol).unwrap();
+        add_to_delegation_index(manager, alice, carol).unwrap();
+        assert_eq!(reverse_index_of(manager, carol), Some(vec![alice, bob]));
+        assert_eq!(index_of(manager, alice), Some(vec![carol]));
+
+        remove_from_delegation_index(manager, alice, carol).unwrap();
+        assert_eq!(reverse_index_of(manager, carol), Some(vec![bob]));
+        assert_eq!(index_of(manager, alice), None);
+    }
+
+    #[test]
+    fn test_backfill_delegation_reverse_index() {
+        let mut chain = TestChainBuilder::new().build();
+        let manager = &mut chain.manager;
+        let (alice, bob, carol) = (address_of("alice"), address_of("bob"), address_of("carol"));
+
+        // A state-db of version 1, without the reverse index.
+        manager.new_layer();
+        manager
+            .state_db
+            .put_key(keys::ResourceDelegationIndex(alice), vec![carol])
+            .unwrap();
+        manager
+            .state_db
+            .put_key(keys::ResourceDelegationIndex(bob), vec![carol])
+            .unwrap();
+        manager.state_db.put_key(keys::DynamicProperty::DbVersion, 1).unwrap();
+        manager.commit_current_layers();
+
+        let genesis_config = manager.genesis_config.clone();
+        let chain_config = manager.config.chain.clone();
+        manager.state_db.init_genesis(&genesis_config, &chain_config).unwrap();
+
+        let from_addrs = manager.state_db.must_get(&keys::ResourceDelegationReverseIndex(carol));
+        assert_eq!(from_addrs.len(), 2);
+        assert!(from_addrs.contains(&alice) && from_addrs.contains(&bob));
+        assert_eq!(manager.state_db.must_get(&keys::DynamicProperty::DbVersion), 2);
+    }
+
+    #[test]
+    fn test_resource_utils() {
+        let mut chain = TestChainBuilder::new().account("alice", 100_000_000).build();
+        let freeze = |resource: ResourceCode| contract_pb::FreezeBalanceContract {
+            owner_address: address_of("alice").as_bytes().to_vec(),
+            frozen_balance: 10_000_000,
+            frozen_duration: 3,
+            resource: resource as i32,
+            receiver_address: vec![],
+        };
+        let txns = vec![
+            chain.transaction("alice", &freeze(ResourceCode::Bandwidth)),
+            chain.transaction("alice", &freeze(ResourceCode::Energy)),
+        ];
+        chain.produce_block(txns).unwrap();
+
+        let acct = chain.account("alice").unwrap();
+        let bandwidth_util = BandwidthUtil::new(&chain.manager);
+        // The first transaction is paid by free bandwidth, the second by frozen bandwidth.
+        assert!(bandwidth_util.get_free_bandwidth_usage(&acct) > 0);
+        assert!(bandwidth_util.get_frozen_bandwidth_usage(&acct) > 0);
+        assert!(
+            bandwidth_util.calculate_global_bandwidth_limit(&acct) > bandwidth_util.get_frozen_bandwidth_usage(&acct)
+        );
+
+        let energy_util = EnergyUtil::new(&chain.manager);
+        assert_eq!(energy_util.get_energy_usage(&acct), 0);
+        assert!(energy_util.calculate_global_energy_limit(&acct) > 0);
+    }
+}
@@ -1,18 +1,24 @@
-//! Shielded transfer, only available in nile testnet.
-//! This is a dummy implementation.
+//! Shielded transfer of the TRZ token, only available in nile testnet.
 
+use std::collections::HashSet;
 use std::convert::TryFrom;
 
 use ::keys::Address;
-use log::warn;
+use chain::{IndexedBlock, IndexedTransaction};
+use crypto::sha256;
+use primitive_types::H256;
+use prost::Message;
 use proto::chain::transaction::Result as TransactionResult;
+use proto::chain::ContractType;
 use proto::contract as contract_pb;
 use proto::state::Account;
 use state::keys;
+use ztron::tree::SaplingCommitmentTree;
+use ztron::verifier::{verify_shielded_transfer, ReceiveDescription, SpendDescription};
 
-use crate::Manager;
 use super::super::TransactionContext;
 use super::BuiltinContractExecutorExt;
+use crate::Manager;
 
 /// The TRZ token id.
 const SHIELDED_TOKEN_ID: i64 = 1000016;
@@ -20,12 +26,112 @@ const SHIELDED_TOKEN_ID: i64 = 1000016;
 const SHIELDED_TRANSACTION_FEE: i64 = 10_000_000;
 const SHIELDED_TRANSACTION_CREATE_ACCOUNT_FEE: i64 = 10_000_000;
 
+const MAX_NUM_OF_SPEND_DESCRIPTIONS: usize = 1;
+const MAX_NUM_OF_RECEIVE_DESCRIPTIONS: usize = 2;
+
 impl BuiltinContractExecutorExt for contract_pb::ShieldedTransferContract {
     fn validate(&self, manager: &Manager, ctx: &mut TransactionContext) -> Result<(), String> {
+        let state_db = &manager.state_db;
+
+        let spends = decode_spend_descriptions(self)?;
+        let receives = decode_receive_descriptions(self)?;
+
+        if self.transparent_from_address.is_empty() && spends.is_empty() {
+            return Err("no sender".into());
+        }
+        if !self.transparent_from_address.is_empty() && !spends.is_empty() {
+            return Err("more than 1 senders".into());
+        }
+        // NOTE: A change output is required, even when sending to a transparent address.
+        if receives.is_empty() {
+            return Err("no receiver".into());
+        }
+        if spends.len() > MAX_NUM_OF_SPEND_DESCRIPTIONS {
+            return Err(format!(
+                "number of spend descriptions must be at most {}",
+                MAX_NUM_OF_SPEND_DESCRIPTIONS
+            ));
+        }
+        if receives.len() > MAX_NUM_OF_RECEIVE_DESCRIPTIONS {
+            return Err(format!(
+                "number of receive descriptions must be at most {}",
+                MAX_NUM_OF_RECEIVE_DESCRIPTIONS
+            ));
+        }
+
+        let fee = self.fee(manager);
+
         if !self.transparent_from_address.is_empty() {
-            let fee = self.fee(manager);
-            ctx.contract_fee = fee;
+            let from_addr = Address::try_from(&self.transparent_from_address).map_err(|_| "invalid from address")?;
+            if self.from_amount <= 0 {
+                return Err("from amount must be greater than 0".into());
+            }
+            let from_acct = state_db
+                .get(&keys::Account(from_addr))
+                .map_err(|_| "db query error")?
+                .ok_or("from account is not on chain")?;
+            if from_acct.token_balance.get(&SHIELDED_TOKEN_ID).copied().unwrap_or(0) < self.from_amount {
+                return Err("insufficient token balance".into());
+            }
+        } else if self.from_amount != 0 {
+            return Err("from amount must be 0 without transparent from address".into());
         }
+
+        if !self.transparent_to_address.is_empty() {
+            let to_addr = Address::try_from(&self.transparent_to_address).map_err(|_| "invalid to address")?;
+            if self.to_amount <= 0 {
+                return Err("to amount must be greater than 0".into());
+            }
+            if self.transparent_to_address == self.transparent_from_address {
+                return Err("cannot transfer to oneself".into());
+            }
+            if let Some(to_acct) = state_db.get(&keys::Account(to_addr)).map_err(|_| "db query error")? {
+                if to_acct
+                    .token_balance
+                    .get(&SHIELDED_TOKEN_ID)
+                    .copied()
+                    .unwrap_or(0)
+                    .checked_add(self.to_amount)
+                    .is_none()
+                {
+                    return Err("math overflow".into());
+                }
+            }
+        } else if self.to_amount != 0 {
+            return Err("to amount must be 0 without transparent to address".into());
+        }
+
+        let mut nullifiers = HashSet::new();
+        for spend in &spends {
+            if spend.nullifier.len() != 32 || spend.anchor.len() != 32 {
+                return Err("invalid spend description".into());
+            }
+            let nf = H256::from_slice(&spend.nullifier);
+            if !nullifiers.insert(nf) {
+                return Err("duplicated nullifier".into());
+            }
+            if state_db
+                .get(&keys::ShieldedNullifier(nf))
+                .map_err(|_| "db query error")?
+                .is_some()
+            {
+                return Err(format!("note already spent, nullifier={:?}", nf));
+            }
+            if state_db
+                .get(&keys::ShieldedAnchor(H256::from_slice(&spend.anchor)))
+                .map_err(|_| "db query error")?
+                .is_none()
+            {
+                return Err("anchor is not found in note commitment tree".into());
+            }
+        }
+        for receive in &receives {
+            if receive.note_commitment.len() != 32 {
+                return Err("invalid receive description".into());
+            }
+        }
+
+        ctx.contract_fee = fee;
         Ok(())
     }
 
@@ -37,32 +143,322 @@ impl BuiltinContractExecutorExt for contract_pb::ShieldedTransferContract {
             from_acct
                 .adjust_token_balance(SHIELDED_TOKEN_ID, -self.from_amount)
                 .unwrap();
-            manager
-                .add_token_to_blackhole(SHIELDED_TOKEN_ID, ctx.contract_fee)
-                .unwrap();
-            ctx.contract_fee = 0; // in TRZ, not TRX
-
             manager.state_db.put_key(keys::Account(from_addr), from_acct).unwrap();
-        } else if !self.transparent_to_address.is_empty() {
+        }
+        if !self.transparent_to_address.is_empty() {
             let to_addr = Address::try_from(&self.transparent_to_address).unwrap();
             let maybe_to_acct = manager.state_db.get(&keys::Account(to_addr)).unwrap();
             let mut to_acct = maybe_to_acct.unwrap_or_else(|| Account::new(manager.latest_block_timestamp()));
             to_acct.adjust_token_balance(SHIELDED_TOKEN_ID, self.to_amount).unwrap();
 
             manager.state_db.put_key(keys::Account(to_addr), to_acct).unwrap();
-        } else {
-            warn!("fake handling z to z shielded transfer");
         }
+
+        // Fee is paid from the shielded pool, in TRZ, not TRX.
+        manager
+            .add_token_to_blackhole(SHIELDED_TOKEN_ID, ctx.contract_fee)
+            .unwrap();
+        ctx.contract_fee = 0;
+
+        for spend in decode_spend_descriptions(self)? {
+            manager
+                .state_db
+                .put_key(
+                    keys::ShieldedNullifier(H256::from_slice(&spend.nullifier)),
+                    ctx.transaction_hash,
+                )
+                .map_err(|_| "db insert error")?;
+        }
+
+        let mut tree = manager
+            .state_db
+            .get(&keys::ShieldedCommitmentTree)
+            .map_err(|_| "db query error")?
+            .map(|raw| SaplingCommitmentTree::from_bytes(&raw).expect("corrupted note commitment tree"))
+            .unwrap_or_default();
+        for receive in decode_receive_descriptions(self)? {
+            let mut cm = [0u8; 32];
+            cm.copy_from_slice(&receive.note_commitment);
+            tree.append(cm).map_err(|_| "note commitment tree is full")?;
+        }
+        // NOTE: The tree root is saved as a valid anchor at the end of the block, in `save_anchor_of_block`.
+        manager
+            .state_db
+            .put_key(keys::ShieldedCommitmentTree, tree.to_bytes())
+            .map_err(|_| "db insert error")?;
+
         Ok(TransactionResult::success())
     }
 
     fn fee(&self, manager: &Manager) -> i64 {
         if !self.transparent_to_address.is_empty() {
-            let to_addr = Address::try_from(&self.transparent_to_address).unwrap();
-            if manager.state_db.get(&keys::Account(to_addr)).unwrap().is_none() {
-                return SHIELDED_TRANSACTION_CREATE_ACCOUNT_FEE;
+            if let Ok(to_addr) = Address::try_from(&self.transparent_to_address) {
+                if manager.state_db.get(&keys::Account(to_addr)).unwrap().is_none() {
+                    return SHIELDED_TRANSACTION_CREATE_ACCOUNT_FEE;
+                }
             }
         }
         SHIELDED_TRANSACTION_FEE
     }
 }
+
+/// Save the root of the note commitment tree as a valid anchor, at the end of a block with shielded transfers.
+///
+/// Same as java-tron, spends can only refer to tree roots of former blocks.
+pub fn save_anchor_of_block(manager: &mut Manager, block: &IndexedBlock) -> Result<(), String> {
+    let has_shielded_transfer = block.transactions.iter().any(|txn| {
+        txn.raw
+            .raw_data
+            .as_ref()
+            .and_then(|raw| raw.contract.as_ref())
+            .map(|cntr| cntr.r#type == ContractType::ShieldedTransferContract as i32)
+            .unwrap_or(false)
+    });
+    if has_shielded_transfer {
+        save_anchor(manager)?;
+    }
+    Ok(())
+}
+
+fn save_anchor(manager: &mut Manager) -> Result<(), String> {
+    if let Some(raw) = manager
+        .state_db
+        .get(&keys::ShieldedCommitmentTree)
+        .map_err(|_| "db query error")?
+    {
+        let tree = SaplingCommitmentTree::from_bytes(&raw).map_err(|_| "corrupted note commitment tree")?;
+        manager
+            .state_db
+            .put_key(keys::ShieldedAnchor(H256::from(tree.root())), tree.size() as i64)
+            .map_err(|_| "db insert error")?;
+    }
+    Ok(())
+}
+
+/// Verify zk-SNARK proofs and the binding signature of a validated shielded transfer.
+///
+/// `fee` is the shielded fee set by `validate`.
+pub fn validate_shielded_proofs(
+    cntr: &contract_pb::ShieldedTransferContract,
+    fee: i64,
+    sighash: &H256,
+) -> Result<(), String> {
+    let spends = decode_spend_descriptions(cntr)?;
+    let receives = decode_receive_descriptions(cntr)?;
+
+    // Net value leaving the shielded pool.
+    let value_balance = cntr
+        .to_amount
+        .checked_add(fee)
+        .and_then(|val| val.checked_sub(cntr.from_amount))
+        .ok_or("math overflow")?;
+
+    let spends: Vec<_> = spends
+        .iter()
+        .map(|spend| SpendDescription {
+            value_commitment: &spend.value_commitment,
+            anchor: &spend.anchor,
+            nullifier: &spend.nullifier,
+            rk: &spend.rk,
+            zkproof: &spend.zkproof,
+            spend_authority_signature: &spend.spend_authority_signature,
+        })
+        .collect();
+    let receives: Vec<_> = receives
+        .iter()
+        .map(|receive| ReceiveDescription {
+            value_commitment: &receive.value_commitment,
+            note_commitment: &receive.note_commitment,
+            epk: &receive.epk,
+            zkproof: &receive.zkproof,
+        })
+        .collect();
+
+    let mut raw_sighash = [0u8; 32];
+    raw_sighash.copy_from_slice(sighash.as_bytes());
+    verify_shielded_transfer(&spends, &receives, &cntr.binding_signature, value_balance, &raw_sighash)
+        .map_err(|e| format!("invalid shielded transfer: {}", e))
+}
+
+/// Hash signed by spend authority signatures and the binding signature.
+///
+/// Renamed: getShieldTransactionHashIgnoreTypeException
+pub fn shielded_transaction_sighash(txn: &IndexedTransaction) -> Result<H256, String> {
+    let mut raw = txn.raw.raw_data.clone().ok_or("malformed transaction")?;
+    let param = raw
+        .contract
+        .as_mut()
+        .and_then(|cntr| cntr.parameter.as_mut())
+        .ok_or("malformed transaction")?;
+
+    let mut cntr = contract_pb::ShieldedTransferContract::decode(&param.value[..]).map_err(|_| "invalid contract")?;
+    cntr.binding_signature.clear();
+    for raw_spend in cntr.spend_description.iter_mut() {
+        let mut spend =
+            contract_pb::SpendDescription::decode(&raw_spend[..]).map_err(|_| "invalid spend description")?;
+        spend.spend_authority_signature.clear();
+        raw_spend.clear();
+        spend.encode(raw_spend).unwrap();
+    }
+    param.value.clear();
+    cntr.encode(&mut param.value).unwrap();
+
+    let mut buf = sha256(SHIELDED_TOKEN_ID.to_string().as_bytes()).as_bytes().to_vec();
+    raw.encode(&mut buf).unwrap();
+    Ok(sha256(&buf))
+}
+
+fn decode_spend_descriptions(
+    cntr: &contract_pb::ShieldedTransferContract,
+) -> Result<Vec<contract_pb::SpendDescription>, String> {
+    cntr.spend_description
+        .iter()
+        .map(|raw| contract_pb::SpendDescription::decode(&raw[..]).map_err(|_| "invalid spend description".into()))
+        .collect()
+}
+
+fn decode_receive_descriptions(
+    cntr: &contract_pb::ShieldedTransferContract,
+) -> Result<Vec<contract_pb::ReceiveDescription>, String> {
+    cntr.receive_description
+        .iter()
+        .map(|raw| contract_pb::ReceiveDescription::decode(&raw[..]).map_err(|_| "invalid receive description".into()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{address_of, TestChainBuilder};
+    use chain::IndexedBlockHeader;
+
+    fn encode<M: Message>(msg: &M) -> Vec<u8> {
+        let mut raw = vec![];
+        msg.encode(&mut raw).unwrap();
+        raw
+    }
+
+    fn transfer(spends: &[(H256, H256)], note_commitments: &[H256]) -> contract_pb::ShieldedTransferContract {
+        contract_pb::ShieldedTransferContract {
+            spend_description: spends
+                .iter()
+                .map(|(nf, anchor)| {
+                    encode(&contract_pb::SpendDescription {
+                        nullifier: nf.as_bytes().to_vec(),
+                        anchor: anchor.as_bytes().to_vec(),
+                        ..Default::default()
+                    })
+                })
+                .collect(),
+            receive_description: note_commitments
+                .iter()
+                .map(|cm| {
+                    encode(&contract_pb::ReceiveDescription {
+                        note_commitment: cm.as_bytes().to_vec(),
+                        ..Default::default()
+                    })
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_validate_transparent_input() {
+        let mut chain = TestChainBuilder::new().account("alice", 0).build();
+        chain.manager.new_layer();
+        let mut acct = chain.account("alice").unwrap();
+        acct.token_balance.insert(SHIELDED_TOKEN_ID, 100_000_000);
+        chain
+            .manager
+            .state_db
+            .put_key(keys::Account(address_of("alice")), acct)
+            .unwrap();
+
+        let header = IndexedBlockHeader::dummy(1, 0);
+        let mut ctx = TransactionContext::dummy(&header);
+        let mut cntr = transfer(&[], &[H256::repeat_byte(1)]);
+        cntr.transparent_from_address = address_of("alice").as_bytes().to_vec();
+
+        cntr.from_amount = 200_000_000;
+        assert_eq!(
+            cntr.validate(&chain.manager, &mut ctx),
+            Err("insufficient token balance".into())
+        );
+
+        cntr.from_amount = 100_000_000;
+        assert_eq!(cntr.validate(&chain.manager, &mut ctx), Ok(()));
+        assert_eq!(ctx.contract_fee, SHIELDED_TRANSACTION_FEE);
+
+        let no_receiver = contract_pb::ShieldedTransferContract {
+            receive_description: vec![],
+            ..cntr.clone()
+        };
+        assert_eq!(
+            no_receiver.validate(&chain.manager, &mut ctx),
+            Err("no receiver".into())
+        );
+
+        let two_senders = contract_pb::ShieldedTransferContract {
+            spend_description: transfer(&[(H256::repeat_byte(2), H256::repeat_byte(3))], &[]).spend_description,
+            ..cntr
+        };
+        assert_eq!(
+            two_senders.validate(&chain.manager, &mut ctx),
+            Err("more than 1 senders".into())
+        );
+    }
+
+    #[test]
+    fn test_validate_anchor_and_nullifier() {
+        let mut chain = TestChainBuilder::new().build();
+        chain.manager.new_layer();
+        let header = IndexedBlockHeader::dummy(1, 0);
+        let mut ctx = TransactionContext::dummy(&header);
+
+        let mut tree = SaplingCommitmentTree::new();
+        tree.append([1u8; 32]).unwrap();
+        chain
+            .manager
+            .state_db
+            .put_key(keys::ShieldedCommitmentTree, tree.to_bytes())
+            .unwrap();
+        let nf = H256::repeat_byte(2);
+        let cntr = transfer(&[(nf, H256::from(tree.root()))], &[H256::repeat_byte(3)]);
+
+        // Roots are valid anchors only after the end of the block.
+        assert_eq!(
+            cntr.validate(&chain.manager, &mut ctx),
+            Err("anchor is not found in note commitment tree".into())
+        );
+        save_anchor(&mut chain.manager).unwrap();
+        assert_eq!(cntr.validate(&chain.manager, &mut ctx), Ok(()));
+
+        chain
+            .manager
+            .state_db
+            .put_key(keys::ShieldedNullifier(nf), H256::zero())
+            .unwrap();
+        assert_eq!(
+            cntr.validate(&chain.manager, &mut ctx),
+            Err(format!("note already spent, nullifier={:?}", nf))
+        );
+    }
+
+    #[test]
+    fn test_validate_shielded_proofs() {
+        let sighash = H256::zero();
+
+        let mut cntr = transfer(&[], &[H256::repeat_byte(1)]);
+        cntr.to_amount = i64::max_value();
+        assert_eq!(
+            validate_shielded_proofs(&cntr, SHIELDED_TRANSACTION_FEE, &sighash),
+            Err("math overflow".into())
+        );
+
+        // Malformed proofs and signatures.
+        cntr.to_amount = 0;
+        cntr.binding_signature = vec![0u8; 64];
+        assert!(validate_shielded_proofs(&cntr, SHIELDED_TRANSACTION_FEE, &sighash).is_err());
+    }
+}
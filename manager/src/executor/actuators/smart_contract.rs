@@ -241,6 +241,7 @@ impl BuiltinContractExecutorExt for contract_pb::CreateSmartContract {
         }
 
         if exit_reason.is_succeed() {
+            backend.apply_pending_operations()?;
             backend.apply(applies, logs, false);
             if allow_tvm_constantinople {
                 manager
@@ -490,6 +491,7 @@ impl BuiltinContractExecutorExt for contract_pb::TriggerSmartContract {
         let (applies, logs) = executor.deconstruct();
 
         if exit_reason.is_succeed() {
+            backend.apply_pending_operations()?;
             backend.apply(applies, logs, false);
         } else {
             drop(backend);
@@ -752,10 +754,14 @@ pub fn execute_smart_contract(
     let ret_val = rt.machine().return_value();
 
     let (applies, logs) = executor.deconstruct();
-    backend.apply(applies, logs, false);
+    let applied = backend.apply_pending_operations();
+    if applied.is_ok() {
+        backend.apply(applies, logs, false);
+    }
     drop(backend);
 
     manager.rollback_layers(1);
+    applied?;
 
     if !ret_val.is_empty() {
         debug!("return value: {:?}", hex::encode(&ret_val));
@@ -805,7 +811,7 @@ fn legacy_get_runtime_code(deploy_code: &[u8]) -> &[u8] {
     return &[0u8; 32];
 }
 
-fn generate_created_contract_address(txn_hash: &H256, owner_address: &Address) -> Address {
+pub(crate) fn generate_created_contract_address(txn_hash: &H256, owner_address: &Address) -> Address {
     use sha3::Digest;
 
     let mut hasher = sha3::Keccak256::new();
@@ -982,9 +988,18 @@ fn get_current_tvm_upgrade(manager: &Manager) -> TvmUpgrade {
             .state_db
             .must_get(&keys::ChainParameter::AllowTvmShieldedUpgrade) !=
             0,
-        stake: false,
-        istanbul: false,
-        asset_issue: false,
+        stake: manager
+            .state_db
+            .must_get(&keys::ChainParameter::AllowTvmStakeUpgrade) !=
+            0,
+        istanbul: manager
+            .state_db
+            .must_get(&keys::ChainParameter::AllowTvmIstanbulUpgrade) !=
+            0,
+        asset_issue: manager
+            .state_db
+            .must_get(&keys::ChainParameter::AllowTvmAssetIssueUpgrade) !=
+            0,
         multisig: manager.state_db.must_get(&keys::ChainParameter::AllowMultisig) != 0,
     }
 }
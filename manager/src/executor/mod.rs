@@ -36,6 +36,8 @@ pub struct TransactionContext<'a> {
     pub new_account_created: bool,
     pub withdrawal_amount: i64,
     pub unfrozen_amount: i64,
+    // Set by AssetIssueContract, or ASSETISSUE of TVM.
+    pub asset_created_token_id: i64,
     pub fee_limit: i64,
     pub energy: i64,
     pub energy_limit: i64,
@@ -62,6 +64,7 @@ impl<'a> TransactionContext<'a> {
             new_account_created: false,
             withdrawal_amount: 0,
             unfrozen_amount: 0,
+            asset_created_token_id: 0,
             fee_limit: transaction.raw.raw_data.as_ref().unwrap().fee_limit,
             // will be filled while validating
             energy: 0,
@@ -86,6 +89,7 @@ impl<'a> TransactionContext<'a> {
             new_account_created: false,
             withdrawal_amount: 0,
             unfrozen_amount: 0,
+            asset_created_token_id: 0,
             fee_limit: 1000_000_000,
             // will be filled while validating
             energy: 0,
@@ -115,6 +119,7 @@ impl From<TransactionContext<'_>> for TransactionReceipt {
                 contract_fee: ctx.contract_fee,
                 ..Default::default()
             }),
+            asset_created_token_id: ctx.asset_created_token_id,
             ..Default::default()
         };
 
@@ -144,6 +149,7 @@ impl ::std::fmt::Debug for TransactionContext<'_> {
             .field("multisig_fee", &self.multisig_fee)
             .field("withdrawal_amount", &self.withdrawal_amount)
             .field("unfrozen_amount", &self.unfrozen_amount)
+            .field("asset_created_token_id", &self.asset_created_token_id)
             .field("new_account_created", &self.new_account_created);
 
         // smart contract
@@ -448,7 +454,6 @@ impl<'m> TransactionExecutor<'m> {
                 check_transaction_result(&exec_result, &maybe_result);
 
                 debug!("context => {:?}", ctx);
-                // TODO: Fill TransactionReceipt with newly created asset token_id.
                 Ok(ctx.into())
             }
             ContractType::UpdateAssetContract => {
@@ -841,12 +846,17 @@ impl<'m> TransactionExecutor<'m> {
             ContractType::ShieldedTransferContract => {
                 let cntr = contract_pb::ShieldedTransferContract::from_any(cntr.parameter.as_ref().unwrap()).unwrap();
 
-                log::warn!("=> Shielded Transaction, use dummy implementation");
-                // NOTE: dummy implementation
+                debug!(
+                    "=> Shielded Transfer {} -> {}",
+                    b58encode_check(&cntr.transparent_from_address),
+                    b58encode_check(&cntr.transparent_to_address)
+                );
 
                 let mut ctx = TransactionContext::new(&block_header, &txn);
-                // cntr.validate_signature(permission_id, recover_addrs, self.manager, &mut ctx)?;
+                // NOTE: Shielded transaction is authorized by spend authority signatures, not by permission.
                 cntr.validate(self.manager, &mut ctx)?;
+                let sighash = actuators::shielded::shielded_transaction_sighash(&txn)?;
+                actuators::shielded::validate_shielded_proofs(&cntr, ctx.contract_fee, &sighash)?;
                 // NOTE: Shielded transaction won't consume bandwidth.
                 check_transaction_result(&cntr.execute(self.manager, &mut ctx)?, &maybe_result);
 
@@ -223,27 +223,7 @@ impl MaintenanceManager<'_> {
 
     // DposService.updateWitness
     fn update_witness_schedule(&mut self) {
-        let mut wit_sched: Vec<(Address, i64, u8)> = Vec::new();
-        {
-            let wit_sched = &mut wit_sched;
-            self.manager.state_db.for_each(move |key: &keys::Witness, value| {
-                wit_sched.push((key.0, value.vote_count, value.brokerage as u8));
-            });
-        }
-
-        // NOTE: This is different from java-tron. In OpenTron, raw address is used as final fallback sorting key.
-        wit_sched.sort_by_cached_key(|&(addr, vote_count, _)| {
-            (
-                vote_count,
-                java_bytestring_hash_code(addr.as_bytes()),
-                addr.as_bytes().to_vec(),
-            )
-        });
-        wit_sched.reverse();
-        if wit_sched.len() > constants::MAX_NUM_OF_STANDBY_WITNESSES {
-            let _ = wit_sched.split_off(constants::MAX_NUM_OF_STANDBY_WITNESSES);
-        }
-
+        let wit_sched = MaintenanceUtil::new(self.manager).compute_witness_schedule();
         self.manager.state_db.put_key(keys::WitnessSchedule, wit_sched).unwrap();
     }
 
@@ -279,6 +259,79 @@ impl MaintenanceManager<'_> {
     }
 }
 
+/// Util for querying witness schedules, without changing the state.
+pub struct MaintenanceUtil<'m> {
+    manager: &'m Manager,
+}
+
+impl MaintenanceUtil<'_> {
+    pub fn new<'a>(manager: &'a Manager) -> MaintenanceUtil<'a> {
+        MaintenanceUtil { manager }
+    }
+
+    /// Current witness schedule, `(address, vote_count, brokerage)` of standby witnesses, ordered by votes.
+    ///
+    /// Empty before the first maintenance, which is block #1.
+    pub fn current_witness_schedule(&self) -> Vec<(Address, i64, u8)> {
+        self.manager
+            .state_db
+            .get(&keys::WitnessSchedule)
+            .unwrap()
+            .unwrap_or_default()
+    }
+
+    /// Active witnesses of current schedule, the top 27 standby witnesses.
+    pub fn active_witnesses(&self) -> Vec<(Address, i64, u8)> {
+        let mut wit_sched = self.current_witness_schedule();
+        wit_sched.truncate(constants::MAX_NUM_OF_ACTIVE_WITNESSES);
+        wit_sched
+    }
+
+    /// Preview of the witness schedule after the next maintenance.
+    ///
+    /// Same as `do_maintenance`, witnesses are re-scheduled only if there are new votes in current epoch.
+    pub fn next_witness_schedule(&self) -> Vec<(Address, i64, u8)> {
+        let has_new_votes = self
+            .manager
+            .state_db
+            .get(&keys::DynamicProperty::HasNewVotesInCurrentEpoch)
+            .unwrap()
+            .unwrap_or(0) !=
+            0;
+        let wit_sched = self.current_witness_schedule();
+        if has_new_votes || wit_sched.is_empty() {
+            self.compute_witness_schedule()
+        } else {
+            wit_sched
+        }
+    }
+
+    /// Standby witnesses ordered by votes, from votes counted in the Witness store.
+    fn compute_witness_schedule(&self) -> Vec<(Address, i64, u8)> {
+        let mut wit_sched: Vec<(Address, i64, u8)> = Vec::new();
+        {
+            let wit_sched = &mut wit_sched;
+            self.manager.state_db.for_each(move |key: &keys::Witness, value| {
+                wit_sched.push((key.0, value.vote_count, value.brokerage as u8));
+            });
+        }
+
+        // NOTE: This is different from java-tron. In OpenTron, raw address is used as final fallback sorting key.
+        wit_sched.sort_by_cached_key(|&(addr, vote_count, _)| {
+            (
+                vote_count,
+                java_bytestring_hash_code(addr.as_bytes()),
+                addr.as_bytes().to_vec(),
+            )
+        });
+        wit_sched.reverse();
+        if wit_sched.len() > constants::MAX_NUM_OF_STANDBY_WITNESSES {
+            let _ = wit_sched.split_off(constants::MAX_NUM_OF_STANDBY_WITNESSES);
+        }
+        wit_sched
+    }
+}
+
 /// `hashCode()` for `com.google.protobuf.ByteString`.
 ///
 /// NOTE: This is a really bad design flaw in java-tron, and is still vulnerable.
@@ -319,7 +372,11 @@ fn java_bytestring_hash_code(bs: &[u8]) -> i32 {
 
 #[cfg(test)]
 mod tests {
+    use proto::common::Vote;
+    use proto::contract as contract_pb;
+
     use super::*;
+    use crate::testing::{address_of, TestChainBuilder};
 
     #[test]
     fn test_java_bytestring_hash_code() {
@@ -332,4 +389,49 @@ mod tests {
             -797585552
         );
     }
+
+    #[test]
+    fn test_next_witness_schedule() {
+        let mut chain = TestChainBuilder::new()
+            .witness("Alice")
+            .witness("Bob")
+            .account("carol", 100_000_000)
+            .build();
+        // The first block does the initial maintenance.
+        chain.produce_block(vec![]).unwrap();
+        let scheduled = |sched: Vec<(Address, i64, u8)>| sched.into_iter().map(|wit| wit.0).collect::<Vec<_>>();
+        let util = MaintenanceUtil::new(&chain.manager);
+        assert_eq!(
+            scheduled(util.active_witnesses()),
+            vec![address_of("Alice"), address_of("Bob")]
+        );
+
+        let freeze = contract_pb::FreezeBalanceContract {
+            owner_address: address_of("carol").as_bytes().to_vec(),
+            frozen_balance: 100_000_000,
+            frozen_duration: 3,
+            ..Default::default()
+        };
+        let vote = contract_pb::VoteWitnessContract {
+            owner_address: address_of("carol").as_bytes().to_vec(),
+            votes: vec![Vote {
+                vote_address: address_of("Bob").as_bytes().to_vec(),
+                vote_count: 2,
+            }],
+            ..Default::default()
+        };
+        let txns = vec![chain.transaction("carol", &freeze), chain.transaction("carol", &vote)];
+        chain.produce_block(txns).unwrap();
+
+        // Current schedule is unchanged until the next maintenance.
+        let util = MaintenanceUtil::new(&chain.manager);
+        assert_eq!(
+            scheduled(util.active_witnesses()),
+            vec![address_of("Alice"), address_of("Bob")]
+        );
+        assert_eq!(
+            scheduled(util.next_witness_schedule()),
+            vec![address_of("Bob"), address_of("Alice")]
+        );
+    }
 }
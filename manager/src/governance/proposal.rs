@@ -218,6 +218,11 @@ impl ProposalUtil<'_> {
                 self.require_version(BlockVersion::GreatVoyage4_1_0)?;
                 self.accept_true(value)
             }
+            AllowTvmStakeUpgrade | AllowTvmAssetIssueUpgrade => {
+                self.require_version(BlockVersion::GreatVoyage4_1_0)?;
+                self.require_proposal(ChainParameter::AllowTvmIstanbulUpgrade)?;
+                self.accept_true(value)
+            }
             AllowMarketTransaction => {
                 self.require_version(BlockVersion::GreatVoyage4_1_0)?;
                 self.accept_true(value)
@@ -3,7 +3,7 @@
 use ::keys::{b58encode_check, Address};
 use chain::{IndexedBlock, IndexedBlockHeader, IndexedTransaction};
 use chrono::Utc;
-use config::{Config, GenesisConfig};
+use config::{Config, GenesisConfig, StorageEngine};
 use log::{debug, info, trace, warn};
 use primitive_types::H256;
 use prost::Message;
@@ -11,16 +11,25 @@ use proto::state::TransactionReceipt;
 use state::db::StateDB;
 use state::keys;
 use std::convert::{TryFrom, TryInto};
+use std::path::Path;
+use std::sync::Arc;
 
 use self::executor::TransactionExecutor;
 use self::governance::maintenance::MaintenanceManager;
 use self::governance::proposal::ProposalController;
 use self::governance::reward::RewardController;
+use self::note_tree::NoteTreeFollower;
+use self::profiler::Profiler;
 use self::resource::EnergyProcessor;
 
 pub mod executor;
 pub mod governance;
+pub mod note_tree;
+mod parallel;
+pub mod profiler;
 pub mod resource;
+#[cfg(test)]
+mod testing;
 pub mod version_fork;
 pub mod vm;
 
@@ -38,25 +47,52 @@ fn new_error(msg: &str) -> Error {
 pub struct Manager {
     state_db: StateDB,
     genesis_block_timestamp: i64,
+    genesis_block_hash: H256,
     blackhole: Address,
     my_witness: Vec<u8>,
 
     block_energy_usage: i64,
-    // TaPoS check, size = 65536, 2MB.
-    ref_block_hashes: Vec<H256>,
-    config: Config,
-    genesis_config: GenesisConfig,
+    // TaPoS check, size = 65536, 2MB. Shared with snapshots.
+    ref_block_hashes: Arc<Vec<H256>>,
+    // Shared with snapshots.
+    config: Arc<Config>,
+    genesis_config: Arc<GenesisConfig>,
     maintenance_started_at: i64,
 
     layers: usize,
+    profiler: Option<Profiler>,
 }
 
 impl Manager {
     pub fn new(config: &Config, genesis_config: &GenesisConfig) -> Self {
-        let mut state_db = StateDB::new(&config.storage.state_data_dir);
+        let mut state_db = match config.storage.engine {
+            StorageEngine::RocksDB => StateDB::new(&config.storage.state_data_dir),
+            StorageEngine::Memory => StateDB::new_in_memory(),
+        };
 
         state_db.init_genesis(&genesis_config, &config.chain).unwrap();
+        Manager::with_state_db(state_db, config, genesis_config)
+    }
+
+    /// Open the StateDB of a running node as a RocksDB secondary, for serving read-only queries.
+    pub fn new_secondary<P: AsRef<Path>>(
+        config: &Config,
+        genesis_config: &GenesisConfig,
+        secondary_path: P,
+    ) -> Result<Self> {
+        let state_db = StateDB::new_secondary(&config.storage.state_data_dir, secondary_path)?;
+        if state_db.get(&keys::DynamicProperty::DbVersion)?.is_none() {
+            return Err(new_error("state-db is not inited by the primary"));
+        }
+        Ok(Manager::with_state_db(state_db, config, genesis_config))
+    }
+
+    fn with_state_db(state_db: StateDB, config: &Config, genesis_config: &GenesisConfig) -> Self {
         let genesis_block_timestamp = genesis_config.timestamp;
+        let genesis_block_hash = *genesis_config
+            .to_indexed_block()
+            .expect("malformed genesis config")
+            .hash();
 
         let blackhole = genesis_config
             .allocs
@@ -70,14 +106,16 @@ impl Manager {
         Manager {
             state_db,
             genesis_block_timestamp,
+            genesis_block_hash,
             blackhole,
             my_witness: vec![],
             block_energy_usage: 0,
-            ref_block_hashes: Vec::with_capacity(65536),
-            config: config.clone(),
-            genesis_config: genesis_config.clone(),
+            ref_block_hashes: Arc::new(Vec::with_capacity(65536)),
+            config: Arc::new(config.clone()),
+            genesis_config: Arc::new(genesis_config.clone()),
             maintenance_started_at: 0,
             layers: 0,
+            profiler: None,
         }
     }
 
@@ -85,14 +123,43 @@ impl Manager {
         &self.state_db
     }
 
+    /// Catch up with the primary, for a Manager opened by `new_secondary`. Ref blocks are updated by the caller.
+    pub fn catch_up_with_primary(&mut self) -> Result<()> {
+        self.state_db.catch_up_with_primary()
+    }
+
+    /// A detached Manager over a snapshot of the current state, for queries and constant calls.
+    ///
+    /// Changes are made to a scratch layer, and are never persisted.
+    pub fn snapshot(&self) -> Manager {
+        let mut manager = Manager {
+            state_db: self.state_db.snapshot(),
+            genesis_block_timestamp: self.genesis_block_timestamp,
+            genesis_block_hash: self.genesis_block_hash,
+            blackhole: self.blackhole,
+            my_witness: self.my_witness.clone(),
+            block_energy_usage: 0,
+            ref_block_hashes: self.ref_block_hashes.clone(),
+            config: self.config.clone(),
+            genesis_config: self.genesis_config.clone(),
+            maintenance_started_at: 0,
+            layers: 0,
+            profiler: None,
+        };
+        manager.new_layer();
+        manager
+    }
+
     pub fn init_ref_blocks(&mut self, hashes: Vec<H256>) {
         debug!("update num of ref_hashes => {:?}", hashes.len());
-        self.ref_block_hashes = hashes;
+        self.ref_block_hashes = Arc::new(hashes);
     }
 
-    fn update_ref_blocks(&mut self, new_hash: H256) {
-        if self.ref_block_hashes.len() < 65536 {
-            self.ref_block_hashes.push(new_hash);
+    pub fn update_ref_blocks(&mut self, new_hash: H256) {
+        // NOTE: Copied if shared with snapshots.
+        let ref_block_hashes = Arc::make_mut(&mut self.ref_block_hashes);
+        if ref_block_hashes.len() < 65536 {
+            ref_block_hashes.push(new_hash);
         } else {
             let ref_block_bytes = {
                 let mut raw = [0u8; 2];
@@ -100,7 +167,7 @@ impl Manager {
                 raw
             };
             let ref_slot_index = u16::from_be_bytes(ref_block_bytes) as usize;
-            self.ref_block_hashes[ref_slot_index] = new_hash;
+            ref_block_hashes[ref_slot_index] = new_hash;
         }
     }
 
@@ -196,11 +263,17 @@ impl Manager {
         self.new_layer();
 
         // . applyBlock = processBlock + updateFork
-        self.process_block(block)?;
+        if let Err(e) = self.process_block(block) {
+            self.rollback_layers(self.layers);
+            return Err(e);
+        }
 
         // NOTE: OpenTron use different logic to handle verson fork. So `updateFork` is removed.
         // And no need to updateFork.
         self.commit_current_layers();
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.num_of_blocks += 1;
+        }
 
         let elapsed = (Utc::now().timestamp_nanos() - started_at) as f64 / 1_000_000.0;
         if !block.transactions.is_empty() {
@@ -238,16 +311,28 @@ impl Manager {
 
         // 3. Execute Transaction, TransactionRet / TransactionReceipt
         // TODO: handle accountState - AccountStateCallBack
-        for (txn, recovered_addrs) in block.transactions.iter().zip(recovered_owners.into_iter()) {
-            debug!(
-                "transaction => {:?} at block #{} v{}",
-                txn.hash,
-                block.number(),
-                block.version()
-            );
-            self.process_transaction(&txn, recovered_addrs, block)?;
+        // NOTE: Transactions are profiled only when executed sequentially.
+        if self.config.chain.parallel_execution && self.profiler.is_none() && block.transactions.len() > 1 {
+            self.process_transactions_in_parallel(block, recovered_owners)?;
+        } else {
+            for (txn, recovered_addrs) in block.transactions.iter().zip(recovered_owners.into_iter()) {
+                debug!(
+                    "transaction => {:?} at block #{} v{}",
+                    txn.hash,
+                    block.number(),
+                    block.version()
+                );
+                self.process_transaction(&txn, recovered_addrs, block)?;
+            }
         }
 
+        // Note commitment tree root of the block is a valid anchor for following blocks.
+        #[cfg(feature = "nile")]
+        executor::actuators::shielded::save_anchor_of_block(self, block).map_err(|e| new_error(&e))?;
+
+        // Followed shielded TRC20 note trees, with witnesses of watched notes.
+        NoteTreeFollower::new(self).apply_block(block)?;
+
         // 4. Adaptive energy processor:
         if self.block_energy_usage > 0 {
             if self.state_db.must_get(&keys::ChainParameter::AllowAdaptiveEnergy) != 0 {
@@ -315,7 +400,11 @@ impl Manager {
         // 6.cusumeMultiSigFee (NOTE: move to BandwidthProcessor)
 
         // 7. transaction is executed by TransactionTrace.
-        let txn_receipt = TransactionExecutor::new(self).execute(txn, recovered_addrs, &block.header)?;
+        let txn_receipt = if self.profiler.is_some() {
+            self.execute_transaction_with_profiler(txn, recovered_addrs, block)?
+        } else {
+            TransactionExecutor::new(self).execute(txn, recovered_addrs, &block.header)?
+        };
         self.state_db.put_key(keys::TransactionReceipt(txn.hash), txn_receipt)?;
         Ok(())
     }
@@ -345,6 +434,86 @@ impl Manager {
         Ok(maybe_receipt?)
     }
 
+    /// Validate a transaction to be packed into the next block, and dry run it.
+    pub fn validate_pending_transaction(&mut self, txn: &IndexedTransaction) -> Result<TransactionReceipt> {
+        if !self.validate_transaction_tapos(txn) {
+            return Err(new_error("tapos validation failed"));
+        }
+        if !self.valide_transaction_common(txn) {
+            return Err(new_error("message size or expiration validation failed"));
+        }
+        self.dry_run_transaction(txn)
+    }
+
+    /// Execute pending transactions one by one on the state of a new block, nothing is persisted.
+    ///
+    /// Returns transactions to be packed into the block, the skipped ones which failed in execution, and the
+    /// rejected ones which can never be packed(tapos, expiration or duplication), with errors. A transaction sees
+    /// changes of the accepted ones before it, so conflicting transactions are skipped, and might succeed later.
+    pub fn select_pending_transactions(
+        &mut self,
+        txns: Vec<IndexedTransaction>,
+        block_header: &IndexedBlockHeader,
+    ) -> (
+        Vec<IndexedTransaction>,
+        Vec<(IndexedTransaction, Error)>,
+        Vec<(IndexedTransaction, Error)>,
+    ) {
+        let old_layers = self.layers;
+        self.new_layer();
+
+        let mut accepted = vec![];
+        let mut skipped = vec![];
+        let mut rejected = vec![];
+        for txn in txns {
+            if let Err(e) = self.check_pending_transaction(&txn) {
+                rejected.push((txn, e));
+                continue;
+            }
+            let layers = self.layers;
+            self.new_layer();
+            match self.execute_pending_transaction(&txn, block_header) {
+                Ok(()) => {
+                    let writes = self.state_db.merged_top_layers(self.layers - layers);
+                    self.rollback_layers(self.layers - layers);
+                    self.state_db.write_to_last_layer(&writes).unwrap();
+                    accepted.push(txn);
+                }
+                Err(e) => {
+                    self.rollback_layers(self.layers - layers);
+                    skipped.push((txn, e));
+                }
+            }
+        }
+
+        self.rollback_layers(self.layers - old_layers);
+        (accepted, skipped, rejected)
+    }
+
+    /// Checks a pending transaction can never pass, against the state before it.
+    fn check_pending_transaction(&self, txn: &IndexedTransaction) -> Result<()> {
+        if !self.validate_transaction_tapos(txn) {
+            return Err(new_error("tapos validation failed"));
+        }
+        if !self.valide_transaction_common(txn) {
+            return Err(new_error("message size or expiration validation failed"));
+        }
+        if self.state_db.get(&keys::TransactionReceipt(txn.hash))?.is_some() {
+            return Err(new_error("duplicated transaction"));
+        }
+        Ok(())
+    }
+
+    fn execute_pending_transaction(
+        &mut self,
+        txn: &IndexedTransaction,
+        block_header: &IndexedBlockHeader,
+    ) -> Result<()> {
+        let txn_receipt = TransactionExecutor::new(self).execute(txn, txn.recover_owner()?, block_header)?;
+        self.state_db.put_key(keys::TransactionReceipt(txn.hash), txn_receipt)?;
+        Ok(())
+    }
+
     fn validate_transaction_tapos(&self, txn: &IndexedTransaction) -> bool {
         let ref_block_hash = &txn.raw.raw_data.as_ref().unwrap().ref_block_hash;
         let ref_block_bytes = {
@@ -384,6 +553,10 @@ impl Manager {
 
     // consensus.validBlock
     fn validate_block_schedule(&self, block: &IndexedBlock) -> Result<bool> {
+        // The only witness of the developer chain produces blocks at any time.
+        if self.config.chain.dev_mode {
+            return Ok(true);
+        }
         if self.state_db.get(&keys::DynamicProperty::LatestBlockNumber).unwrap() == Some(0) {
             return Ok(true);
         }
@@ -504,7 +677,7 @@ impl Manager {
             return self.genesis_block_timestamp + slot * constants::BLOCK_PRODUCING_INTERVAL;
         }
 
-        if self.is_latest_block_maintenance() {
+        if self.is_latest_block_maintenance() && !self.config.chain.dev_mode {
             slot += constants::NUM_OF_SKIPPED_SLOTS_IN_MAINTENANCE as i64;
         }
 
@@ -558,7 +731,7 @@ impl Manager {
     }
 
     #[inline]
-    fn latest_block_timestamp(&self) -> i64 {
+    pub fn latest_block_timestamp(&self) -> i64 {
         self.state_db.must_get(&keys::DynamicProperty::LatestBlockTimestamp)
     }
 
@@ -573,7 +746,7 @@ impl Manager {
     }
 
     #[inline]
-    fn latest_block_hash(&self) -> H256 {
+    pub fn latest_block_hash(&self) -> H256 {
         self.state_db.must_get(&keys::LatestBlockHash)
     }
 }
@@ -610,7 +783,8 @@ impl WitnessStatisticManager<'_> {
 
         self.manager.state_db.put_key(keys::Witness(wit_addr), wit).unwrap();
 
-        let slot = if block.number() != 1 {
+        // NOTE: No missed slots in the developer chain, blocks are produced on demand.
+        let slot = if block.number() != 1 && !self.manager.config.chain.dev_mode {
             self.manager.get_slot(block.timestamp())
         } else {
             1
@@ -649,3 +823,90 @@ impl WitnessStatisticManager<'_> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use proto::contract as contract_pb;
+
+    use super::*;
+    use crate::testing::{address_of, TestChainBuilder};
+
+    #[test]
+    fn test_state_snapshot() {
+        let mut chain = TestChainBuilder::new()
+            .account("alice", 100_000_000)
+            .account("bob", 0)
+            .build();
+
+        let snapshot = chain.manager.snapshot();
+        let transfer = contract_pb::TransferContract {
+            owner_address: address_of("alice").as_bytes().to_vec(),
+            to_address: address_of("bob").as_bytes().to_vec(),
+            amount: 1_000_000,
+        };
+        let txn = chain.transaction("alice", &transfer);
+        chain.produce_block(vec![txn.clone()]).unwrap();
+
+        // The snapshot is not affected by new blocks.
+        assert_eq!(snapshot.latest_block_number(), 0);
+        assert_eq!(snapshot.state().must_get(&keys::Account(address_of("bob"))).balance, 0);
+
+        // Layers are shared with the snapshot, and copied on write.
+        chain.manager.new_layer();
+        let snapshot = chain.manager.snapshot();
+        let bob = address_of("bob");
+        let mut acct = chain.account("bob").unwrap();
+        acct.balance = 0;
+        chain.manager.state_db.put_key(keys::Account(bob), acct).unwrap();
+        assert_eq!(snapshot.state().must_get(&keys::Account(bob)).balance, 1_000_000);
+        chain.manager.rollback_layers(1);
+
+        // Dry runs on a snapshot leave the manager unchanged.
+        let mut scratch = chain.manager.snapshot();
+        let txn = chain.transaction("alice", &transfer);
+        assert!(scratch.dry_run_transaction(&txn).unwrap().success);
+        assert_eq!(chain.account("bob").unwrap().balance, 1_000_000);
+    }
+
+    #[test]
+    fn test_select_pending_transactions() {
+        let mut chain = TestChainBuilder::new()
+            .account("alice", 100_000_000)
+            .account("bob", 0)
+            .build();
+        let transfer = |from: &str, to: &str, amount: i64| contract_pb::TransferContract {
+            owner_address: address_of(from).as_bytes().to_vec(),
+            to_address: address_of(to).as_bytes().to_vec(),
+            amount,
+        };
+
+        let first = chain.transaction("alice", &transfer("alice", "bob", 60_000_000));
+        let txns = vec![
+            first.clone(),
+            // valid against the head state, conflicts with the first one
+            chain.transaction("alice", &transfer("alice", "bob", 50_000_000)),
+            // depends on the first one
+            chain.transaction("bob", &transfer("bob", "alice", 10_000_000)),
+            first.clone(),
+        ];
+        let header = IndexedBlockHeader::dummy(
+            chain.manager.latest_block_number() + 1,
+            chain.manager.get_slot_timestamp(1),
+        );
+        let (accepted, skipped, rejected) = chain.manager.select_pending_transactions(txns.clone(), &header);
+        let hashes = |txns: &[IndexedTransaction]| txns.iter().map(|txn| txn.hash).collect::<Vec<_>>();
+        assert_eq!(hashes(&accepted), hashes(&[txns[0].clone(), txns[2].clone()]));
+        // The conflicting one might be packed later, the duplicated one never.
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].0.hash, txns[1].hash);
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].0.hash, txns[3].hash);
+        assert_eq!(rejected[0].1.to_string(), "duplicated transaction");
+        // Nothing is persisted.
+        assert_eq!(chain.account("alice").unwrap().balance, 100_000_000);
+
+        chain.produce_block(accepted).unwrap();
+        assert_eq!(chain.account("alice").unwrap().balance, 50_000_000);
+        assert_eq!(chain.account("bob").unwrap().balance, 50_000_000);
+    }
+}
@@ -0,0 +1,185 @@
+//! Follows note commitment trees of shielded TRC20 contracts, block by block.
+//!
+//! Trees are kept in the state db, so they are rolled back with the block layers on fork. A contract
+//! must be followed from its deployment, since the tree is rebuilt from its `NewLeaf` events.
+
+use std::collections::HashSet;
+
+use ::keys::Address;
+use chain::IndexedBlock;
+use log::warn;
+use primitive_types::H256;
+use state::keys;
+use ztron::scanner::{NoteScanner, ShieldedEvent};
+use ztron::tree::IncrementalNoteTree;
+
+use super::{Manager, Result};
+
+/// Max number of blocks with leaves that a followed tree can roll back by itself.
+pub const MAX_NOTE_TREE_ROLLBACK: usize = 32;
+
+/// Appends `NewLeaf` events of followed contracts to their note commitment trees.
+pub struct NoteTreeFollower<'m> {
+    manager: &'m mut Manager,
+    contracts: Vec<Address>,
+    ivks: Vec<Vec<u8>>,
+}
+
+impl NoteTreeFollower<'_> {
+    pub fn new<'a>(manager: &'a mut Manager) -> NoteTreeFollower<'a> {
+        let contracts = manager
+            .config
+            .ztron
+            .follow_contracts
+            .iter()
+            .filter_map(|raw| match raw.parse::<Address>() {
+                Ok(addr) => Some(addr),
+                Err(_) => {
+                    warn!("invalid shielded contract address to follow: {}", raw);
+                    None
+                }
+            })
+            .collect();
+        let ivks = manager
+            .config
+            .ztron
+            .watch_ivks
+            .iter()
+            .filter_map(|raw| match hex::decode(raw) {
+                Ok(ivk) if NoteScanner::from_ivk(&ivk).is_some() => Some(ivk),
+                _ => {
+                    warn!("invalid incoming viewing key to watch: {}", raw);
+                    None
+                }
+            })
+            .collect();
+
+        NoteTreeFollower {
+            manager,
+            contracts,
+            ivks,
+        }
+    }
+
+    pub fn apply_block(mut self, block: &IndexedBlock) -> Result<()> {
+        if self.contracts.is_empty() {
+            return Ok(());
+        }
+
+        let mut logs = vec![];
+        for txn in &block.transactions {
+            if let Some(receipt) = self.manager.state_db.get(&keys::TransactionReceipt(txn.hash))? {
+                logs.extend(receipt.vm_logs.into_iter().map(|log| (txn.hash, log)));
+            }
+        }
+
+        for contract in self.contracts.clone() {
+            let events: Vec<(H256, ShieldedEvent)> = logs
+                .iter()
+                .filter(|(_, log)| log.address == contract.as_bytes())
+                .filter_map(|(txid, log)| ShieldedEvent::from_log(&log.topics, &log.data).map(|ev| (*txid, ev)))
+                .collect();
+            let leaves: Vec<(u64, [u8; 32])> = events.iter().filter_map(|(_, ev)| ev.leaf()).collect();
+            if leaves.is_empty() {
+                continue;
+            }
+
+            let mut watched = HashSet::new();
+            for ivk in &self.ivks {
+                let mut scanner = NoteScanner::from_ivk(ivk).unwrap();
+                for (txid, event) in &events {
+                    scanner.scan_event(block.number(), txid.as_fixed_bytes(), event);
+                }
+                watched.extend(scanner.received_notes().iter().map(|note| note.position));
+            }
+
+            self.append_leaves(contract, block.number(), &leaves, &watched)?;
+        }
+        Ok(())
+    }
+
+    /// Append leaves of a block to the tree of `contract`. A mismatched tree is reported, not fatal to the block.
+    fn append_leaves(
+        &mut self,
+        contract: Address,
+        block_number: i64,
+        leaves: &[(u64, [u8; 32])],
+        watched: &HashSet<u64>,
+    ) -> Result<()> {
+        let mut tree = match get_note_tree(self.manager, contract)? {
+            Some(tree) => tree,
+            None => IncrementalNoteTree::new(MAX_NOTE_TREE_ROLLBACK),
+        };
+        if let Err(e) = tree.append_block(block_number, leaves, |pos| watched.contains(&pos)) {
+            warn!(
+                "can not follow note tree of {} at block #{}: {:?}",
+                contract, block_number, e
+            );
+            return Ok(());
+        }
+        self.manager
+            .state_db
+            .put_key(keys::ShieldedNoteTree(contract), tree.to_bytes())?;
+        Ok(())
+    }
+}
+
+/// Followed note commitment tree of a shielded TRC20 contract, with witnesses of watched notes.
+pub fn get_note_tree(manager: &Manager, contract: Address) -> Result<Option<IncrementalNoteTree>> {
+    match manager.state_db.get(&keys::ShieldedNoteTree(contract))? {
+        Some(raw) => Ok(Some(IncrementalNoteTree::from_bytes(&raw)?)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{address_of, TestChainBuilder};
+
+    fn leaf(i: u8) -> [u8; 32] {
+        let mut cm = [0u8; 32];
+        cm[0] = i;
+        cm
+    }
+
+    #[test]
+    fn test_note_tree_fork_rollback() {
+        let mut chain = TestChainBuilder::new().build();
+        let contract = address_of("contract");
+        let watched: HashSet<u64> = vec![1].into_iter().collect();
+
+        chain.manager.new_layer();
+        NoteTreeFollower::new(&mut chain.manager)
+            .append_leaves(contract, 1, &[(0, leaf(0)), (1, leaf(1))], &watched)
+            .unwrap();
+        let anchor_at_1 = get_note_tree(&chain.manager, contract).unwrap().unwrap().anchor();
+
+        chain.manager.new_layer();
+        NoteTreeFollower::new(&mut chain.manager)
+            .append_leaves(contract, 2, &[(2, leaf(2))], &watched)
+            .unwrap();
+        assert_eq!(get_note_tree(&chain.manager, contract).unwrap().unwrap().size(), 3);
+
+        // Switch to a fork of block #2.
+        chain.manager.rollback_layers(1);
+        let tree = get_note_tree(&chain.manager, contract).unwrap().unwrap();
+        assert_eq!(tree.latest_block_number(), 1);
+        assert_eq!(tree.anchor(), anchor_at_1);
+
+        chain.manager.new_layer();
+        NoteTreeFollower::new(&mut chain.manager)
+            .append_leaves(contract, 2, &[(2, leaf(22)), (3, leaf(23))], &watched)
+            .unwrap();
+        let tree = get_note_tree(&chain.manager, contract).unwrap().unwrap();
+        assert_eq!(tree.size(), 4);
+        assert_eq!(tree.watched_positions().collect::<Vec<_>>(), vec![1]);
+        assert!(tree.merkle_path(1).is_some());
+
+        // A gap in positions is reported and leaves the tree untouched.
+        NoteTreeFollower::new(&mut chain.manager)
+            .append_leaves(contract, 3, &[(5, leaf(5))], &watched)
+            .unwrap();
+        assert_eq!(get_note_tree(&chain.manager, contract).unwrap().unwrap().size(), 4);
+    }
+}
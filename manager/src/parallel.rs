@@ -0,0 +1,261 @@
+//! Optimistic parallel execution of transactions within a block.
+//!
+//! All transactions are executed in parallel, each against its own snapshot of the state before the block, with keys
+//! read recorded. Results are then committed in block order. A transaction which read any key written by former
+//! transactions of the block is re-executed, so the result is the same as sequential execution.
+
+use ::keys::Address;
+use chain::{IndexedBlock, IndexedTransaction};
+use log::debug;
+use rayon::prelude::*;
+use state::db::{OverlayWriteBatch, ReadSet};
+
+use super::{new_error, Manager, Result};
+
+/// Result of a transaction executed against a snapshot.
+struct OptimisticExecution {
+    // NOTE: Errors are converted to String, to be sent across threads.
+    result: Result<(), String>,
+    reads: ReadSet,
+    writes: OverlayWriteBatch,
+    energy_usage: i64,
+}
+
+impl Manager {
+    pub(crate) fn process_transactions_in_parallel(
+        &mut self,
+        block: &IndexedBlock,
+        recovered_owners: Vec<Result<Vec<Address>, ::keys::Error>>,
+    ) -> Result<()> {
+        let base = self.snapshot();
+        let executions: Vec<_> = block
+            .transactions
+            .par_iter()
+            .zip(recovered_owners.par_iter())
+            .map(|(txn, recovered_addrs)| base.execute_optimistically(txn, recovered_addrs.clone(), block))
+            .collect();
+
+        // Changes of transactions committed.
+        let mut written = OverlayWriteBatch::new();
+        let mut num_of_conflicts = 0;
+        for ((txn, recovered_addrs), execution) in block.transactions.iter().zip(recovered_owners).zip(executions) {
+            if execution.reads.conflicts_with(&written) {
+                num_of_conflicts += 1;
+                let old_layers = self.layers;
+                self.new_layer();
+                self.process_transaction(txn, recovered_addrs, block)?;
+                written.merge(&self.state_db.merged_top_layers(self.layers - old_layers));
+            } else {
+                execution.result.map_err(|e| new_error(&e))?;
+                self.state_db.write_to_last_layer(&execution.writes)?;
+                self.block_energy_usage += execution.energy_usage;
+                written.merge(&execution.writes);
+            }
+        }
+
+        debug!(
+            "block #{} executed in parallel, txns={} re-executed={}",
+            block.number(),
+            block.transactions.len(),
+            num_of_conflicts
+        );
+        Ok(())
+    }
+
+    fn execute_optimistically(
+        &self,
+        txn: &IndexedTransaction,
+        recovered_addrs: Result<Vec<Address>, ::keys::Error>,
+        block: &IndexedBlock,
+    ) -> OptimisticExecution {
+        let mut manager = self.snapshot();
+        manager.state_db.track_reads();
+        let result = manager
+            .process_transaction(txn, recovered_addrs, block)
+            .map_err(|e| e.to_string());
+
+        OptimisticExecution {
+            result,
+            reads: manager.state_db.take_reads(),
+            writes: manager.state_db.merged_top_layers(manager.layers),
+            energy_usage: manager.block_energy_usage,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proto::common::SmartContract;
+    use proto::contract as contract_pb;
+    use state::keys;
+
+    use crate::executor::actuators::smart_contract::generate_created_contract_address;
+    use crate::testing::{address_of, TestChain, TestChainBuilder};
+
+    const FEE_LIMIT: i64 = 100_000_000;
+
+    fn transfer(from: &str, to: &str, amount: i64) -> contract_pb::TransferContract {
+        contract_pb::TransferContract {
+            owner_address: address_of(from).as_bytes().to_vec(),
+            to_address: address_of(to).as_bytes().to_vec(),
+            amount,
+        }
+    }
+
+    #[test]
+    fn test_parallel_execution() {
+        let build = |parallel: bool| {
+            let mut builder = TestChainBuilder::new()
+                .account("alice", 100_000_000)
+                .account("bob", 0)
+                .account("carol", 100_000_000)
+                .account("dave", 0);
+            if parallel {
+                builder = builder.parallel_execution();
+            }
+            builder.build()
+        };
+        let mut sequential = build(false);
+        let mut parallel = build(true);
+        for chain in &mut [&mut sequential, &mut parallel] {
+            let txns = vec![
+                chain.transaction("alice", &transfer("alice", "bob", 1_000_000)),
+                chain.transaction("carol", &transfer("carol", "dave", 2_000_000)),
+                // depends on the first one
+                chain.transaction("bob", &transfer("bob", "dave", 500_000)),
+            ];
+            chain.produce_block(txns).unwrap();
+        }
+
+        for name in &["alice", "bob", "carol", "dave"] {
+            assert_eq!(sequential.account(name), parallel.account(name), "account {}", name);
+        }
+        assert_eq!(parallel.account("dave").unwrap().balance, 2_500_000);
+        assert_eq!(
+            sequential.manager.latest_block_hash(),
+            parallel.manager.latest_block_hash()
+        );
+
+        // Failure of a transaction fails the block, as sequential execution.
+        let txns = vec![
+            parallel.transaction("alice", &transfer("alice", "bob", 1_000_000)),
+            parallel.transaction("dave", &transfer("dave", "bob", 1_000_000_000)),
+        ];
+        assert!(parallel.produce_block(txns).is_err());
+        assert_eq!(parallel.dynamic_property(keys::DynamicProperty::LatestBlockNumber), 1);
+    }
+
+    /// Runtime code: increase the counter in slot 0, then STAKE the call value to `witness`.
+    fn staking_counter_code(witness: &str) -> Vec<u8> {
+        let mut code = vec![
+            0x60, 0x00, 0x54, // SLOAD(0)
+            0x60, 0x01, 0x01, // ADD(1)
+            0x60, 0x00, 0x55, // SSTORE(0)
+            0x34, // CALLVALUE
+            0x73, // PUSH20 witness
+        ];
+        code.extend_from_slice(address_of(witness).as_tvm_bytes());
+        code.extend_from_slice(&[
+            0xd5, // STAKE
+            0x50, // POP
+            0x00, // STOP
+        ]);
+        code
+    }
+
+    /// Deploy `runtime` by alice, returns the contract address.
+    fn deploy(chain: &mut TestChain, runtime: &[u8]) -> ::keys::Address {
+        // PUSH1 len, DUP1, CODECOPY(0, 11, len), RETURN(0, len)
+        let mut bytecode = vec![0x60, runtime.len() as u8];
+        bytecode.extend_from_slice(&[0x80, 0x60, 0x0b, 0x60, 0x00, 0x39, 0x60, 0x00, 0xf3]);
+        bytecode.extend_from_slice(runtime);
+        let create = contract_pb::CreateSmartContract {
+            owner_address: address_of("alice").as_bytes().to_vec(),
+            new_contract: Some(SmartContract {
+                origin_address: address_of("alice").as_bytes().to_vec(),
+                bytecode,
+                consume_user_energy_percent: 100,
+                origin_energy_limit: 10_000_000,
+                name: "StakingCounter".into(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let txn = chain.transaction_with_fee_limit("alice", &create, FEE_LIMIT);
+        chain.produce_block(vec![txn.clone()]).unwrap();
+        assert!(chain.receipt(&txn).unwrap().success);
+        generate_created_contract_address(&txn.hash, &address_of("alice"))
+    }
+
+    #[test]
+    fn test_parallel_execution_with_tvm() {
+        let build = |parallel: bool| {
+            let mut builder = TestChainBuilder::new()
+                .account("alice", 1_000_000_000)
+                .account("bob", 1_000_000_000)
+                .account("carol", 100_000_000)
+                .account("dave", 0)
+                .enable("allow-tvm")
+                .enable("allow-tvm-constantinople-upgrade")
+                .enable("allow-tvm-solidity-059-upgrade")
+                .enable("allow-tvm-istanbul-upgrade")
+                .enable("allow-tvm-stake-upgrade");
+            if parallel {
+                builder = builder.parallel_execution();
+            }
+            builder.build()
+        };
+        let trigger = |owner: &str, contract: ::keys::Address, call_value: i64| contract_pb::TriggerSmartContract {
+            owner_address: address_of(owner).as_bytes().to_vec(),
+            contract_address: contract.as_bytes().to_vec(),
+            call_value,
+            ..Default::default()
+        };
+
+        let mut sequential = build(false);
+        let mut parallel = build(true);
+        let mut contracts = vec![];
+        for chain in &mut [&mut sequential, &mut parallel] {
+            let contract = deploy(chain, &staking_counter_code("Witness"));
+            let txns = vec![
+                chain.transaction_with_fee_limit("alice", &trigger("alice", contract, 1_000_000), FEE_LIMIT),
+                chain.transaction("carol", &transfer("carol", "dave", 2_000_000)),
+                // reads the counter and the stake written by the first one, staking the call value
+                chain.transaction_with_fee_limit("bob", &trigger("bob", contract, 2_000_000), FEE_LIMIT),
+            ];
+            chain.produce_block(txns.clone()).unwrap();
+            for txn in &txns {
+                assert!(chain.receipt(txn).unwrap().success);
+            }
+
+            let cntr_acct = chain.manager.state_db.must_get(&keys::Account(contract));
+            assert_eq!(cntr_acct.frozen_amount_for_bandwidth, 2_000_000);
+            assert_eq!(cntr_acct.balance, 1_000_000);
+            let counter = chain
+                .manager
+                .state_db
+                .must_get(&keys::ContractStorage(contract, Default::default()));
+            assert_eq!(counter.to_low_u64_be(), 2);
+            contracts.push(contract);
+        }
+
+        // Same as sequential execution.
+        assert_eq!(contracts[0], contracts[1]);
+        let contract = contracts[0];
+        assert_eq!(
+            sequential.manager.state_db.get(&keys::Account(contract)).unwrap(),
+            parallel.manager.state_db.get(&keys::Account(contract)).unwrap()
+        );
+        assert_eq!(
+            sequential.manager.state_db.get(&keys::Votes(contract)).unwrap(),
+            parallel.manager.state_db.get(&keys::Votes(contract)).unwrap()
+        );
+        for name in &["alice", "bob", "carol", "dave"] {
+            assert_eq!(sequential.account(name), parallel.account(name), "account {}", name);
+        }
+        assert_eq!(
+            sequential.manager.latest_block_hash(),
+            parallel.manager.latest_block_hash()
+        );
+    }
+}
@@ -0,0 +1,273 @@
+//! Execution profiler, for finding hot paths when replaying blocks.
+//!
+//! Execution time, energy and StateDB reads/writes of each transaction are attributed to its contract type, and for
+//! smart contract calls and creations, to the contract address. Precompiles called and opcodes executed by TVM are
+//! profiled by `tvm`.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::Write;
+use std::hash::Hash;
+use std::time::Instant;
+
+use ::keys::Address;
+use chain::{IndexedBlock, IndexedTransaction};
+use primitive_types::H160;
+use proto::chain::ContractType;
+use proto::contract as contract_pb;
+use proto::state::TransactionReceipt;
+use tvm::profile::{OpcodeStat, PrecompileStat};
+
+use super::executor::actuators::smart_contract::generate_created_contract_address;
+use super::executor::actuators::BuiltinContractExt;
+use super::executor::TransactionExecutor;
+use super::Manager;
+
+/// Accumulated statistics of executions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stat {
+    pub count: u64,
+    pub elapsed_nanos: u64,
+    pub energy: i64,
+    pub reads: u64,
+    pub writes: u64,
+}
+
+impl Stat {
+    fn add(&mut self, other: &Stat) {
+        self.count += other.count;
+        self.elapsed_nanos += other.elapsed_nanos;
+        self.energy += other.energy;
+        self.reads += other.reads;
+        self.writes += other.writes;
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    pub num_of_blocks: u64,
+    pub contract_types: HashMap<ContractType, Stat>,
+    pub contracts: HashMap<Address, Stat>,
+    /// Precompiles, StateDB reads and writes are not tracked.
+    pub precompiles: HashMap<H160, Stat>,
+    /// Opcodes, only counts and time are tracked.
+    pub opcodes: HashMap<u8, Stat>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler::default()
+    }
+
+    pub(crate) fn record_transaction(&mut self, cntr_type: ContractType, contract: Option<Address>, stat: &Stat) {
+        self.contract_types.entry(cntr_type).or_default().add(stat);
+        if let Some(addr) = contract {
+            self.contracts.entry(addr).or_default().add(stat);
+        }
+    }
+
+    pub(crate) fn record_precompiles(&mut self, precompiles: HashMap<H160, PrecompileStat>) {
+        for (addr, precompile_stat) in precompiles {
+            self.precompiles.entry(addr).or_default().add(&Stat {
+                count: precompile_stat.count,
+                elapsed_nanos: precompile_stat.elapsed_nanos,
+                energy: precompile_stat.energy as i64,
+                ..Default::default()
+            });
+        }
+    }
+
+    pub(crate) fn record_opcodes(&mut self, opcodes: HashMap<u8, OpcodeStat>) {
+        for (opcode, opcode_stat) in opcodes {
+            self.opcodes.entry(opcode).or_default().add(&Stat {
+                count: opcode_stat.count,
+                elapsed_nanos: opcode_stat.elapsed_nanos,
+                ..Default::default()
+            });
+        }
+    }
+
+    /// Contract types, the most time consuming first.
+    pub fn top_contract_types(&self, n: usize) -> Vec<(ContractType, Stat)> {
+        top_n(&self.contract_types, n)
+    }
+
+    /// Smart contracts, the most time consuming first.
+    pub fn top_contracts(&self, n: usize) -> Vec<(Address, Stat)> {
+        top_n(&self.contracts, n)
+    }
+
+    /// Precompiles, the most time consuming first.
+    pub fn top_precompiles(&self, n: usize) -> Vec<(H160, Stat)> {
+        top_n(&self.precompiles, n)
+    }
+
+    /// Opcodes, the most time consuming first.
+    pub fn top_opcodes(&self, n: usize) -> Vec<(u8, Stat)> {
+        top_n(&self.opcodes, n)
+    }
+
+    /// A human readable top-N report.
+    pub fn report(&self, n: usize) -> String {
+        let mut out = String::new();
+        writeln!(out, "profiled blocks => {}", self.num_of_blocks).unwrap();
+
+        writeln!(out, "top contract types:").unwrap();
+        for (cntr_type, stat) in self.top_contract_types(n) {
+            write_stat(&mut out, &format!("{:?}", cntr_type), &stat);
+        }
+        writeln!(out, "top smart contracts:").unwrap();
+        for (addr, stat) in self.top_contracts(n) {
+            write_stat(&mut out, &addr.to_string(), &stat);
+        }
+        writeln!(out, "top precompiles:").unwrap();
+        for (addr, stat) in self.top_precompiles(n) {
+            write_stat(&mut out, &format!("{:#x}", addr.to_low_u64_be()), &stat);
+        }
+        writeln!(out, "top opcodes:").unwrap();
+        for (opcode, stat) in self.top_opcodes(n) {
+            write_stat(&mut out, &format!("{:#04x}", opcode), &stat);
+        }
+        out
+    }
+}
+
+impl Manager {
+    /// Start profiling transactions executed, until `take_profiler`.
+    pub fn enable_profiler(&mut self) {
+        if self.profiler.is_none() {
+            self.profiler = Some(Profiler::new());
+        }
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    /// Stop profiling, and return the profile.
+    pub fn take_profiler(&mut self) -> Option<Profiler> {
+        self.profiler.take()
+    }
+
+    pub(crate) fn execute_transaction_with_profiler(
+        &mut self,
+        txn: &IndexedTransaction,
+        recovered_addrs: Vec<Address>,
+        block: &IndexedBlock,
+    ) -> Result<TransactionReceipt, String> {
+        let cntr = txn.raw.raw_data.as_ref().unwrap().contract.as_ref().unwrap();
+        let cntr_type =
+            ContractType::from_i32(cntr.r#type).ok_or_else(|| format!("unknown contract type {}", cntr.r#type))?;
+
+        let reads = self.state_db.num_of_reads();
+        let writes = self.state_db.num_of_writes();
+        // NOTE: Precompiles and opcodes are profiled per thread, the executing thread might change between blocks.
+        tvm::profile::enable_precompile_profile();
+        tvm::profile::enable_opcode_profile();
+        let started_at = Instant::now();
+
+        let maybe_receipt = TransactionExecutor::new(self).execute(txn, recovered_addrs, &block.header);
+
+        let stat = Stat {
+            count: 1,
+            elapsed_nanos: started_at.elapsed().as_nanos() as u64,
+            energy: maybe_receipt
+                .as_ref()
+                .ok()
+                .and_then(|receipt| receipt.resource_receipt.as_ref())
+                .map(|r| r.energy)
+                .unwrap_or_default(),
+            reads: self.state_db.num_of_reads() - reads,
+            writes: self.state_db.num_of_writes() - writes,
+        };
+        let contract = match cntr_type {
+            ContractType::TriggerSmartContract => {
+                contract_pb::TriggerSmartContract::from_any(cntr.parameter.as_ref().unwrap())
+                    .and_then(|cntr| Address::try_from(&cntr.contract_address).ok())
+            }
+            ContractType::CreateSmartContract => {
+                contract_pb::CreateSmartContract::from_any(cntr.parameter.as_ref().unwrap())
+                    .and_then(|cntr| Address::try_from(&cntr.owner_address).ok())
+                    .map(|owner| generate_created_contract_address(&txn.hash, &owner))
+            }
+            _ => None,
+        };
+
+        let profiler = self.profiler.as_mut().expect("profiler enabled; qed");
+        profiler.record_transaction(cntr_type, contract, &stat);
+        profiler.record_precompiles(tvm::profile::take_precompile_profile());
+        profiler.record_opcodes(tvm::profile::take_opcode_profile());
+        tvm::profile::disable_precompile_profile();
+        tvm::profile::disable_opcode_profile();
+
+        maybe_receipt
+    }
+}
+
+fn top_n<K: Clone + Eq + Hash>(stats: &HashMap<K, Stat>, n: usize) -> Vec<(K, Stat)> {
+    let mut stats: Vec<_> = stats.iter().map(|(k, stat)| (k.clone(), *stat)).collect();
+    stats.sort_by(|(_, a), (_, b)| b.elapsed_nanos.cmp(&a.elapsed_nanos));
+    stats.truncate(n);
+    stats
+}
+
+fn write_stat(out: &mut String, name: &str, stat: &Stat) {
+    writeln!(
+        out,
+        "  {:<36} count={:<8} time={:.3}ms energy={:<12} reads={:<10} writes={}",
+        name,
+        stat.count,
+        stat.elapsed_nanos as f64 / 1_000_000.0,
+        stat.energy,
+        stat.reads,
+        stat.writes
+    )
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{address_of, TestChainBuilder};
+
+    #[test]
+    fn test_profiler() {
+        let mut chain = TestChainBuilder::new()
+            .account("alice", 100_000_000)
+            .account("bob", 0)
+            .parallel_execution()
+            .build();
+        let transfer = contract_pb::TransferContract {
+            owner_address: address_of("alice").as_bytes().to_vec(),
+            to_address: address_of("bob").as_bytes().to_vec(),
+            amount: 1_000_000,
+        };
+
+        let txn = chain.transaction("alice", &transfer);
+        chain.produce_block(vec![txn]).unwrap();
+
+        chain.manager.enable_profiler();
+        // Parallel execution is bypassed while profiling.
+        let another_transfer = contract_pb::TransferContract {
+            amount: 2_000_000,
+            ..transfer.clone()
+        };
+        let txns = vec![
+            chain.transaction("alice", &transfer),
+            chain.transaction("alice", &another_transfer),
+        ];
+        chain.produce_block(txns).unwrap();
+
+        let profiler = chain.manager.take_profiler().unwrap();
+        assert_eq!(profiler.num_of_blocks, 1);
+        let top = profiler.top_contract_types(10);
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].0, ContractType::TransferContract);
+        assert_eq!(top[0].1.count, 2);
+        assert!(top[0].1.reads > 0 && top[0].1.writes > 0);
+        assert!(profiler.contracts.is_empty());
+        assert!(profiler.opcodes.is_empty());
+        assert!(chain.manager.profiler().is_none());
+        assert_eq!(chain.account("bob").unwrap().balance, 4_000_000);
+    }
+}
@@ -412,34 +412,7 @@ impl<C: BuiltinContractExt> BandwidthProcessor<'_, C> {
 
     /// `calculateGlobalNetLimit`
     fn calculate_global_bandwidth_limit(&self, acct: &Account) -> i64 {
-        let amount_for_bw = acct.amount_for_bandwidth();
-        if amount_for_bw < 1_000_000 {
-            return 0;
-        }
-        let bw_weight = amount_for_bw / 1_000_000;
-        // NOTE: Although resource weight values update as new freeze and unfreeze transactions handled,
-        // new weight values is used when doing resource calculations of current block.
-        //
-        // Take block #43004 of mainnet as an example. This is an edge case with 3 transactions.
-        // First is a FreezeBalanceContract of 5_000_000_TRX, last one is a TransferContract all balance to create a
-        // new account(with 3 TRX frozen, enough BW to create account free of charge).
-        // Freezing so much TRX causes weight to increase, so bandwidth acquired from previous freezing is decreased.
-        //
-        // For better handling of this situation, block producer should reorder transactions.
-        let total_bw_limit = self
-            .manager
-            .state_db
-            .must_get(&keys::DynamicProperty::TotalBandwidthLimit);
-
-        let total_bw_weight = self
-            .manager
-            .state_db
-            .must_get(&keys::DynamicProperty::TotalBandwidthWeight);
-
-        if total_bw_weight == 0 {
-            return 0;
-        }
-        return (bw_weight as f64 * (total_bw_limit as f64 / total_bw_weight as f64)) as i64;
+        BandwidthUtil::new(self.manager).calculate_global_bandwidth_limit(acct)
     }
 }
 
@@ -710,17 +683,18 @@ impl EnergyUtil<'_> {
 
     // getAccountLeftEnergyFromFreeze
     pub fn get_left_frozen_energy(&self, acct: &Account) -> i64 {
-        let now = self.manager.get_head_slot();
-
-        let e_usage = acct.resource().energy_used;
-        let e_latest_slot = acct.resource().energy_latest_slot;
         let e_limit = self.calculate_global_energy_limit(acct);
-
-        let new_e_usage = adjust_usage(e_usage, 0, e_latest_slot, now);
+        let new_e_usage = self.get_energy_usage(acct);
 
         return (e_limit - new_e_usage).max(0);
     }
 
+    /// Energy usage at the head slot.
+    pub fn get_energy_usage(&self, acct: &Account) -> i64 {
+        let now = self.manager.get_head_slot();
+        adjust_usage(acct.resource().energy_used, 0, acct.resource().energy_latest_slot, now)
+    }
+
     // getOriginUsage
     pub fn get_origin_usage(&self, origin_acct: &Account, origin_energy_limit: i64, origin_usage: i64) -> i64 {
         let energy_left = self.get_left_frozen_energy(origin_acct);
@@ -754,3 +728,73 @@ impl EnergyUtil<'_> {
         (energy_weight as f64 * (total_energy_limit as f64 / total_energy_weight as f64)) as i64
     }
 }
+
+/// Util for calculating bandwidth, `getAccountNetMessage` of java-tron.
+pub struct BandwidthUtil<'a> {
+    manager: &'a Manager,
+}
+
+impl BandwidthUtil<'_> {
+    pub fn new<'a>(manager: &'a Manager) -> BandwidthUtil<'a> {
+        BandwidthUtil { manager }
+    }
+
+    /// Free bandwidth limit of each account.
+    pub fn get_free_bandwidth_limit(&self) -> i64 {
+        constants::FREE_BANDWIDTH
+    }
+
+    /// Free bandwidth usage at the head slot.
+    pub fn get_free_bandwidth_usage(&self, acct: &Account) -> i64 {
+        let now = self.manager.get_head_slot();
+        adjust_usage(
+            acct.resource().free_bandwidth_used,
+            0,
+            acct.resource().free_bandwidth_latest_slot,
+            now,
+        )
+    }
+
+    /// Frozen and delegated bandwidth usage at the head slot.
+    pub fn get_frozen_bandwidth_usage(&self, acct: &Account) -> i64 {
+        let now = self.manager.get_head_slot();
+        adjust_usage(
+            acct.resource().frozen_bandwidth_used,
+            0,
+            acct.resource().frozen_bandwidth_latest_slot,
+            now,
+        )
+    }
+
+    /// `calculateGlobalNetLimit`
+    pub fn calculate_global_bandwidth_limit(&self, acct: &Account) -> i64 {
+        let amount_for_bw = acct.amount_for_bandwidth();
+        if amount_for_bw < 1_000_000 {
+            return 0;
+        }
+        let bw_weight = amount_for_bw / 1_000_000;
+        // NOTE: Although resource weight values update as new freeze and unfreeze transactions handled,
+        // new weight values is used when doing resource calculations of current block.
+        //
+        // Take block #43004 of mainnet as an example. This is an edge case with 3 transactions.
+        // First is a FreezeBalanceContract of 5_000_000_TRX, last one is a TransferContract all balance to create a
+        // new account(with 3 TRX frozen, enough BW to create account free of charge).
+        // Freezing so much TRX causes weight to increase, so bandwidth acquired from previous freezing is decreased.
+        //
+        // For better handling of this situation, block producer should reorder transactions.
+        let total_bw_limit = self
+            .manager
+            .state_db
+            .must_get(&keys::DynamicProperty::TotalBandwidthLimit);
+
+        let total_bw_weight = self
+            .manager
+            .state_db
+            .must_get(&keys::DynamicProperty::TotalBandwidthWeight);
+
+        if total_bw_weight == 0 {
+            return 0;
+        }
+        return (bw_weight as f64 * (total_bw_limit as f64 / total_bw_weight as f64)) as i64;
+    }
+}
@@ -0,0 +1,564 @@
+//! Deterministic block-execution harness.
+//!
+//! A `TestChain` starts from a generated genesis in an in-memory StateDB. Blocks are constructed, signed by
+//! the scheduled witness, and applied via `Manager::push_block`, exactly as synced blocks are.
+//!
+//! Regression cases of builtin contracts, mostly derived from java-tron's actuator tests, are JSON fixtures
+//! under `manager/fixtures/`:
+//!
+//! ```json
+//! {
+//!   "description": "transfer to an existing account",
+//!   "witnesses": ["Witness"],
+//!   "enable": ["allow-multisig"],
+//!   "accounts": { "alice": 100000000, "bob": 0 },
+//!   "blocks": [
+//!     {
+//!       "transactions": [
+//!         {
+//!           "owner": "alice",
+//!           "contract": { "type": "TransferContract", "to": "bob", "amount": 1000000 },
+//!           "receipt": { "success": true, "fee": 0 }
+//!         }
+//!       ]
+//!     },
+//!     { "transactions": [...], "error": "cannot transfer to oneself" }
+//!   ],
+//!   "expect": {
+//!     "accounts": { "alice": { "balance": 99000000 }, "carol": null },
+//!     "dynamic_properties": { "LatestBlockNumber": 1 }
+//!   }
+//! }
+//! ```
+//!
+//! Accounts are referred by name, keys are derived from names. A block with `error` must be rejected, with
+//! the error message containing the given string, and leaves no change to the state.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use ::keys::{Address, KeyPair, Private};
+use chain::{BlockHeader, ContractParameter, IndexedBlock, IndexedBlockHeader, IndexedTransaction, TransactionBuilder};
+use config::genesis::{Alloc, Witness};
+use config::{Config, GenesisConfig, StorageEngine};
+use crypto::sha256;
+use proto::chain::block_header::Raw as BlockHeaderRaw;
+use proto::common::{ResourceCode, Vote};
+use proto::contract as contract_pb;
+use proto::state::{Account, TransactionReceipt};
+use serde::Deserialize;
+use state::keys;
+
+use super::{new_error, Manager, Result};
+
+const GENESIS_TIMESTAMP: i64 = 1_600_000_000_000;
+const FIXTURE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures");
+
+/// Deterministic key of a named account.
+pub fn key_of(name: &str) -> KeyPair {
+    KeyPair::from_private(Private::from(sha256(name.as_bytes()).to_fixed_bytes())).expect("valid private key")
+}
+
+pub fn address_of(name: &str) -> Address {
+    key_of(name).address()
+}
+
+pub struct TestChainBuilder {
+    config: Config,
+    witnesses: Vec<String>,
+    accounts: Vec<(String, i64)>,
+}
+
+impl TestChainBuilder {
+    pub fn new() -> Self {
+        let mut config = Config::load_from_str(include_str!("../../etc/conf.toml")).unwrap();
+        config.storage.engine = StorageEngine::Memory;
+        TestChainBuilder {
+            config,
+            witnesses: vec![],
+            accounts: vec![],
+        }
+    }
+
+    /// Add a genesis witness. Witnesses added earlier have more votes. Defaults to a single `Witness`.
+    pub fn witness(mut self, name: &str) -> Self {
+        self.witnesses.push(name.into());
+        self
+    }
+
+    /// Allocate balance to an account in genesis.
+    pub fn account(mut self, name: &str, balance: i64) -> Self {
+        self.accounts.push((name.into(), balance));
+        self
+    }
+
+    /// Execute transactions of a block in parallel.
+    pub fn parallel_execution(mut self) -> Self {
+        self.config.chain.parallel_execution = true;
+        self
+    }
+
+    /// Enable a chain parameter flag, like `allow-tvm`.
+    pub fn enable(mut self, flag: &str) -> Self {
+        self.config.chain.parameter.enable(flag).unwrap();
+        self
+    }
+
+    pub fn build(mut self) -> TestChain {
+        if self.witnesses.is_empty() {
+            self.witnesses.push("Witness".into());
+        }
+        let num_witnesses = self.witnesses.len();
+        let witnesses = self
+            .witnesses
+            .iter()
+            .enumerate()
+            .map(|(i, name)| Witness {
+                address: address_of(name).to_string(),
+                url: format!("http://{}.local", name.to_lowercase()),
+                votes: 100_000_000 + (num_witnesses - i) as i64,
+            })
+            .collect();
+        let allocs = self
+            .accounts
+            .iter()
+            .map(|(name, balance)| Alloc {
+                address: address_of(name).to_string(),
+                name: name.clone(),
+                balance: *balance,
+            })
+            .collect();
+        let genesis_config = GenesisConfig::new(GENESIS_TIMESTAMP, &[0u8; 32], witnesses, allocs);
+        let genesis_block = genesis_config.to_indexed_block().unwrap();
+
+        let mut manager = Manager::new(&self.config, &genesis_config);
+        manager.init_ref_blocks(vec![*genesis_block.hash()]);
+
+        TestChain {
+            manager,
+            witnesses: self.witnesses,
+            latest_header: genesis_block.header,
+        }
+    }
+}
+
+pub struct TestChain {
+    pub manager: Manager,
+    witnesses: Vec<String>,
+    latest_header: IndexedBlockHeader,
+}
+
+impl TestChain {
+    /// Build a transaction referring the latest block, signed by the named account.
+    pub fn transaction<C: ContractParameter>(&self, signer: &str, contract: &C) -> IndexedTransaction {
+        self.transaction_with_fee_limit(signer, contract, 0)
+    }
+
+    /// Build a transaction with the fee limit of TVM execution.
+    pub fn transaction_with_fee_limit<C: ContractParameter>(
+        &self,
+        signer: &str,
+        contract: &C,
+        fee_limit: i64,
+    ) -> IndexedTransaction {
+        TransactionBuilder::new(contract)
+            .ref_block(&self.latest_header)
+            .timestamp(self.latest_header.timestamp())
+            .fee_limit(fee_limit)
+            .sign(key_of(signer).private())
+            .unwrap()
+    }
+
+    /// Produce a block in the next slot, by the scheduled witness.
+    pub fn produce_block(&mut self, txns: Vec<IndexedTransaction>) -> Result<IndexedBlock> {
+        let timestamp = self.manager.get_slot_timestamp(1);
+        // WitnessSchedule is inited in the first maintenance cycle, which is block #1.
+        let witness = if self.manager.latest_block_number() == 0 {
+            address_of(&self.witnesses[0])
+        } else {
+            self.manager.get_scheduled_witness(self.manager.get_slot(timestamp))
+        };
+        let key = self
+            .witnesses
+            .iter()
+            .map(|name| key_of(name))
+            .find(|key| key.address() == witness)
+            .expect("scheduled witness is a genesis witness");
+
+        let raw_header = BlockHeaderRaw {
+            number: self.manager.latest_block_number() + 1,
+            timestamp,
+            parent_hash: self.manager.latest_block_hash().as_bytes().to_vec(),
+            witness_address: witness.as_bytes().to_vec(),
+            version: constants::CURRENT_BLOCK_VERSION as i32,
+            ..Default::default()
+        };
+        let header = BlockHeader {
+            raw_data: Some(raw_header),
+            ..Default::default()
+        };
+        let txns = txns.into_iter().map(|txn| txn.raw).collect();
+        let mut block =
+            IndexedBlock::from_raw_header_and_txns(header, txns).ok_or_else(|| new_error("invalid block"))?;
+        block.sign(key.private())?;
+
+        if !self.manager.push_block(&block)? {
+            return Err(new_error("block rejected"));
+        }
+        self.latest_header = block.header.clone();
+        Ok(block)
+    }
+
+    pub fn account(&self, name: &str) -> Option<Account> {
+        self.manager.state_db.get(&keys::Account(address_of(name))).unwrap()
+    }
+
+    pub fn receipt(&self, txn: &IndexedTransaction) -> Option<TransactionReceipt> {
+        self.manager.state_db.get(&keys::TransactionReceipt(txn.hash)).unwrap()
+    }
+
+    pub fn dynamic_property(&self, prop: keys::DynamicProperty) -> i64 {
+        self.manager.state_db.must_get(&prop)
+    }
+}
+
+// ! fixtures
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Fixture {
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    witnesses: Vec<String>,
+    #[serde(default)]
+    enable: Vec<String>,
+    accounts: BTreeMap<String, i64>,
+    blocks: Vec<FixtureBlock>,
+    #[serde(default)]
+    expect: FixtureExpect,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct FixtureBlock {
+    #[serde(default)]
+    transactions: Vec<FixtureTransaction>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct FixtureTransaction {
+    owner: String,
+    contract: FixtureContract,
+    receipt: Option<ReceiptExpect>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "UPPERCASE")]
+enum Resource {
+    Bandwidth,
+    Energy,
+}
+
+impl Default for Resource {
+    fn default() -> Self {
+        Resource::Bandwidth
+    }
+}
+
+impl From<Resource> for ResourceCode {
+    fn from(res: Resource) -> ResourceCode {
+        match res {
+            Resource::Bandwidth => ResourceCode::Bandwidth,
+            Resource::Energy => ResourceCode::Energy,
+        }
+    }
+}
+
+/// Builtin contracts, addresses are account names. Owner is filled from the transaction.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", deny_unknown_fields)]
+enum FixtureContract {
+    TransferContract {
+        to: String,
+        amount: i64,
+    },
+    AccountUpdateContract {
+        account_name: String,
+    },
+    FreezeBalanceContract {
+        frozen_balance: i64,
+        frozen_duration: i64,
+        #[serde(default)]
+        resource: Resource,
+        receiver: Option<String>,
+    },
+    UnfreezeBalanceContract {
+        #[serde(default)]
+        resource: Resource,
+        receiver: Option<String>,
+    },
+    VoteWitnessContract {
+        votes: BTreeMap<String, i64>,
+    },
+    WithdrawBalanceContract {},
+}
+
+impl FixtureContract {
+    fn to_transaction(&self, chain: &TestChain, owner: &str) -> IndexedTransaction {
+        let owner_address = address_of(owner).as_bytes().to_vec();
+        let receiver_address = |receiver: &Option<String>| {
+            receiver
+                .as_ref()
+                .map(|name| address_of(name).as_bytes().to_vec())
+                .unwrap_or_default()
+        };
+        match self {
+            FixtureContract::TransferContract { to, amount } => {
+                let cntr = contract_pb::TransferContract {
+                    owner_address,
+                    to_address: address_of(to).as_bytes().to_vec(),
+                    amount: *amount,
+                };
+                chain.transaction(owner, &cntr)
+            }
+            FixtureContract::AccountUpdateContract { account_name } => {
+                let cntr = contract_pb::AccountUpdateContract {
+                    owner_address,
+                    account_name: account_name.clone(),
+                };
+                chain.transaction(owner, &cntr)
+            }
+            FixtureContract::FreezeBalanceContract {
+                frozen_balance,
+                frozen_duration,
+                resource,
+                receiver,
+            } => {
+                let cntr = contract_pb::FreezeBalanceContract {
+                    owner_address,
+                    frozen_balance: *frozen_balance,
+                    frozen_duration: *frozen_duration,
+                    resource: ResourceCode::from(*resource) as i32,
+                    receiver_address: receiver_address(receiver),
+                };
+                chain.transaction(owner, &cntr)
+            }
+            FixtureContract::UnfreezeBalanceContract { resource, receiver } => {
+                let cntr = contract_pb::UnfreezeBalanceContract {
+                    owner_address,
+                    resource: ResourceCode::from(*resource) as i32,
+                    receiver_address: receiver_address(receiver),
+                };
+                chain.transaction(owner, &cntr)
+            }
+            FixtureContract::VoteWitnessContract { votes } => {
+                let cntr = contract_pb::VoteWitnessContract {
+                    owner_address,
+                    votes: votes
+                        .iter()
+                        .map(|(name, count)| Vote {
+                            vote_address: address_of(name).as_bytes().to_vec(),
+                            vote_count: *count,
+                        })
+                        .collect(),
+                    ..Default::default()
+                };
+                chain.transaction(owner, &cntr)
+            }
+            FixtureContract::WithdrawBalanceContract {} => {
+                let cntr = contract_pb::WithdrawBalanceContract { owner_address };
+                chain.transaction(owner, &cntr)
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ReceiptExpect {
+    success: Option<bool>,
+    fee: Option<i64>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct FixtureExpect {
+    /// `null` for accounts that must not exist.
+    #[serde(default)]
+    accounts: BTreeMap<String, Option<AccountExpect>>,
+    /// Keyed by variant names of `DynamicProperty`.
+    #[serde(default)]
+    dynamic_properties: BTreeMap<String, i64>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct AccountExpect {
+    name: Option<String>,
+    balance: Option<i64>,
+    allowance: Option<i64>,
+    frozen_amount_for_bandwidth: Option<i64>,
+    frozen_amount_for_energy: Option<i64>,
+}
+
+fn dynamic_property_of(name: &str) -> keys::DynamicProperty {
+    keys::DynamicProperty::default_properties()
+        .into_iter()
+        .map(|(prop, _)| prop)
+        .find(|prop| format!("{:?}", prop) == name)
+        .unwrap_or_else(|| panic!("unknown dynamic property {}", name))
+}
+
+fn run_fixture(path: &Path, parallel: bool) {
+    let name = path.file_stem().unwrap().to_str().unwrap();
+    let fixture: Fixture = serde_json::from_str(&fs::read_to_string(path).unwrap())
+        .unwrap_or_else(|e| panic!("malformed fixture {}: {}", name, e));
+    println!("fixture {} (parallel={}) => {}", name, parallel, fixture.description);
+
+    let mut builder = TestChainBuilder::new();
+    if parallel {
+        builder = builder.parallel_execution();
+    }
+    for witness in &fixture.witnesses {
+        builder = builder.witness(witness);
+    }
+    for flag in &fixture.enable {
+        builder = builder.enable(flag);
+    }
+    for (account, balance) in &fixture.accounts {
+        builder = builder.account(account, *balance);
+    }
+    let mut chain = builder.build();
+
+    for (i, block) in fixture.blocks.iter().enumerate() {
+        let txns: Vec<_> = block
+            .transactions
+            .iter()
+            .map(|txn| txn.contract.to_transaction(&chain, &txn.owner))
+            .collect();
+        let number_before = chain.manager.latest_block_number();
+        match (chain.produce_block(txns.clone()), &block.error) {
+            (Ok(_), None) => {}
+            (Ok(_), Some(expected)) => panic!("{}: block #{} should fail with {:?}", name, i + 1, expected),
+            (Err(e), Some(expected)) => {
+                assert!(
+                    e.to_string().contains(expected.as_str()),
+                    "{}: block #{} failed with {:?}, expected {:?}",
+                    name,
+                    i + 1,
+                    e.to_string(),
+                    expected
+                );
+                assert_eq!(chain.manager.latest_block_number(), number_before);
+                continue;
+            }
+            (Err(e), None) => panic!("{}: block #{} failed: {}", name, i + 1, e),
+        }
+
+        for (txn, expected) in txns.iter().zip(&block.transactions) {
+            if let Some(ReceiptExpect { success, fee }) = &expected.receipt {
+                let receipt = chain.receipt(txn).expect("receipt is saved");
+                if let Some(success) = success {
+                    assert_eq!(receipt.success, *success, "{}: success of {:?}", name, txn.hash);
+                }
+                if let Some(fee) = fee {
+                    assert_eq!(receipt.fee, *fee, "{}: fee of {:?}", name, txn.hash);
+                }
+            }
+        }
+    }
+
+    for (account, expected) in &fixture.expect.accounts {
+        match (chain.account(account), expected) {
+            (None, None) => {}
+            (Some(_), None) => panic!("{}: account {} should not exist", name, account),
+            (None, Some(_)) => panic!("{}: account {} not found", name, account),
+            (Some(acct), Some(expected)) => {
+                let fields = [
+                    ("balance", acct.balance, expected.balance),
+                    ("allowance", acct.allowance, expected.allowance),
+                    (
+                        "frozen_amount_for_bandwidth",
+                        acct.frozen_amount_for_bandwidth,
+                        expected.frozen_amount_for_bandwidth,
+                    ),
+                    (
+                        "frozen_amount_for_energy",
+                        acct.frozen_amount_for_energy,
+                        expected.frozen_amount_for_energy,
+                    ),
+                ];
+                for (field, actual, expected) in fields.iter() {
+                    if let Some(expected) = expected {
+                        assert_eq!(actual, expected, "{}: {} of {}", name, field, account);
+                    }
+                }
+                if let Some(account_name) = &expected.name {
+                    assert_eq!(&acct.name, account_name, "{}: name of {}", name, account);
+                }
+            }
+        }
+    }
+
+    for (prop, expected) in &fixture.expect.dynamic_properties {
+        assert_eq!(
+            chain.dynamic_property(dynamic_property_of(prop)),
+            *expected,
+            "{}: dynamic property {}",
+            name,
+            prop
+        );
+    }
+}
+
+#[test]
+fn test_fixtures() {
+    let mut paths: Vec<_> = fs::read_dir(FIXTURE_DIR)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map(|ext| ext == "json").unwrap_or(false))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no fixture found");
+    for path in paths {
+        run_fixture(&path, false);
+        run_fixture(&path, true);
+    }
+}
+
+#[test]
+fn test_produce_blocks() {
+    let mut chain = TestChainBuilder::new()
+        .account("alice", 100_000_000)
+        .account("bob", 0)
+        .build();
+
+    chain.produce_block(vec![]).unwrap();
+    let transfer = contract_pb::TransferContract {
+        owner_address: address_of("alice").as_bytes().to_vec(),
+        to_address: address_of("bob").as_bytes().to_vec(),
+        amount: 1_000_000,
+    };
+    let txn = chain.transaction("alice", &transfer);
+    let block = chain.produce_block(vec![txn.clone()]).unwrap();
+
+    assert_eq!(block.number(), 2);
+    assert_eq!(chain.dynamic_property(keys::DynamicProperty::LatestBlockNumber), 2);
+    assert!(chain.receipt(&txn).unwrap().success);
+    assert_eq!(chain.account("alice").unwrap().balance, 99_000_000);
+    assert_eq!(chain.account("bob").unwrap().balance, 1_000_000);
+
+    // An invalid transaction rolls back the whole block.
+    let transfer = contract_pb::TransferContract {
+        amount: 1_000_000_000,
+        ..transfer
+    };
+    let txn = chain.transaction("alice", &transfer);
+    assert!(chain.produce_block(vec![txn]).is_err());
+    assert_eq!(chain.dynamic_property(keys::DynamicProperty::LatestBlockNumber), 2);
+    assert_eq!(chain.account("alice").unwrap().balance, 99_000_000);
+}
@@ -1,20 +1,25 @@
 //! The TVM backend.
 
-use std::collections::HashSet;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 
 use ::keys::Address;
+use chain::IndexedBlockHeader;
 use crypto::keccak256;
 use lazy_static::lazy_static;
 use log::debug;
 use primitive_types::{H160, H256, U256};
+use proto::common::{ResourceCode, Vote};
+use proto::contract as contract_pb;
 use proto::state::{Account, AccountType, SmartContract, TransactionLog};
 use state::db::StateDB;
 use state::keys;
 use tvm::backend::{Apply, ApplyBackend, Backend, Basic, Log};
 
-use super::executor::actuators::validate_multisig;
+use super::executor::actuators::{validate_multisig, BuiltinContractExecutorExt};
 use super::executor::TransactionContext;
+use super::governance::reward::{RewardController, RewardUtil};
 use super::Manager;
 
 lazy_static! {
@@ -29,17 +34,116 @@ lazy_static! {
     };
 }
 
+/// System operations requested by TVM opcodes(STAKE, UNSTAKE, WITHDRAWREWARD, ASSETISSUE, UPDATEASSET).
+///
+/// They are validated when the opcode is executed, against the state with former operations applied, and queued in
+/// the current call frame. Operations of a reverted frame are dropped. The rest are saved to state-db only when the
+/// whole execution succeeds, in `StateBackend::apply_pending_operations`.
+///
+/// Operations spending balance carry the balance of the owner in TVM when requested, since the balance in state-db
+/// does not include transfers of the execution, e.g. the call value.
+enum SystemOperation {
+    Freeze {
+        cntr: contract_pb::FreezeBalanceContract,
+        /// Balance of the owner in TVM.
+        balance: i64,
+    },
+    Unfreeze(contract_pb::UnfreezeBalanceContract),
+    Vote(contract_pb::VoteWitnessContract),
+    WithdrawReward(Address),
+    AssetIssue {
+        cntr: contract_pb::AssetIssueContract,
+        /// Token id returned to TVM.
+        token_id: i64,
+        /// Fee subtracted from balance by TVM.
+        fee: i64,
+        /// Balance of the owner in TVM.
+        balance: i64,
+    },
+    UpdateAsset(contract_pb::UpdateAssetContract),
+}
+
+impl SystemOperation {
+    /// Validate and execute the operation.
+    ///
+    /// Balance changes are already tracked by TVM, and will be overwritten by `Apply::Modify`.
+    fn apply(&self, manager: &mut Manager, block_header: &IndexedBlockHeader) -> Result<(), String> {
+        let mut ctx = TransactionContext::dummy(block_header);
+        match self {
+            SystemOperation::Freeze { cntr, balance } => {
+                set_balance(manager, &cntr.owner_address, *balance)?;
+                cntr.validate(manager, &mut ctx)?;
+                cntr.execute(manager, &mut ctx)?;
+            }
+            SystemOperation::Unfreeze(cntr) => {
+                cntr.validate(manager, &mut ctx)?;
+                cntr.execute(manager, &mut ctx)?;
+            }
+            SystemOperation::Vote(cntr) => {
+                cntr.validate(manager, &mut ctx)?;
+                cntr.execute(manager, &mut ctx)?;
+            }
+            SystemOperation::WithdrawReward(addr) => {
+                let cntr = contract_pb::WithdrawBalanceContract {
+                    owner_address: addr.as_bytes().to_vec(),
+                };
+                cntr.validate(manager, &mut ctx)?;
+                withdraw_reward_to_balance(manager, *addr)?;
+            }
+            SystemOperation::AssetIssue {
+                cntr,
+                token_id,
+                fee,
+                balance,
+            } => {
+                set_balance(manager, &cntr.owner_address, *balance)?;
+                cntr.validate(manager, &mut ctx)?;
+                // The fee is subtracted by TVM, only the blackhole part is done here.
+                ctx.contract_fee = 0;
+                cntr.execute(manager, &mut ctx)?;
+                if ctx.asset_created_token_id != *token_id {
+                    return Err(format!(
+                        "inconsistent token id of ASSETISSUE, expected={} actual={}",
+                        token_id, ctx.asset_created_token_id
+                    ));
+                }
+                manager.add_to_blackhole(*fee).map_err(|e| e.to_string())?;
+            }
+            SystemOperation::UpdateAsset(cntr) => {
+                cntr.validate(manager, &mut ctx)?;
+                cntr.execute(manager, &mut ctx)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 /// StateDB backend, storing all state values in a RocksDB instance.
 pub struct StateBackend<'m, 'c, 'ctx> {
     manager: &'m mut Manager,
     ctx: &'c mut TransactionContext<'ctx>,
     sender: Address,
+    /// System operations queued by each call frame, the current frame last.
+    frames: RefCell<Vec<Vec<SystemOperation>>>,
+    /// A snapshot with all queued system operations applied, for validating the following operations.
+    ///
+    /// Created on the first system operation, and re-created after a frame with system operations reverts.
+    pending_state: RefCell<Option<Manager>>,
+    beneficiaries: RefCell<HashMap<Address, Address>>,
 }
 
 impl<'m, 'c, 'ctx> StateBackend<'m, 'c, 'ctx> {
     /// Create a new StateDB backend.
     pub fn new(sender: Address, manager: &'m mut Manager, ctx: &'c mut TransactionContext<'ctx>) -> Self {
-        Self { manager, ctx, sender }
+        Self {
+            manager,
+            ctx,
+            sender,
+            // The top-level frame is never exited.
+            frames: RefCell::new(vec![vec![]]),
+            pending_state: RefCell::new(None),
+            beneficiaries: RefCell::new(HashMap::new()),
+        }
     }
 
     /// Get the underlying `StateDB` storing the state.
@@ -51,6 +155,132 @@ impl<'m, 'c, 'ctx> StateBackend<'m, 'c, 'ctx> {
     fn state_mut(&mut self) -> &mut StateDB {
         &mut self.manager.state_db
     }
+
+    /// Run `f` on the state with queued system operations applied.
+    ///
+    /// On error, the pending state is dropped, since `f` might have left it half modified.
+    fn with_pending_state<T, F>(&self, f: F) -> Result<T, String>
+    where
+        F: FnOnce(&mut Manager) -> Result<T, String>,
+    {
+        let mut pending_state = self.pending_state.borrow_mut();
+        if pending_state.is_none() {
+            let mut manager = self.manager.snapshot();
+            for op in self.frames.borrow().iter().flatten() {
+                op.apply(&mut manager, self.ctx.block_header)?;
+            }
+            *pending_state = Some(manager);
+        }
+        let ret = f(pending_state.as_mut().unwrap());
+        if ret.is_err() {
+            *pending_state = None;
+        }
+        ret
+    }
+
+    /// Queue system operations in the current call frame. They must be applied to the pending state already.
+    fn push_operations(&self, ops: Vec<SystemOperation>) {
+        self.frames
+            .borrow_mut()
+            .last_mut()
+            .expect("top-level frame is never exited; qed")
+            .extend(ops);
+    }
+
+    /// Save queued system operations to state-db.
+    ///
+    /// Must be called before `apply`, only when the whole execution succeeds. An error here means the queued operations
+    /// are inconsistent with state-db.
+    pub fn apply_pending_operations(&mut self) -> Result<(), String> {
+        let frames = self.frames.replace(vec![vec![]]);
+        self.pending_state.replace(None);
+        for op in frames.into_iter().flatten() {
+            op.apply(self.manager, self.ctx.block_header)?;
+            if let SystemOperation::AssetIssue { token_id, .. } = op {
+                self.ctx.asset_created_token_id = token_id;
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete a suicided contract account, its code and storage, and transfer TRC10 tokens to the beneficiary.
+    ///
+    /// TRX balance is transferred by TVM. TRC10 tokens are burnt when the beneficiary is the contract itself.
+    fn delete_suicided_account(&mut self, addr: Address) {
+        let maybe_beneficiary = self.beneficiaries.borrow().get(&addr).copied();
+        let maybe_acct = self.state().get(&keys::Account(addr)).expect("db query");
+
+        if let (Some(acct), Some(to)) = (maybe_acct, maybe_beneficiary) {
+            if to != addr && !acct.token_balance.is_empty() {
+                let mut to_acct = self
+                    .state()
+                    .get(&keys::Account(to))
+                    .expect("db query")
+                    .unwrap_or_else(|| Account::new(self.manager.latest_block_timestamp()));
+                for (token_id, amount) in acct.token_balance {
+                    *to_acct.token_balance.entry(token_id).or_default() += amount;
+                }
+                self.state_mut().put_key(keys::Account(to), to_acct).unwrap();
+            }
+        }
+
+        self.state_mut().delete_key(&keys::Account(addr)).unwrap();
+        self.state_mut().delete_key(&keys::Contract(addr)).unwrap();
+        self.state_mut().delete_key(&keys::ContractCode(addr)).unwrap();
+        self.state_mut()
+            .delete_by_prefix::<_, keys::ContractStorage>(addr.as_bytes())
+            .unwrap();
+        debug!("suicide and delete account: {}", addr);
+    }
+}
+
+/// Convert bytes32 from TVM to string, trailing zeros are stripped.
+fn bytes32_to_string(raw: &[u8]) -> Option<String> {
+    let len = raw.iter().rposition(|&b| b != 0).map(|pos| pos + 1).unwrap_or(0);
+    String::from_utf8(raw[..len].to_vec()).ok()
+}
+
+/// Overwrite balance in state-db with the balance in TVM, for validation. Overwritten again by `Apply::Modify`.
+fn set_balance(manager: &mut Manager, owner_address: &[u8], balance: i64) -> Result<(), String> {
+    let addr = Address::try_from(owner_address).map_err(|_| "invalid owner address")?;
+    let mut acct = manager
+        .state_db
+        .get(&keys::Account(addr))
+        .map_err(|_| "db query error")?
+        .ok_or("account not exists")?;
+    acct.balance = balance;
+    manager
+        .state_db
+        .put_key(keys::Account(addr), acct)
+        .map_err(|_| "db insert error")?;
+    Ok(())
+}
+
+/// Unwithdrawn voting reward and allowance.
+fn reward_balance_of(manager: &Manager, addr: Address) -> i64 {
+    let allowance = manager
+        .state_db
+        .get(&keys::Account(addr))
+        .unwrap()
+        .map(|acct| acct.allowance)
+        .unwrap_or_default();
+    let reward = RewardUtil::new(manager).query_reward(addr).unwrap_or_default();
+    allowance + reward
+}
+
+/// Withdraw voting reward and allowance to balance.
+fn withdraw_reward_to_balance(manager: &mut Manager, addr: Address) -> Result<(), String> {
+    RewardController::new(manager).withdraw_reward(addr)?;
+
+    let mut acct = manager.state_db.must_get(&keys::Account(addr));
+    acct.adjust_balance(acct.allowance).map_err(|_| "math overflow")?;
+    acct.allowance = 0;
+    acct.latest_withdraw_timestamp = manager.latest_block_timestamp();
+    manager
+        .state_db
+        .put_key(keys::Account(addr), acct)
+        .map_err(|_| "db insert error")?;
+    Ok(())
 }
 
 #[allow(unused_variables)]
@@ -92,8 +322,9 @@ impl Backend for StateBackend<'_, '_, '_> {
         0.into()
     }
 
+    // CHAINID: hash of block #0, since AllowTvmIstanbulUpgrade.
     fn chain_id(&self) -> U256 {
-        unimplemented!("hash of block #0")
+        U256::from_big_endian(self.manager.genesis_block_hash.as_bytes())
     }
 
     fn exists(&self, address: H160) -> bool {
@@ -119,8 +350,17 @@ impl Backend for StateBackend<'_, '_, '_> {
             .unwrap_or_default()
     }
 
+    // EXTCODEHASH, and CREATE2 address calculation.
     fn code_hash(&self, address: H160) -> H256 {
-        unimplemented!()
+        let addr = Address::from_tvm_bytes(address.as_bytes());
+        if self.state().get(&keys::Account(addr)).unwrap().is_none() {
+            return H256::zero();
+        }
+        self.state()
+            .get(&keys::ContractCode(addr))
+            .unwrap()
+            .map(|code| keccak256(&code))
+            .unwrap_or_else(|| keccak256(&[]))
     }
 
     fn code_size(&self, address: H160) -> usize {
@@ -181,15 +421,303 @@ impl Backend for StateBackend<'_, '_, '_> {
             .map_err(|e| log::error!("validata multisig error: {:?}", e))
             .is_ok()
     }
+
+    // STAKE: freeze balance for bandwidth up to `amount`, and vote all tron power to `witness`.
+    //
+    // Returns the amount of newly frozen balance, which should be subtracted from balance by TVM. The freeze is
+    // validated against `balance`, the balance of `address` in TVM.
+    fn stake(&self, address: H160, witness: H160, amount: U256, balance: U256) -> Option<U256> {
+        if amount > U256::from(i64::max_value()) || balance > U256::from(i64::max_value()) {
+            return None;
+        }
+        let amount = amount.low_u64() as i64;
+        let balance = balance.low_u64() as i64;
+        let owner_addr = Address::from_tvm_bytes(address.as_bytes());
+        let wit_addr = Address::from_tvm_bytes(witness.as_bytes());
+
+        let ret = self.with_pending_state(|manager| {
+            let acct = manager
+                .state_db
+                .get(&keys::Account(owner_addr))
+                .map_err(|_| "db query error")?
+                .ok_or("account not exists")?;
+            manager
+                .state_db
+                .get(&keys::Witness(wit_addr))
+                .map_err(|_| "db query error")?
+                .ok_or("witness not exists")?;
+
+            // Stake amount can only be increased. Use UNSTAKE to get frozen balance back.
+            let frozen_amount = acct.frozen_amount_for_bandwidth;
+            if amount < frozen_amount {
+                return Err("stake amount is less than frozen amount".into());
+            }
+
+            let mut ops = vec![];
+            let freeze_amount = amount - frozen_amount;
+            if freeze_amount > 0 {
+                let op = SystemOperation::Freeze {
+                    cntr: contract_pb::FreezeBalanceContract {
+                        owner_address: owner_addr.as_bytes().to_vec(),
+                        frozen_balance: freeze_amount,
+                        frozen_duration: constants::MIN_NUM_OF_FROZEN_DAYS_FOR_RESOURCE,
+                        resource: ResourceCode::Bandwidth as i32,
+                        ..Default::default()
+                    },
+                    balance,
+                };
+                op.apply(manager, self.ctx.block_header)?;
+                ops.push(op);
+            }
+
+            // Tron power includes the freeze above.
+            let tron_power = manager.state_db.must_get(&keys::Account(owner_addr)).tron_power();
+            let op = SystemOperation::Vote(contract_pb::VoteWitnessContract {
+                owner_address: owner_addr.as_bytes().to_vec(),
+                votes: vec![Vote {
+                    vote_address: wit_addr.as_bytes().to_vec(),
+                    vote_count: tron_power,
+                }],
+                ..Default::default()
+            });
+            op.apply(manager, self.ctx.block_header)?;
+            ops.push(op);
+
+            Ok((ops, freeze_amount))
+        });
+
+        match ret {
+            Ok((ops, freeze_amount)) => {
+                self.push_operations(ops);
+                Some(freeze_amount.into())
+            }
+            Err(e) => {
+                debug!("STAKE error: {}", e);
+                None
+            }
+        }
+    }
+
+    // UNSTAKE: unfreeze bandwidth balance and clear all votes.
+    //
+    // Returns the unfrozen amount, which should be added to balance by TVM.
+    fn unstake(&self, address: H160) -> Option<U256> {
+        let owner_addr = Address::from_tvm_bytes(address.as_bytes());
+        let ret = self.with_pending_state(|manager| {
+            let acct = manager
+                .state_db
+                .get(&keys::Account(owner_addr))
+                .map_err(|_| "db query error")?
+                .ok_or("account not exists")?;
+            if acct.frozen_amount_for_bandwidth <= 0 {
+                return Err("no frozen balance".into());
+            }
+
+            let op = SystemOperation::Unfreeze(contract_pb::UnfreezeBalanceContract {
+                owner_address: owner_addr.as_bytes().to_vec(),
+                resource: ResourceCode::Bandwidth as i32,
+                ..Default::default()
+            });
+            op.apply(manager, self.ctx.block_header)?;
+            Ok((op, acct.frozen_amount_for_bandwidth))
+        });
+
+        match ret {
+            Ok((op, unfrozen_amount)) => {
+                self.push_operations(vec![op]);
+                Some(unfrozen_amount.into())
+            }
+            Err(e) => {
+                debug!("UNSTAKE error: {}", e);
+                None
+            }
+        }
+    }
+
+    // WITHDRAWREWARD: withdraw voting reward and allowance to balance.
+    //
+    // Returns the withdrawn amount, which should be added to balance by TVM.
+    fn withdraw_reward(&self, address: H160) -> U256 {
+        let owner_addr = Address::from_tvm_bytes(address.as_bytes());
+        let ret = self.with_pending_state(|manager| {
+            let amount = reward_balance_of(manager, owner_addr);
+            let op = SystemOperation::WithdrawReward(owner_addr);
+            op.apply(manager, self.ctx.block_header)?;
+            Ok((op, amount))
+        });
+
+        match ret {
+            Ok((op, amount)) => {
+                self.push_operations(vec![op]);
+                amount.into()
+            }
+            Err(e) => {
+                debug!("WITHDRAWREWARD error: {}", e);
+                U256::zero()
+            }
+        }
+    }
+
+    // REWARDBALANCE: unwithdrawn voting reward and allowance.
+    fn reward_balance(&self, address: H160) -> U256 {
+        let addr = Address::from_tvm_bytes(address.as_bytes());
+        self.with_pending_state(|manager| Ok(reward_balance_of(manager, addr)))
+            .unwrap_or_default()
+            .into()
+    }
+
+    // SELFDESTRUCT: record the beneficiary of a suicided contract.
+    //
+    // TRX balance is transferred by TVM, TRC10 tokens are transferred in `apply`.
+    fn selfdestruct(&self, address: H160, target: H160) {
+        let addr = Address::from_tvm_bytes(address.as_bytes());
+        let to = Address::from_tvm_bytes(target.as_bytes());
+        self.beneficiaries.borrow_mut().insert(addr, to);
+    }
+
+    // ASSETISSUE: issue a TRC10 asset owned by `address`, charging `AssetIssueFee`.
+    //
+    // Returns the new token id and the fee. TVM should subtract the fee from balance, and add the
+    // total supply to token balance. The fee is validated against `balance`, the balance of `address` in TVM.
+    fn asset_issue(
+        &self,
+        address: H160,
+        name: &[u8],
+        abbr: &[u8],
+        total_supply: U256,
+        precision: U256,
+        url: &[u8],
+        description: &[u8],
+        balance: U256,
+    ) -> Option<(U256, U256)> {
+        if total_supply > U256::from(i64::max_value()) ||
+            precision > U256::from(i32::max_value()) ||
+            balance > U256::from(i64::max_value())
+        {
+            return None;
+        }
+        let balance = balance.low_u64() as i64;
+        let owner_addr = Address::from_tvm_bytes(address.as_bytes());
+        // Assets issued by contracts are not for sale, the ICO window is set to the minimum.
+        let start_time = self.manager.latest_block_timestamp() + 1;
+        let cntr = contract_pb::AssetIssueContract {
+            owner_address: owner_addr.as_bytes().to_vec(),
+            name: bytes32_to_string(name)?,
+            abbr: bytes32_to_string(abbr)?,
+            total_supply: total_supply.low_u64() as i64,
+            precision: precision.low_u32() as i32,
+            trx_num: 1,
+            num: 1,
+            start_time,
+            end_time: start_time + 1,
+            url: String::from_utf8(url.to_vec()).ok()?,
+            description: description.to_vec(),
+            ..Default::default()
+        };
+
+        let ret = self.with_pending_state(|manager| {
+            let token_id = manager.state_db.must_get(&keys::DynamicProperty::LatestTokenId) + 1;
+            let fee = cntr.fee(manager);
+            let op = SystemOperation::AssetIssue {
+                cntr,
+                token_id,
+                fee,
+                balance,
+            };
+            op.apply(manager, self.ctx.block_header)?;
+            Ok((op, token_id, fee))
+        });
+
+        match ret {
+            Ok((op, token_id, fee)) => {
+                self.push_operations(vec![op]);
+                Some((token_id.into(), fee.into()))
+            }
+            Err(e) => {
+                debug!("ASSETISSUE error: {}", e);
+                None
+            }
+        }
+    }
+
+    // UPDATEASSET: update url and description of the asset issued by `address`.
+    fn update_asset(&self, address: H160, url: &[u8], description: &[u8]) -> bool {
+        let owner_addr = Address::from_tvm_bytes(address.as_bytes());
+        let url = match String::from_utf8(url.to_vec()) {
+            Ok(url) => url,
+            Err(_) => return false,
+        };
+
+        let ret = self.with_pending_state(|manager| {
+            let asset = manager
+                .state_db
+                .get(&keys::Account(owner_addr))
+                .map_err(|_| "db query error")?
+                .and_then(|acct| manager.state_db.get(&keys::Asset(acct.issued_asset_id)).unwrap())
+                .ok_or("asset not exists")?;
+            let op = SystemOperation::UpdateAsset(contract_pb::UpdateAssetContract {
+                owner_address: owner_addr.as_bytes().to_vec(),
+                url,
+                description: description.to_vec(),
+                new_limit: asset.free_asset_bandwidth_limit,
+                new_public_limit: asset.public_free_asset_bandwidth_limit,
+            });
+            op.apply(manager, self.ctx.block_header)?;
+            Ok(op)
+        });
+
+        match ret {
+            Ok(op) => {
+                self.push_operations(vec![op]);
+                true
+            }
+            Err(e) => {
+                debug!("UPDATEASSET error: {}", e);
+                false
+            }
+        }
+    }
+
+    // A step of the interpreter, before the opcode is executed.
+    fn step(&self, opcode: u8) {
+        tvm::profile::record_step(opcode);
+    }
+
+    // A call frame is entered, by CALL, CREATE and their variants.
+    fn enter_call_frame(&self) {
+        self.frames.borrow_mut().push(vec![]);
+    }
+
+    // A call frame exits. System operations of a reverted frame are dropped, as its state changes in TVM.
+    fn exit_call_frame(&self, reverted: bool) {
+        let mut frames = self.frames.borrow_mut();
+        let ops = frames.pop().expect("call frame entered; qed");
+        if reverted {
+            if !ops.is_empty() {
+                *self.pending_state.borrow_mut() = None;
+            }
+        } else {
+            frames
+                .last_mut()
+                .expect("top-level frame is never exited; qed")
+                .extend(ops);
+        }
+    }
 }
 
 impl ApplyBackend for StateBackend<'_, '_, '_> {
-    fn apply<A, I, L>(&mut self, values: A, logs: L, delete_empty: bool)
+    // NOTE: `delete_empty` is ignored. In java-tron, an account exists regardless of its balance, so empty accounts
+    // are never deleted. While an empty account is never created by TVM.
+    fn apply<A, I, L>(&mut self, values: A, logs: L, _delete_empty: bool)
     where
         A: IntoIterator<Item = Apply<I>>,
         I: IntoIterator<Item = (H256, H256)>,
         L: IntoIterator<Item = Log>,
     {
+        // Suicided accounts are handled after all modifications, so that TRC10 tokens are added to
+        // the updated beneficiary account.
+        let mut suicided = vec![];
+
         for apply in values {
             match apply {
                 Apply::Modify {
@@ -200,24 +728,23 @@ impl ApplyBackend for StateBackend<'_, '_, '_> {
                     reset_storage,
                 } => {
                     let addr = Address::from_tvm_bytes(address.as_bytes());
-                    if delete_empty &&
+                    let storage: Vec<_> = storage.into_iter().collect();
+
+                    let maybe_account = self.state().get(&keys::Account(addr)).expect("db query");
+                    if maybe_account.is_none() &&
                         basic.balance == U256::zero() &&
-                        basic.nonce == U256::zero() &&
+                        basic.token_balance.values().all(|value| value.is_zero()) &&
                         code.is_none() &&
-                        basic.token_balance.is_empty()
+                        storage.is_empty()
                     {
-                        unimplemented!("TODO: delete empty");
+                        debug!("skip creating empty account in TVM: {}", addr);
+                        continue;
                     }
 
-                    let (mut account, created) = self
-                        .state()
-                        .get(&keys::Account(addr))
-                        .expect("db query")
-                        .map(|acct| (acct, false))
-                        .unwrap_or_else(|| {
-                            log::debug!("create new account in TVM: {} {:?}", addr, address);
-                            (Account::new(self.manager.latest_block_timestamp()), true)
-                        });
+                    let (mut account, created) = maybe_account.map(|acct| (acct, false)).unwrap_or_else(|| {
+                        log::debug!("create new account in TVM: {} {:?}", addr, address);
+                        (Account::new(self.manager.latest_block_timestamp()), true)
+                    });
 
                     account.balance = basic.balance.as_u64() as i64;
                     for (token_id, token_value) in basic.token_balance {
@@ -244,7 +771,10 @@ impl ApplyBackend for StateBackend<'_, '_, '_> {
                     }
 
                     if !created && reset_storage {
-                        log::warn!("TODO: reset_storage {}", addr);
+                        debug!("reset storage of {}", addr);
+                        self.state_mut()
+                            .delete_by_prefix::<_, keys::ContractStorage>(addr.as_bytes())
+                            .unwrap();
                     }
                     self.state_mut().put_key(keys::Account(addr), account).unwrap();
 
@@ -263,29 +793,15 @@ impl ApplyBackend for StateBackend<'_, '_, '_> {
                 }
                 // Suicided
                 Apply::Delete { address } => {
-                    let addr = Address::from_tvm_bytes(address.as_bytes());
-                    self.state_mut().delete_key(&keys::Account(addr)).unwrap();
-                    self.state_mut().delete_key(&keys::Contract(addr)).unwrap();
-                    self.state_mut().delete_key(&keys::ContractCode(addr)).unwrap();
-                    debug!("suicide and delete account: {}", addr);
-                    let mut to_be_deleted: Vec<keys::ContractStorage> = Vec::new();
-                    {
-                        let to_be_deleted = &mut to_be_deleted;
-                        self.state()
-                            .for_each_by_prefix(addr.as_bytes(), move |key: &keys::ContractStorage, value| {
-                                debug!("suicide DELETE {} ({} => {:?})", key.0, key.1, value);
-                                to_be_deleted.push(key.clone());
-                            });
-                    }
-                    if !to_be_deleted.is_empty() {
-                        for key in to_be_deleted {
-                            self.state_mut().delete_key(&key).unwrap()
-                        }
-                    }
+                    suicided.push(Address::from_tvm_bytes(address.as_bytes()));
                 }
             }
         }
 
+        for addr in suicided {
+            self.delete_suicided_account(addr);
+        }
+
         for Log { address, topics, data } in logs {
             // let addr = Address::from_tvm_bytes(address.as_bytes());
             self.ctx.logs.push(TransactionLog {
@@ -296,3 +812,241 @@ impl ApplyBackend for StateBackend<'_, '_, '_> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::{Config, GenesisConfig};
+
+    fn new_test_manager(name: &str) -> Manager {
+        let data_dir = std::env::temp_dir().join(format!("opentron-test-vm-{}", name));
+        let _ = std::fs::remove_dir_all(&data_dir);
+
+        let mut config = Config::load_from_str(include_str!("../../etc/conf.toml")).unwrap();
+        config.storage.state_data_dir = data_dir.to_str().unwrap().to_owned();
+        let genesis_config = GenesisConfig::load_from_str(include_str!("../../etc/genesis.json")).unwrap();
+
+        let mut manager = Manager::new(&config, &genesis_config);
+        manager.new_layer();
+        manager
+    }
+
+    fn new_contract_account(manager: &mut Manager, addr: Address, token_balance: &[(i64, i64)]) {
+        let mut acct = Account::new(0);
+        acct.r#type = AccountType::Contract as i32;
+        acct.token_balance = token_balance.iter().copied().collect();
+        manager.state_db.put_key(keys::Account(addr), acct).unwrap();
+        manager
+            .state_db
+            .put_key(keys::ContractCode(addr), vec![0x60, 0x00])
+            .unwrap();
+        for i in 1..=3 {
+            manager
+                .state_db
+                .put_key(keys::ContractStorage(addr, H256::from_low_u64_be(i)), H256::from_low_u64_be(i))
+                .unwrap();
+        }
+    }
+
+    fn num_of_storage_entries(manager: &Manager, addr: Address) -> usize {
+        let mut n = 0;
+        manager
+            .state_db
+            .for_each_by_prefix(addr.as_bytes(), |_: &keys::ContractStorage, _| n += 1);
+        n
+    }
+
+    fn tvm_address(addr: &Address) -> H160 {
+        H160::from_slice(addr.as_tvm_bytes())
+    }
+
+    #[test]
+    fn test_reset_storage() {
+        let mut manager = new_test_manager("reset-storage");
+        let header = IndexedBlockHeader::dummy(1, 0);
+        let mut ctx = TransactionContext::dummy(&header);
+        let addr: Address = "TCCcBZEdTHmS1NfFtCYfwpjBKeTv515n71".parse().unwrap();
+        new_contract_account(&mut manager, addr, &[]);
+        assert_eq!(num_of_storage_entries(&manager, addr), 3);
+
+        let mut backend = StateBackend::new(addr, &mut manager, &mut ctx);
+        backend.apply(
+            vec![Apply::Modify {
+                address: tvm_address(&addr),
+                basic: Basic::default(),
+                code: None,
+                storage: vec![(H256::from_low_u64_be(4), H256::from_low_u64_be(4))],
+                reset_storage: true,
+            }],
+            vec![],
+            false,
+        );
+
+        assert_eq!(num_of_storage_entries(&manager, addr), 1);
+        assert!(manager.state_db.get(&keys::Account(addr)).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_selfdestruct() {
+        let mut manager = new_test_manager("selfdestruct");
+        let header = IndexedBlockHeader::dummy(1, 0);
+        let mut ctx = TransactionContext::dummy(&header);
+        let addr: Address = "TCCcBZEdTHmS1NfFtCYfwpjBKeTv515n71".parse().unwrap();
+        let beneficiary: Address = "TN21Wx2yoNYiZ7znuQonmZMJnH5Vdfxu78".parse().unwrap();
+        new_contract_account(&mut manager, addr, &[(1000001, 100), (1000002, 200)]);
+
+        let mut backend = StateBackend::new(addr, &mut manager, &mut ctx);
+        backend.selfdestruct(tvm_address(&addr), tvm_address(&beneficiary));
+        backend.apply(
+            vec![
+                Apply::Delete {
+                    address: tvm_address(&addr),
+                },
+                Apply::Modify {
+                    address: tvm_address(&beneficiary),
+                    basic: Basic {
+                        balance: 1_000_000.into(),
+                        ..Default::default()
+                    },
+                    code: None,
+                    storage: vec![],
+                    reset_storage: false,
+                },
+            ],
+            vec![],
+            true,
+        );
+
+        assert!(manager.state_db.get(&keys::Account(addr)).unwrap().is_none());
+        assert!(manager.state_db.get(&keys::ContractCode(addr)).unwrap().is_none());
+        assert_eq!(num_of_storage_entries(&manager, addr), 0);
+
+        let acct = manager.state_db.must_get(&keys::Account(beneficiary));
+        assert_eq!(acct.balance, 1_000_000);
+        assert_eq!(acct.token_balance.get(&1000001), Some(&100));
+        assert_eq!(acct.token_balance.get(&1000002), Some(&200));
+    }
+
+    #[test]
+    fn test_empty_account() {
+        let mut manager = new_test_manager("empty-account");
+        let header = IndexedBlockHeader::dummy(1, 0);
+        let mut ctx = TransactionContext::dummy(&header);
+        let addr: Address = "TCCcBZEdTHmS1NfFtCYfwpjBKeTv515n71".parse().unwrap();
+        let nonexistent: Address = "TN21Wx2yoNYiZ7znuQonmZMJnH5Vdfxu78".parse().unwrap();
+        new_contract_account(&mut manager, addr, &[]);
+
+        let mut backend = StateBackend::new(addr, &mut manager, &mut ctx);
+        let empty = |addr: &Address| Apply::Modify {
+            address: tvm_address(addr),
+            basic: Basic::default(),
+            code: None,
+            storage: vec![],
+            reset_storage: false,
+        };
+        backend.apply(vec![empty(&addr), empty(&nonexistent)], vec![], true);
+
+        // Empty accounts are neither deleted nor created.
+        assert!(manager.state_db.get(&keys::Account(addr)).unwrap().is_some());
+        assert!(manager.state_db.get(&keys::Account(nonexistent)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_system_operations_of_call_frames() {
+        let mut manager = new_test_manager("system-operations");
+        let header = IndexedBlockHeader::dummy(1, 0);
+        let mut ctx = TransactionContext::dummy(&header);
+        let addr: Address = "TCCcBZEdTHmS1NfFtCYfwpjBKeTv515n71".parse().unwrap();
+        new_contract_account(&mut manager, addr, &[]);
+        let mut acct = manager.state_db.must_get(&keys::Account(addr));
+        acct.balance = 10_000_000_000;
+        manager.state_db.put_key(keys::Account(addr), acct).unwrap();
+        let mut witnesses = vec![];
+        manager
+            .state_db
+            .for_each(|key: &keys::Witness, _| witnesses.push(tvm_address(&key.0)));
+        let witness = witnesses[0];
+
+        let backend = StateBackend::new(addr, &mut manager, &mut ctx);
+        let stake = |amount: u64| backend.stake(tvm_address(&addr), witness, amount.into(), 10_000_000_000u64.into());
+        // Dropped with the reverted frame.
+        backend.enter_call_frame();
+        assert_eq!(stake(1_000_000_000), Some(1_000_000_000.into()));
+        backend.exit_call_frame(true);
+        backend.enter_call_frame();
+        assert_eq!(stake(2_000_000_000), Some(2_000_000_000.into()));
+        backend.exit_call_frame(false);
+        // Validated against the stake of the succeeded frame, which is still pending.
+        assert_eq!(stake(3_000_000_000), Some(1_000_000_000.into()));
+        assert_eq!(stake(1_000_000_000), None);
+
+        let mut backend = backend;
+        backend.apply_pending_operations().unwrap();
+        drop(backend);
+
+        let acct = manager.state_db.must_get(&keys::Account(addr));
+        assert_eq!(acct.frozen_amount_for_bandwidth, 3_000_000_000);
+        let votes = manager.state_db.must_get(&keys::Votes(addr));
+        assert_eq!(votes.votes.len(), 1);
+        assert_eq!(votes.votes[0].vote_count, 3_000);
+    }
+
+    #[test]
+    fn test_stake_with_balance_in_tvm() {
+        let mut manager = new_test_manager("stake-balance-in-tvm");
+        let header = IndexedBlockHeader::dummy(1, 0);
+        let mut ctx = TransactionContext::dummy(&header);
+        let addr: Address = "TCCcBZEdTHmS1NfFtCYfwpjBKeTv515n71".parse().unwrap();
+        new_contract_account(&mut manager, addr, &[]);
+        let mut witnesses = vec![];
+        manager
+            .state_db
+            .for_each(|key: &keys::Witness, _| witnesses.push(tvm_address(&key.0)));
+        let witness = witnesses[0];
+
+        // No balance in state-db, funded by the call value.
+        let mut backend = StateBackend::new(addr, &mut manager, &mut ctx);
+        assert_eq!(
+            backend.stake(tvm_address(&addr), witness, 1_000_000_000.into(), 0.into()),
+            None
+        );
+        assert_eq!(
+            backend.stake(tvm_address(&addr), witness, 1_000_000_000.into(), 1_000_000_000.into()),
+            Some(1_000_000_000.into())
+        );
+        backend.apply_pending_operations().unwrap();
+        backend.apply(
+            vec![Apply::Modify {
+                address: tvm_address(&addr),
+                basic: Basic::default(),
+                code: None,
+                storage: vec![],
+                reset_storage: false,
+            }],
+            vec![],
+            true,
+        );
+        drop(backend);
+
+        let acct = manager.state_db.must_get(&keys::Account(addr));
+        assert_eq!(acct.balance, 0);
+        assert_eq!(acct.frozen_amount_for_bandwidth, 1_000_000_000);
+    }
+
+    #[test]
+    fn test_shielded_precompile_without_sapling_parameters() {
+        let mut manager = new_test_manager("shielded-precompile-without-params");
+        let header = IndexedBlockHeader::dummy(1, 0);
+        let mut ctx = TransactionContext::dummy(&header);
+        let addr: Address = "TCCcBZEdTHmS1NfFtCYfwpjBKeTv515n71".parse().unwrap();
+        let backend = StateBackend::new(addr, &mut manager, &mut ctx);
+
+        // Sapling parameters are never loaded in tests. Zeros decode as empty arrays, reaching the parameters.
+        let verify_transfer_proof = H160::from_low_u64_be(0x1000002);
+        let ret = tvm::precompile::tron_precompile(verify_transfer_proof, &[0u8; 2080], None, &backend);
+        match ret {
+            Some(Err(tvm::ExitError::Other(msg))) => assert!(msg.contains("not initialized")),
+            _ => panic!("unexpected precompile result: {:?}", ret),
+        }
+    }
+}
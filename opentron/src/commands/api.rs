@@ -0,0 +1,138 @@
+//! Read-only API server, following a running node on the same host via RocksDB secondaries.
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use clap::ArgMatches;
+use config::Config;
+use context::AppContext;
+use futures::join;
+use log::{info, warn};
+use slog::o;
+use slog_scope_futures::FutureExt as SlogFutureExt;
+use tokio::sync::broadcast;
+use tokio_compat_02::FutureExt as Compat02FutureExt;
+
+use graphql_service::server::graphql_server;
+
+/// The per-process secondary directory, used when `--secondary-dir` is not given. Removed on exit.
+fn default_secondary_dir(config: &Config) -> PathBuf {
+    PathBuf::from(&config.storage.state_cache_dir).join(format!("api-{}", process::id()))
+}
+
+fn remove_secondary_dir(dir: &Path) {
+    match fs::remove_dir_all(dir) {
+        Ok(()) => info!("secondary dir {:?} removed", dir),
+        Err(e) => warn!("remove secondary dir {:?} failed: {}", dir, e),
+    }
+}
+
+/// Open the context as secondaries. Each API process needs its own secondary directory.
+pub fn open_context(config_file: &str, matches: &ArgMatches) -> Result<AppContext, Box<dyn Error>> {
+    match matches.value_of("secondary-dir") {
+        Some(dir) => {
+            info!("secondary dir => {:?}", dir);
+            AppContext::from_config_as_secondary(config_file, dir)
+        }
+        None => {
+            let secondary_dir = default_secondary_dir(&Config::load_from_file(config_file)?);
+            info!("secondary dir => {:?}", secondary_dir);
+            AppContext::from_config_as_secondary(config_file, &secondary_dir).map_err(|e| {
+                remove_secondary_dir(&secondary_dir);
+                e
+            })
+        }
+    }
+}
+
+/// Catch up ChainDB, then StateDB, and publish a new state snapshot.
+///
+/// Returns the latest block number of which the ref block hash is known.
+fn catch_up(ctx: &AppContext, mut ref_block_number: i64) -> Result<i64, Box<dyn Error>> {
+    ctx.chain_db.catch_up_with_primary()?;
+
+    let mut manager = ctx.manager.write().unwrap();
+    manager.catch_up_with_primary()?;
+
+    // NOTE: The primary might apply a block to StateDB before inserting it to ChainDB.
+    while ref_block_number < manager.latest_block_number() {
+        match ctx.chain_db.get_block_header_by_number(ref_block_number + 1) {
+            Ok(header) => manager.update_ref_blocks(header.hash),
+            Err(_) => break,
+        }
+        ref_block_number += 1;
+    }
+    ctx.update_state_snapshot(&manager);
+
+    Ok(ref_block_number)
+}
+
+async fn follow_primary(ctx: Arc<AppContext>, interval: u64, mut done_signal: broadcast::Receiver<()>) {
+    let mut ref_block_number = ctx.manager.read().unwrap().latest_block_number();
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_millis(interval)) => {}
+            _ = done_signal.recv() => break,
+        }
+
+        // Wait for in-flight queries, the secondaries change under them.
+        let _guard = ctx.catch_up_lock.write().await;
+        match catch_up(&ctx, ref_block_number) {
+            Ok(number) => ref_block_number = number,
+            Err(e) => warn!("catch up with primary failed: {}", e),
+        }
+    }
+}
+
+pub async fn main(mut ctx: AppContext, matches: &ArgMatches<'_>) -> Result<(), Box<dyn Error>> {
+    let interval: u64 = matches
+        .value_of("catch-up-interval")
+        .expect("has default in cli.yml; qed")
+        .parse()?;
+    if let Some(endpoint) = matches.value_of("graphql-endpoint") {
+        ctx.config.graphql.endpoint = endpoint.into();
+    }
+    let ctx = Arc::new(ctx);
+
+    let termination_handler = {
+        let ctx = ctx.clone();
+        move || {
+            ctx.running.store(false, std::sync::atomic::Ordering::SeqCst);
+            let _ = ctx.termination_signal.send(());
+        }
+    };
+    let f = Mutex::new(Some(termination_handler));
+    ctrlc::set_handler(move || {
+        if let Some(f) = f.lock().unwrap().take() {
+            eprintln!("\nCtrl-C pressed. Now shuting down gracefully... ");
+            f();
+        }
+    })?;
+
+    info!(
+        "api server started at block #{}, catching up every {}ms",
+        ctx.manager.read().unwrap().latest_block_number(),
+        interval
+    );
+
+    let graphql_service = {
+        let ctx = ctx.clone();
+        let done_signal = ctx.termination_signal.subscribe();
+        let logger = slog_scope::logger().new(o!("service" => "graphql"));
+        graphql_server(ctx, done_signal).with_logger(logger).compat()
+    };
+    let follower = follow_primary(ctx.clone(), interval, ctx.termination_signal.subscribe());
+    let _ = join!(graphql_service, follower);
+
+    if matches.value_of("secondary-dir").is_none() {
+        let secondary_dir = default_secondary_dir(&ctx.config);
+        drop(ctx);
+        remove_secondary_dir(&secondary_dir);
+    }
+
+    Ok(())
+}
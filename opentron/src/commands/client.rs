@@ -0,0 +1,73 @@
+//! A minimal GraphQL client of a running node, for client commands.
+
+use std::error::Error;
+
+use chain::{BlockHeader, IndexedBlockHeader, H256};
+use keys::Address;
+use proto::chain::block_header::Raw as BlockHeaderRaw;
+use serde_json::{json, Value};
+
+pub struct GraphQLClient {
+    endpoint: String,
+    client: reqwest::blocking::Client,
+}
+
+impl GraphQLClient {
+    pub fn new(endpoint: &str) -> Self {
+        GraphQLClient {
+            endpoint: endpoint.to_owned(),
+            client: reqwest::blocking::Client::new(),
+        }
+    }
+
+    pub fn query(&self, query: &str, variables: Value) -> Result<Value, Box<dyn Error>> {
+        let mut resp: Value = self
+            .client
+            .post(&self.endpoint)
+            .json(&json!({ "query": query, "variables": variables }))
+            .send()?
+            .json()?;
+        if let Some(errors) = resp.get("errors") {
+            return Err(format!("graphql error: {}", errors).into());
+        }
+        Ok(resp["data"].take())
+    }
+
+    /// Constant call of a contract method.
+    pub fn call(&self, contract: &Address, data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        let query = r#"query($to: Address!, $data: Bytes!) {
+            call(data: { to: $to, data: $data }) { data vmStatus }
+        }"#;
+        let resp = self.query(query, json!({ "to": contract.to_string(), "data": hex::encode(data) }))?;
+        if resp["call"]["vmStatus"] != "SUCCESS" {
+            return Err(format!("contract call failed: {}", resp["call"]["vmStatus"]).into());
+        }
+        Ok(hex::decode(resp["call"]["data"].as_str().unwrap_or_default())?)
+    }
+
+    /// The latest block header, with only number, hash and timestamp, enough for TaPoS.
+    pub fn latest_block_header(&self) -> Result<IndexedBlockHeader, Box<dyn Error>> {
+        let resp = self.query("{ block { number hash timestamp } }", json!({}))?;
+        let block = &resp["block"];
+        let hash = hex::decode(block["hash"].as_str().unwrap_or_default())?;
+        if hash.len() != 32 {
+            return Err("invalid block hash".into());
+        }
+        let header = BlockHeader {
+            raw_data: Some(BlockHeaderRaw {
+                number: block["number"].as_str().unwrap_or_default().parse()?,
+                timestamp: block["timestamp"].as_i64().ok_or("invalid block timestamp")?,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        Ok(IndexedBlockHeader::new(H256::from_slice(&hash), header))
+    }
+
+    /// Send a protobuf-encoded signed transaction, returns the transaction hash.
+    pub fn send_raw_transaction(&self, raw: &[u8]) -> Result<String, Box<dyn Error>> {
+        let query = r#"mutation($data: Bytes!) { sendRawTransaction(data: $data) }"#;
+        let resp = self.query(query, json!({ "data": hex::encode(raw) }))?;
+        Ok(resp["sendRawTransaction"].as_str().unwrap_or_default().to_owned())
+    }
+}
@@ -1,9 +1,14 @@
 use chrono::Utc;
+use clap::ArgMatches;
 use log::info;
 
 use context::AppContext;
 
-pub async fn main(ctx: AppContext) -> Result<(), Box<dyn std::error::Error>> {
+pub async fn main(ctx: AppContext, matches: &ArgMatches<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    if let ("profile", Some(arg_matches)) = matches.subcommand() {
+        return profile(ctx, arg_matches);
+    }
+
     let mut manager = ctx.manager.write().unwrap();
 
     let mut start_time = Utc::now().timestamp_millis();
@@ -27,3 +32,45 @@ pub async fn main(ctx: AppContext) -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+/// Replay blocks in the range, and print the top-N report of the profiler.
+///
+/// StateDB can only move forward, so the range must start after the current state.
+fn profile(ctx: AppContext, matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let mut manager = ctx.manager.write().unwrap();
+    let next_block = manager.latest_block_number() as u64 + 1;
+
+    let from = match matches.value_of("from") {
+        Some(num) => num.parse()?,
+        None => next_block,
+    };
+    let to: u64 = matches.value_of("to").expect("required in cli.yml; qed").parse()?;
+    let top: usize = matches.value_of("top").expect("has default in cli.yml; qed").parse()?;
+    if from < next_block {
+        return Err(format!(
+            "state-db is at block #{}, can not profile from #{}",
+            next_block - 1,
+            from
+        )
+        .into());
+    }
+    if from > to {
+        return Err("--from should not be greater than --to".into());
+    }
+
+    if from > next_block {
+        info!("replaying block #{}..#{} without profiling", next_block, from - 1);
+    }
+    for i in next_block..=to {
+        if i == from {
+            manager.enable_profiler();
+        }
+        let blk = ctx.chain_db.get_block_by_number(i)?;
+        manager.push_block(&blk)?;
+    }
+
+    let profiler = manager.take_profiler().expect("profiler enabled; qed");
+    println!("{}", profiler.report(top));
+
+    Ok(())
+}
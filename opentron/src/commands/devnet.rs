@@ -0,0 +1,236 @@
+//! Single-node developer chain, which seals blocks instantly or by interval.
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chain::{BlockHeader, IndexedBlock, IndexedBlockHeader};
+use clap::ArgMatches;
+use config::genesis::{Alloc, Witness};
+use config::{ChainParameterConfig, GenesisConfig};
+use context::AppContext;
+use futures::join;
+use keys::{DerivationPath, ExtendedPrivate, KeyPair, Mnemonic};
+use log::{debug, info, warn};
+use proto::chain::block_header::Raw as BlockHeaderRaw;
+use slog::o;
+use slog_scope_futures::FutureExt as SlogFutureExt;
+use tokio::sync::broadcast;
+use tokio_compat_02::FutureExt as Compat02FutureExt;
+
+use graphql_service::server::graphql_server;
+
+use super::init::{genesis_timestamp, private_net_config};
+
+/// Init the data directory of the developer chain, or reuse an existing one. Returns the config path.
+pub fn init_datadir(matches: &ArgMatches) -> Result<PathBuf, Box<dyn Error>> {
+    let datadir = Path::new(matches.value_of("datadir").expect("has default in cli.yml; qed"));
+    let config_path = datadir.join("conf.toml");
+    if config_path.exists() {
+        info!("reuse developer chain at {:?}", datadir);
+        return Ok(config_path);
+    }
+    fs::create_dir_all(datadir)?;
+    let datadir = datadir.canonicalize()?;
+
+    let mnemonic = match matches.value_of("mnemonic") {
+        Some(phrase) => phrase.parse::<Mnemonic>()?,
+        None => Mnemonic::generate(12)?,
+    };
+    let num_accounts: u32 = matches
+        .value_of("accounts")
+        .expect("has default in cli.yml; qed")
+        .parse()?;
+    if num_accounts == 0 {
+        return Err("at least one account is required".into());
+    }
+    let balance: i64 = matches
+        .value_of("balance")
+        .expect("has default in cli.yml; qed")
+        .parse()?;
+
+    let master = ExtendedPrivate::from_seed(&mnemonic.to_seed(""))?;
+    let accounts = (0..num_accounts)
+        .map(|i| Ok(master.derive(&DerivationPath::tron(0, i)?)?.key_pair()))
+        .collect::<Result<Vec<KeyPair>, Box<dyn Error>>>()?;
+
+    // The first account is the only witness.
+    let witnesses = vec![Witness {
+        address: accounts[0].address().to_string(),
+        url: "http://devnet.local".into(),
+        votes: 100_000_000,
+    }];
+    let allocs = accounts
+        .iter()
+        .enumerate()
+        .map(|(i, key)| Alloc {
+            address: key.address().to_string(),
+            name: format!("Dev{}", i),
+            balance,
+        })
+        .collect();
+    let parent_hash: [u8; 32] = rand::random();
+    let genesis = GenesisConfig::new(genesis_timestamp(), &parent_hash, witnesses, allocs);
+    fs::write(datadir.join("genesis.json"), genesis.to_json())?;
+
+    let mut config = private_net_config(&datadir)?;
+    config.chain.dev_mode = true;
+    for name in ChainParameterConfig::FLAGS {
+        config.chain.parameter.enable(name)?;
+    }
+    config.protocol.channel.enable = false;
+    // Plaintext is acceptable, all keys are printed anyway.
+    config.witness.private_key = accounts[0].private().to_string();
+    let config_path = datadir.join("conf.toml");
+    fs::write(&config_path, config.to_toml()?)?;
+
+    println!("mnemonic: {}", mnemonic);
+    println!("accounts:");
+    for (i, key) in accounts.iter().enumerate() {
+        println!("  ({}) {} {}", i, key.address(), key.private());
+    }
+    Ok(config_path)
+}
+
+/// Pack pending transactions into a new block, sign and apply it.
+///
+/// Transactions are executed in order on the block's state. Rejected ones are dropped, skipped ones are put back
+/// to the pending queue for later blocks, and so are all of them if the block can not be applied.
+fn seal_block(ctx: &AppContext, key: &KeyPair) -> Result<IndexedBlock, Box<dyn Error>> {
+    let pending_txns: Vec<_> = ctx.pending_transactions.lock().unwrap().drain(..).collect();
+
+    let mut manager = ctx.manager.write().unwrap();
+    let raw_header = BlockHeaderRaw {
+        number: manager.latest_block_number() + 1,
+        // Blocks can be sealed in the same slot.
+        timestamp: chrono::Utc::now()
+            .timestamp_millis()
+            .max(manager.latest_block_timestamp() + 1),
+        parent_hash: manager.latest_block_hash().as_bytes().to_vec(),
+        witness_address: key.address().as_bytes().to_vec(),
+        version: constants::CURRENT_BLOCK_VERSION as i32,
+        ..Default::default()
+    };
+    let header = BlockHeader {
+        raw_data: Some(raw_header),
+        ..Default::default()
+    };
+
+    let indexed_header = IndexedBlockHeader::from_raw(header.clone()).ok_or("invalid block header")?;
+    let (txns, skipped, rejected) = manager.select_pending_transactions(pending_txns, &indexed_header);
+    for (txn, e) in rejected {
+        warn!("pending transaction {:?} dropped: {}", txn.hash, e);
+    }
+    let skipped: Vec<_> = skipped
+        .into_iter()
+        .map(|(txn, e)| {
+            debug!("pending transaction {:?} skipped: {}", txn.hash, e);
+            txn
+        })
+        .collect();
+
+    let raw_txns = txns.iter().map(|txn| txn.raw.clone()).collect();
+    // merkle_root_hash is filled here, sign afterwards.
+    let sealed = IndexedBlock::from_raw_header_and_txns(header, raw_txns)
+        .ok_or_else(|| Box::<dyn Error>::from("invalid block"))
+        .and_then(|mut block| {
+            block.sign(key.private())?;
+            if !manager.push_block(&block)? {
+                return Err("block is not applied".into());
+            }
+            Ok(block)
+        });
+    // Put back in the original order, ahead of transactions arrived meanwhile.
+    let mut requeued = skipped;
+    if sealed.is_err() {
+        requeued.splice(0..0, txns);
+    }
+    ctx.pending_transactions.lock().unwrap().splice(0..0, requeued);
+    let block = sealed?;
+    ctx.update_state_snapshot(&manager);
+    drop(manager);
+
+    ctx.chain_db.insert_block(&block)?;
+    ctx.chain_db.update_block_height(block.number());
+    Ok(block)
+}
+
+/// Seal a block once a transaction arrives, or every `block_time` milliseconds if not 0.
+async fn seal_blocks(ctx: Arc<AppContext>, block_time: u64, mut done_signal: broadcast::Receiver<()>) {
+    let key = ctx
+        .witness_key
+        .clone()
+        .expect("witness key is written in devnet config; qed");
+    loop {
+        if block_time == 0 {
+            tokio::select! {
+                _ = ctx.new_transaction_signal.notified() => {}
+                _ = done_signal.recv() => break,
+            }
+        } else {
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_millis(block_time)) => {}
+                _ = done_signal.recv() => break,
+            }
+        }
+
+        match seal_block(&ctx, &key) {
+            Ok(block) => info!(
+                "sealed block #{} hash={} txns={}",
+                block.number(),
+                block.hash(),
+                block.transactions.len()
+            ),
+            Err(e) => warn!("seal block failed: {}", e),
+        }
+    }
+}
+
+pub async fn main(ctx: AppContext, matches: &ArgMatches<'_>) -> Result<(), Box<dyn Error>> {
+    let block_time: u64 = matches
+        .value_of("block-time")
+        .expect("has default in cli.yml; qed")
+        .parse()?;
+    if matches.is_present("profile") {
+        ctx.manager.write().unwrap().enable_profiler();
+        info!("execution profiler enabled");
+    }
+    let ctx = Arc::new(ctx);
+
+    let termination_handler = {
+        let ctx = ctx.clone();
+        move || {
+            ctx.running.store(false, std::sync::atomic::Ordering::SeqCst);
+            let _ = ctx.termination_signal.send(());
+            unsafe {
+                ctx.chain_db.prepare_close();
+            }
+        }
+    };
+    let f = Mutex::new(Some(termination_handler));
+    ctrlc::set_handler(move || {
+        if let Some(f) = f.lock().unwrap().take() {
+            eprintln!("\nCtrl-C pressed. Now shuting down gracefully... ");
+            f();
+        }
+    })?;
+
+    if block_time == 0 {
+        info!("devnet started, blocks are sealed on new transactions");
+    } else {
+        info!("devnet started, blocks are sealed every {}ms", block_time);
+    }
+
+    let graphql_service = {
+        let ctx = ctx.clone();
+        let done_signal = ctx.termination_signal.subscribe();
+        let logger = slog_scope::logger().new(o!("service" => "graphql"));
+        graphql_server(ctx, done_signal).with_logger(logger).compat()
+    };
+    let sealer = seal_blocks(ctx.clone(), block_time, ctx.termination_signal.subscribe());
+    let _ = join!(graphql_service, sealer);
+
+    Ok(())
+}
@@ -0,0 +1,144 @@
+//! Bootstrap a private network, with witness keys, genesis and config.
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use clap::ArgMatches;
+use config::genesis::{Alloc, Witness};
+use config::{ChainParameterConfig, Config, GenesisConfig, WitnessConfig};
+use context::read_keystore_password;
+use keys::{Address, KeyPair, Keystore};
+use rand::Rng;
+
+/// Allocated to the first witness, when no `--alloc` is given. Same as Zion of mainnet.
+const DEFAULT_ALLOC_BALANCE: i64 = 99_000_000_000_000_000;
+const BLOCK_PRODUCING_INTERVAL: i64 = 3_000;
+/// Default energy fee of private networks, as suggested in conf.toml.
+const PRIVATE_NET_ENERGY_FEE: i64 = 10;
+
+fn alloc_of(index: usize, spec: &str) -> Result<Alloc, Box<dyn Error>> {
+    let mut parts = spec.splitn(2, ':');
+    let address: Address = parts.next().unwrap().parse()?;
+    let balance: i64 = parts.next().ok_or("alloc should be ADDRESS:BALANCE")?.parse()?;
+    Ok(Alloc {
+        address: address.to_string(),
+        name: format!("Alloc{}", index + 1),
+        balance,
+    })
+}
+
+fn password_of(matches: &ArgMatches) -> Result<String, Box<dyn Error>> {
+    read_keystore_password(
+        matches.value_of("password-file"),
+        &WitnessConfig::default().password_env,
+    )
+}
+
+/// Config of a private network in `datadir`, from the default config. Peer discovery is disabled.
+pub(super) fn private_net_config(datadir: &Path) -> Result<Config, Box<dyn Error>> {
+    let mut config = Config::load_from_str(include_str!("../../../etc/conf.toml"))?;
+    config.chain.genesis = "genesis.json".into();
+    config.chain.p2p_version = rand::thread_rng().gen_range(100_000, 1_000_000);
+    config.chain.parameter.energy_fee = PRIVATE_NET_ENERGY_FEE;
+    config.storage.data_dir = datadir.join("data/chaindb").to_string_lossy().into();
+    config.storage.state_data_dir = datadir.join("data/statedb").to_string_lossy().into();
+    config.storage.state_cache_dir = datadir.join("data/cache").to_string_lossy().into();
+    config.protocol.seed_nodes = vec![];
+    config.protocol.discovery.enable = false;
+    config.protocol.channel.active_nodes = vec![];
+    Ok(config)
+}
+
+/// Genesis timestamp, aligned to block producing slots.
+pub(super) fn genesis_timestamp() -> i64 {
+    chrono::Utc::now().timestamp_millis() / BLOCK_PRODUCING_INTERVAL * BLOCK_PRODUCING_INTERVAL
+}
+
+pub fn main(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let datadir = Path::new(matches.value_of("datadir").expect("has default in cli.yml; qed"));
+    if datadir.exists() && datadir.read_dir()?.next().is_some() {
+        return Err(format!("{:?} is not empty", datadir).into());
+    }
+    let num_witnesses: usize = matches
+        .value_of("witnesses")
+        .expect("has default in cli.yml; qed")
+        .parse()?;
+    if num_witnesses == 0 {
+        return Err("at least one witness is required".into());
+    }
+    let password = password_of(matches)?;
+
+    fs::create_dir_all(datadir.join("keystore"))?;
+    let datadir = datadir.canonicalize()?;
+
+    let keys: Vec<_> = (0..num_witnesses).map(|_| KeyPair::generate()).collect();
+    let mut keystores = vec![];
+    for (i, key) in keys.iter().enumerate() {
+        let path = datadir.join("keystore").join(format!("witness-{}.json", i + 1));
+        fs::write(&path, Keystore::encrypt(key.private(), password.as_bytes())?.to_json())?;
+        keystores.push(path);
+    }
+
+    // ! genesis
+    let witnesses = keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| Witness {
+            address: key.address().to_string(),
+            url: format!("http://witness{}.local", i + 1),
+            // Same as mainnet, the first witness has the most votes.
+            votes: 100_000_000 + (num_witnesses - i) as i64,
+        })
+        .collect();
+    let allocs = match matches.values_of("alloc") {
+        Some(specs) => specs
+            .enumerate()
+            .map(|(i, spec)| alloc_of(i, spec))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => vec![Alloc {
+            address: keys[0].address().to_string(),
+            name: "Zion".into(),
+            balance: DEFAULT_ALLOC_BALANCE,
+        }],
+    };
+    let timestamp = match matches.value_of("timestamp") {
+        Some(timestamp) => timestamp.parse()?,
+        None => genesis_timestamp(),
+    };
+    // A random parent hash makes the genesis block unique to this network.
+    let parent_hash: [u8; 32] = rand::thread_rng().gen();
+    let genesis = GenesisConfig::new(timestamp, &parent_hash, witnesses, allocs);
+    let genesis_hash = *genesis.to_indexed_block()?.hash();
+    fs::write(datadir.join("genesis.json"), genesis.to_json())?;
+
+    // ! config
+    let mut config = private_net_config(&datadir)?;
+    match matches.values_of("enable") {
+        Some(names) if names.clone().any(|name| name == "all") => {
+            for name in ChainParameterConfig::FLAGS {
+                config.chain.parameter.enable(name)?;
+            }
+        }
+        Some(names) => {
+            for name in names {
+                config.chain.parameter.enable(name)?;
+            }
+        }
+        None => {}
+    }
+    config.witness.private_key = "".into();
+    config.witness.keystore = keystores[0].to_string_lossy().into();
+    if let Some(path) = matches.value_of("password-file") {
+        config.witness.password_file = Path::new(path).canonicalize()?.to_string_lossy().into();
+    }
+    let config_path = datadir.join("conf.toml");
+    fs::write(&config_path, config.to_toml()?)?;
+
+    println!("genesis block => {:?}", genesis_hash);
+    for (key, path) in keys.iter().zip(&keystores) {
+        println!("witness {} => {:?}", key.address(), path);
+    }
+    println!("run with: opentron --config {:?}", config_path);
+    Ok(())
+}
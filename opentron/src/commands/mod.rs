@@ -1,3 +1,10 @@
+pub mod api;
 pub mod check;
+mod client;
 pub mod dev;
+pub mod devnet;
 pub mod fix;
+pub mod init;
+mod multisig;
+pub mod wallet;
+pub mod ztron;
@@ -0,0 +1,346 @@
+//! Offline multisig, collecting signatures of a transaction from multiple signers.
+//!
+//! A partially-signed transaction is a JSON file, passed around signers until the
+//! total weight reaches the threshold of the permission.
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fs;
+
+use chain::builder::owner_address_of;
+use chain::IndexedTransaction;
+use clap::ArgMatches;
+use keys::{Address, Public, Signature};
+use prost::Message;
+use proto::chain::Transaction;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::client::GraphQLClient;
+use super::wallet::{key_of, output};
+
+/// A collected signature.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PartialSignature {
+    address: String,
+    signature: String,
+}
+
+/// An unsigned transaction, with signatures collected so far.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PartiallySignedTransaction {
+    /// Protobuf-encoded unsigned transaction, in hex.
+    transaction: String,
+    permission_id: i32,
+    signatures: Vec<PartialSignature>,
+}
+
+impl PartiallySignedTransaction {
+    fn from_transaction(mut txn: Transaction) -> Result<Self, Box<dyn Error>> {
+        let signatures = txn.signatures.split_off(0);
+        let txn = IndexedTransaction::from_raw(txn).ok_or("invalid transaction")?;
+        let mut pst = PartiallySignedTransaction {
+            transaction: hex::encode(encode(&txn.raw)?),
+            permission_id: contract_of(&txn)?.permission_id,
+            signatures: vec![],
+        };
+        for sig in signatures {
+            pst.add_signature(&txn, sig)?;
+        }
+        Ok(pst)
+    }
+
+    fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// The unsigned transaction.
+    fn unsigned_transaction(&self) -> Result<IndexedTransaction, Box<dyn Error>> {
+        let raw = hex::decode(&self.transaction)?;
+        let txn = IndexedTransaction::from_raw(Transaction::decode(&raw[..])?).ok_or("invalid transaction")?;
+        if !txn.raw.signatures.is_empty() || contract_of(&txn)?.permission_id != self.permission_id {
+            return Err("transaction is inconsistent with the partially-signed transaction".into());
+        }
+        Ok(txn)
+    }
+
+    /// Verify and add a signature, duplicated signers are rejected.
+    fn add_signature(&mut self, txn: &IndexedTransaction, raw_sig: Vec<u8>) -> Result<Address, Box<dyn Error>> {
+        let sig = Signature::try_from(&raw_sig)?;
+        let public = Public::recover_digest(txn.hash.as_bytes(), &sig)?;
+        let address = Address::from_public(&public);
+        if self.signers()?.contains(&address) {
+            return Err(format!("already signed by {}", address).into());
+        }
+        self.signatures.push(PartialSignature {
+            address: address.to_string(),
+            signature: hex::encode(raw_sig),
+        });
+        Ok(address)
+    }
+
+    fn signers(&self) -> Result<Vec<Address>, Box<dyn Error>> {
+        self.signatures.iter().map(|sig| Ok(sig.address.parse()?)).collect()
+    }
+
+    /// The signed transaction, in the order of collected signatures.
+    fn signed_transaction(&self) -> Result<IndexedTransaction, Box<dyn Error>> {
+        let mut txn = self.unsigned_transaction()?;
+        for sig in &self.signatures {
+            txn.raw.signatures.push(hex::decode(&sig.signature)?);
+        }
+        Ok(txn)
+    }
+}
+
+/// A permission of the owner account, as in state.
+#[derive(Debug, Clone)]
+struct Permission {
+    id: i32,
+    name: String,
+    threshold: i64,
+    /// None for owner permission, which allows all contract types.
+    operations: Option<Vec<u8>>,
+    keys: Vec<(Address, i64)>,
+}
+
+impl Permission {
+    fn from_json(value: &Value) -> Result<Self, Box<dyn Error>> {
+        let operations = match value["operations"].as_str() {
+            Some(ops) => Some(hex::decode(ops)?),
+            None => None,
+        };
+        let keys = value["keys"]
+            .as_array()
+            .ok_or("invalid permission keys")?
+            .iter()
+            .map(|key| {
+                let address = key["address"].as_str().ok_or("invalid permission key")?.parse()?;
+                let weight = key["weight"].as_str().ok_or("invalid permission key")?.parse()?;
+                Ok((address, weight))
+            })
+            .collect::<Result<_, Box<dyn Error>>>()?;
+        Ok(Permission {
+            id: value["id"].as_i64().ok_or("invalid permission id")? as i32,
+            name: value["name"].as_str().unwrap_or_default().to_owned(),
+            threshold: value["threshold"]
+                .as_str()
+                .ok_or("invalid permission threshold")?
+                .parse()?,
+            operations,
+            keys,
+        })
+    }
+
+    fn allows(&self, contract_type: i32) -> bool {
+        match self.operations {
+            None => true,
+            Some(ref ops) => ops
+                .get(contract_type as usize / 8)
+                .map(|byte| (byte >> (contract_type % 8)) & 1 == 1)
+                .unwrap_or(false),
+        }
+    }
+
+    fn weight_of(&self, signer: &Address) -> Option<i64> {
+        self.keys
+            .iter()
+            .find(|(addr, _)| addr == signer)
+            .map(|&(_, weight)| weight)
+    }
+
+    /// Total weight of signers, unknown signers are ignored.
+    fn collected_weight(&self, signers: &[Address]) -> i64 {
+        signers.iter().filter_map(|signer| self.weight_of(signer)).sum()
+    }
+}
+
+fn encode(txn: &Transaction) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut raw = Vec::with_capacity(255);
+    txn.encode(&mut raw)?;
+    Ok(raw)
+}
+
+fn contract_of(txn: &IndexedTransaction) -> Result<&proto::chain::transaction::Contract, Box<dyn Error>> {
+    txn.raw
+        .raw_data
+        .as_ref()
+        .and_then(|raw| raw.contract.as_ref())
+        .ok_or_else(|| "transaction without contract".into())
+}
+
+fn permission_of(
+    client: &GraphQLClient,
+    txn: &IndexedTransaction,
+    permission_id: i32,
+) -> Result<Permission, Box<dyn Error>> {
+    let owner = owner_address_of(contract_of(txn)?).ok_or("unknown owner address of the contract")?;
+    let query = r#"query($address: Address!) {
+        account(address: $address) { permissions { id name threshold operations keys { address weight } } }
+    }"#;
+    let resp = client.query(query, json!({ "address": owner.to_string() }))?;
+    let permissions = resp["account"]["permissions"]
+        .as_array()
+        .ok_or("invalid account permissions")?;
+    permissions
+        .iter()
+        .map(Permission::from_json)
+        .find(|perm| perm.as_ref().map(|perm| perm.id == permission_id).unwrap_or(true))
+        .unwrap_or_else(|| Err(format!("permission #{} not found of {}", permission_id, owner).into()))
+}
+
+/// Start a partially-signed transaction from a (signed or unsigned) transaction.
+fn create(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let raw = hex::decode(matches.value_of("TRANSACTION").expect("required in cli.yml; qed"))?;
+    let mut txn = Transaction::decode(&raw[..])?;
+
+    if let Some(permission_id) = matches.value_of("permission-id") {
+        let permission_id = permission_id.parse()?;
+        let cntr = txn
+            .raw_data
+            .as_mut()
+            .and_then(|raw| raw.contract.as_mut())
+            .ok_or("transaction without contract")?;
+        if cntr.permission_id != permission_id {
+            cntr.permission_id = permission_id;
+            // Changing the permission id changes the transaction hash.
+            if !txn.signatures.is_empty() {
+                eprintln!("permission id changed, existing signatures dropped");
+                txn.signatures.clear();
+            }
+        }
+    }
+
+    let pst = PartiallySignedTransaction::from_transaction(txn)?;
+    pst.save(matches.value_of("FILE").expect("required in cli.yml; qed"))?;
+    eprintln!("collected signatures => {}", pst.signatures.len());
+    Ok(())
+}
+
+fn sign(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let path = matches.value_of("FILE").expect("required in cli.yml; qed");
+    let key = key_of(matches)?;
+    let mut pst = PartiallySignedTransaction::load(path)?;
+    let txn = pst.unsigned_transaction()?;
+
+    let sig = key.private().sign_digest(txn.hash.as_bytes())?;
+    let signer = pst.add_signature(&txn, sig.into())?;
+    pst.save(path)?;
+    eprintln!("signed by {}, collected signatures => {}", signer, pst.signatures.len());
+    Ok(())
+}
+
+fn status(client: &GraphQLClient, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let pst = PartiallySignedTransaction::load(matches.value_of("FILE").expect("required in cli.yml; qed"))?;
+    let txn = pst.unsigned_transaction()?;
+    let perm = permission_of(client, &txn, pst.permission_id)?;
+    let signers = pst.signers()?;
+
+    println!("txid:       {:?}", txn.hash);
+    println!("permission: #{} {}", perm.id, perm.name);
+    if !perm.allows(contract_of(&txn)?.r#type) {
+        println!("!! contract type is not allowed by the permission");
+    }
+    for (addr, weight) in &perm.keys {
+        let mark = if signers.contains(addr) { "x" } else { " " };
+        println!("  [{}] {} weight={}", mark, addr, weight);
+    }
+    for signer in signers.iter().filter(|signer| perm.weight_of(signer).is_none()) {
+        println!("  [!] {} is not a key of the permission", signer);
+    }
+    println!("weight:     {}/{}", perm.collected_weight(&signers), perm.threshold);
+    Ok(())
+}
+
+/// Check the threshold and output the signed transaction.
+fn finalize(client: &GraphQLClient, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let pst = PartiallySignedTransaction::load(matches.value_of("FILE").expect("required in cli.yml; qed"))?;
+    let txn = pst.signed_transaction()?;
+    let perm = permission_of(client, &txn, pst.permission_id)?;
+    let signers = pst.signers()?;
+
+    if !perm.allows(contract_of(&txn)?.r#type) {
+        return Err(format!("contract type is not allowed by permission #{}", perm.id).into());
+    }
+    if let Some(signer) = signers.iter().find(|signer| perm.weight_of(signer).is_none()) {
+        return Err(format!("{} is not a key of permission #{}", signer, perm.id).into());
+    }
+    let weight = perm.collected_weight(&signers);
+    if weight < perm.threshold {
+        return Err(format!("weight {} is less than threshold {}", weight, perm.threshold).into());
+    }
+
+    output(client, matches, &txn)
+}
+
+pub fn main(client: &GraphQLClient, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    match matches.subcommand() {
+        ("create", Some(arg_matches)) => create(arg_matches),
+        ("sign", Some(arg_matches)) => sign(arg_matches),
+        ("status", Some(arg_matches)) => status(client, arg_matches),
+        ("finalize", Some(arg_matches)) => finalize(client, arg_matches),
+        _ => {
+            eprintln!("{}", matches.usage());
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain::TransactionBuilder;
+    use keys::KeyPair;
+    use proto::contract as contract_pb;
+
+    #[test]
+    fn test_collect_signatures() {
+        let owner = KeyPair::generate();
+        let signers = vec![KeyPair::generate(), KeyPair::generate(), KeyPair::generate()];
+        let transfer = contract_pb::TransferContract {
+            owner_address: owner.address().as_bytes().to_vec(),
+            to_address: owner.address().as_bytes().to_vec(),
+            amount: 1,
+        };
+        let txn = TransactionBuilder::new(&transfer)
+            .permission_id(2)
+            .sign(signers[0].private())
+            .unwrap();
+
+        let mut pst = PartiallySignedTransaction::from_transaction(txn.raw.clone()).unwrap();
+        assert_eq!(pst.permission_id, 2);
+        assert_eq!(pst.signers().unwrap(), vec![signers[0].address()]);
+
+        let unsigned = pst.unsigned_transaction().unwrap();
+        assert_eq!(unsigned.hash, txn.hash);
+        let sig = signers[1].private().sign_digest(unsigned.hash.as_bytes()).unwrap();
+        pst.add_signature(&unsigned, sig.into()).unwrap();
+        let sig = signers[1].private().sign_digest(unsigned.hash.as_bytes()).unwrap();
+        assert!(pst.add_signature(&unsigned, sig.into()).is_err());
+
+        let perm = Permission {
+            id: 2,
+            name: "active".into(),
+            threshold: 3,
+            // TransferContract = 1
+            operations: Some(vec![0b0000_0010]),
+            keys: signers.iter().map(|kp| (kp.address(), 1)).collect(),
+        };
+        assert!(perm.allows(1));
+        assert!(!perm.allows(2));
+        assert_eq!(perm.collected_weight(&pst.signers().unwrap()), 2);
+
+        let sig = signers[2].private().sign_digest(unsigned.hash.as_bytes()).unwrap();
+        pst.add_signature(&unsigned, sig.into()).unwrap();
+        assert_eq!(perm.collected_weight(&pst.signers().unwrap()), 3);
+        assert_eq!(
+            pst.signed_transaction().unwrap().recover_owner().unwrap(),
+            signers.iter().map(|kp| kp.address()).collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,418 @@
+//! Wallet commands, building and signing transactions offline.
+
+use std::error::Error;
+use std::fs;
+
+use chain::builder::DEFAULT_EXPIRATION;
+use chain::{BlockHeader, ContractParameter, IndexedBlockHeader, IndexedTransaction, TransactionBuilder, H256};
+use clap::ArgMatches;
+use config::WitnessConfig;
+use context::read_keystore_password;
+use ethabi::param_type::{ParamType, Reader};
+use ethabi::token::{LenientTokenizer, Token, Tokenizer};
+use keys::{Address, DerivationPath, ExtendedPrivate, KeyPair, Keystore, Mnemonic, Private};
+use prost::Message;
+use proto::chain::block_header::Raw as BlockHeaderRaw;
+use proto::chain::Transaction;
+use proto::common::{ResourceCode, Vote};
+use proto::contract as contract_pb;
+
+use super::client::GraphQLClient;
+use super::multisig;
+
+fn password_of(matches: &ArgMatches) -> Result<String, Box<dyn Error>> {
+    read_keystore_password(
+        matches.value_of("password-file"),
+        &WitnessConfig::default().password_env,
+    )
+}
+
+/// The signing key, from `--private-key` or `--keystore`.
+pub(super) fn key_of(matches: &ArgMatches) -> Result<KeyPair, Box<dyn Error>> {
+    let private = if let Some(path) = matches.value_of("keystore") {
+        let keystore = Keystore::from_json(&fs::read_to_string(path)?)?;
+        keystore.decrypt(password_of(matches)?.as_bytes())?
+    } else if let Some(raw) = matches.value_of("private-key") {
+        raw.parse::<Private>()?
+    } else {
+        return Err("either --private-key or --keystore is required".into());
+    };
+    Ok(KeyPair::from_private(private)?)
+}
+
+fn address_of(matches: &ArgMatches, name: &str) -> Result<Address, Box<dyn Error>> {
+    Ok(matches.value_of(name).expect("required in cli.yml; qed").parse()?)
+}
+
+fn amount_of(matches: &ArgMatches) -> Result<i64, Box<dyn Error>> {
+    Ok(matches.value_of("amount").expect("required in cli.yml; qed").parse()?)
+}
+
+/// Reference block of TaPoS, from `--ref-block HASH` offline, or the latest block of the node.
+fn ref_block_of(client: &GraphQLClient, matches: &ArgMatches) -> Result<IndexedBlockHeader, Box<dyn Error>> {
+    match matches.value_of("ref-block") {
+        Some(hash) => {
+            let hash = hex::decode(hash.trim_start_matches("0x"))?;
+            if hash.len() != 32 {
+                return Err("invalid block hash".into());
+            }
+            // Block timestamp is unknown offline, expiration is relative to now.
+            let header = BlockHeader {
+                raw_data: Some(BlockHeaderRaw {
+                    timestamp: chrono::Utc::now().timestamp_millis(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            Ok(IndexedBlockHeader::new(H256::from_slice(&hash), header))
+        }
+        None => client.latest_block_header(),
+    }
+}
+
+/// Build, sign, print and optionally broadcast the transaction.
+fn sign_and_send<C: ContractParameter>(
+    client: &GraphQLClient,
+    matches: &ArgMatches,
+    key: &KeyPair,
+    contract: &C,
+) -> Result<(), Box<dyn Error>> {
+    let ref_block = ref_block_of(client, matches)?;
+    let expiration = match matches.value_of("expiration") {
+        Some(secs) => ref_block.timestamp() + secs.parse::<i64>()? * 1_000,
+        None => ref_block.timestamp() + DEFAULT_EXPIRATION,
+    };
+
+    let mut builder = TransactionBuilder::new(contract)
+        .ref_block(&ref_block)
+        .expiration(expiration);
+    if let Some(fee_limit) = matches.value_of("fee-limit") {
+        builder = builder.fee_limit(fee_limit.parse()?);
+    }
+    if let Some(memo) = matches.value_of("memo") {
+        builder = builder.memo(memo);
+    }
+    if let Some(permission_id) = matches.value_of("permission-id") {
+        builder = builder.permission_id(permission_id.parse()?);
+    }
+    let txn = builder.sign(key.private())?;
+
+    output(client, matches, &txn)
+}
+
+/// Print the transaction, and broadcast if `--broadcast`.
+pub(super) fn output(
+    client: &GraphQLClient,
+    matches: &ArgMatches,
+    txn: &IndexedTransaction,
+) -> Result<(), Box<dyn Error>> {
+    let mut raw = Vec::with_capacity(255);
+    txn.raw.encode(&mut raw)?;
+
+    eprintln!("txid => {:?}", txn.hash);
+    println!("{}", hex::encode(&raw));
+    if matches.is_present("broadcast") {
+        eprintln!("broadcasted => {}", client.send_raw_transaction(&raw)?);
+    }
+    Ok(())
+}
+
+fn keygen(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let key = if matches.is_present("mnemonic") {
+        let word_count = matches
+            .value_of("words")
+            .expect("has default in cli.yml; qed")
+            .parse()?;
+        let mnemonic = Mnemonic::generate(word_count)?;
+        let path = DerivationPath::tron(0, 0)?;
+        let xprv = ExtendedPrivate::from_seed(&mnemonic.to_seed(""))?;
+        println!("mnemonic: {}", mnemonic);
+        println!("path:    {}", path);
+        xprv.derive(&path)?.key_pair()
+    } else {
+        KeyPair::generate()
+    };
+
+    if let Some(path) = matches.value_of("keystore") {
+        let keystore = Keystore::encrypt(key.private(), password_of(matches)?.as_bytes())?;
+        fs::write(path, keystore.to_json())?;
+        println!("address: {}", key.address());
+        println!("keystore saved to {}", path);
+    } else {
+        println!("{}", key);
+    }
+    Ok(())
+}
+
+fn address(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let addr = address_of(matches, "ADDRESS")?;
+    println!("base58: {}", addr);
+    println!("hex:    {}", hex::encode(addr.as_bytes()));
+    println!("tvm:    0x{}", hex::encode(addr.as_tvm_bytes()));
+    Ok(())
+}
+
+fn transfer(client: &GraphQLClient, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let key = key_of(matches)?;
+    let to = address_of(matches, "to")?;
+    let amount = amount_of(matches)?;
+
+    match matches.value_of("token") {
+        Some(token_id) => {
+            let cntr = contract_pb::TransferAssetContract {
+                owner_address: key.address().as_bytes().to_vec(),
+                to_address: to.as_bytes().to_vec(),
+                asset_name: token_id.parse::<i64>()?.to_string(),
+                amount,
+            };
+            sign_and_send(client, matches, &key, &cntr)
+        }
+        None => {
+            let cntr = contract_pb::TransferContract {
+                owner_address: key.address().as_bytes().to_vec(),
+                to_address: to.as_bytes().to_vec(),
+                amount,
+            };
+            sign_and_send(client, matches, &key, &cntr)
+        }
+    }
+}
+
+fn freeze(client: &GraphQLClient, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let key = key_of(matches)?;
+    let resource = match matches.value_of("resource").expect("has default in cli.yml; qed") {
+        "bandwidth" => ResourceCode::Bandwidth,
+        "energy" => ResourceCode::Energy,
+        _ => unreachable!("checked by possible_values in cli.yml; qed"),
+    };
+    let receiver_address = match matches.value_of("receiver") {
+        Some(_) => address_of(matches, "receiver")?.as_bytes().to_vec(),
+        None => vec![],
+    };
+
+    let cntr = contract_pb::FreezeBalanceContract {
+        owner_address: key.address().as_bytes().to_vec(),
+        frozen_balance: amount_of(matches)?,
+        frozen_duration: matches
+            .value_of("duration")
+            .expect("has default in cli.yml; qed")
+            .parse()?,
+        resource: resource as i32,
+        receiver_address,
+    };
+    sign_and_send(client, matches, &key, &cntr)
+}
+
+fn vote(client: &GraphQLClient, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let key = key_of(matches)?;
+    let votes = matches
+        .values_of("vote")
+        .expect("required in cli.yml; qed")
+        .map(|spec| {
+            let mut parts = spec.splitn(2, ':');
+            let addr: Address = parts.next().unwrap().parse()?;
+            let count: i64 = parts.next().ok_or("vote should be WITNESS:COUNT")?.parse()?;
+            Ok(Vote {
+                vote_address: addr.as_bytes().to_vec(),
+                vote_count: count,
+            })
+        })
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+
+    let cntr = contract_pb::VoteWitnessContract {
+        owner_address: key.address().as_bytes().to_vec(),
+        votes,
+        ..Default::default()
+    };
+    sign_and_send(client, matches, &key, &cntr)
+}
+
+/// Split top-level types of a method signature, `transfer(address,uint256)`.
+fn parse_method_signature(method: &str) -> Result<(&str, Vec<ParamType>), Box<dyn Error>> {
+    let lparen = method.find('(').ok_or("invalid method signature")?;
+    if !method.ends_with(')') {
+        return Err("invalid method signature".into());
+    }
+    let name = &method[..lparen];
+    let inner = &method[lparen + 1..method.len() - 1];
+
+    let mut types = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                types.push(Reader::read(&inner[start..i])?);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if !inner.is_empty() {
+        types.push(Reader::read(&inner[start..])?);
+    }
+    Ok((name, types))
+}
+
+/// Lenient tokenizer, with addresses in any Tron format, also inside arrays and tuples.
+struct TronTokenizer;
+
+impl Tokenizer for TronTokenizer {
+    fn tokenize_address(value: &str) -> Result<[u8; 20], ethabi::Error> {
+        match value.parse::<Address>() {
+            Ok(addr) => {
+                let mut raw = [0u8; 20];
+                raw.copy_from_slice(addr.as_tvm_bytes());
+                Ok(raw)
+            }
+            // Hex addresses, which might be stripped of the 0x prefix.
+            Err(_) => LenientTokenizer::tokenize_address(value),
+        }
+    }
+
+    fn tokenize_string(value: &str) -> Result<String, ethabi::Error> {
+        LenientTokenizer::tokenize_string(value)
+    }
+
+    fn tokenize_bool(value: &str) -> Result<bool, ethabi::Error> {
+        LenientTokenizer::tokenize_bool(value)
+    }
+
+    fn tokenize_bytes(value: &str) -> Result<Vec<u8>, ethabi::Error> {
+        LenientTokenizer::tokenize_bytes(value)
+    }
+
+    fn tokenize_fixed_bytes(value: &str, len: usize) -> Result<Vec<u8>, ethabi::Error> {
+        LenientTokenizer::tokenize_fixed_bytes(value, len)
+    }
+
+    fn tokenize_uint(value: &str) -> Result<[u8; 32], ethabi::Error> {
+        LenientTokenizer::tokenize_uint(value)
+    }
+
+    fn tokenize_int(value: &str) -> Result<[u8; 32], ethabi::Error> {
+        LenientTokenizer::tokenize_int(value)
+    }
+}
+
+/// Tokenize an argument. Addresses can be in any Tron format.
+fn tokenize_arg(param: &ParamType, arg: &str) -> Result<Token, Box<dyn Error>> {
+    TronTokenizer::tokenize(param, arg).map_err(|e| format!("invalid argument {:?}: {}", arg, e).into())
+}
+
+fn trigger(client: &GraphQLClient, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let key = key_of(matches)?;
+    let contract = address_of(matches, "contract")?;
+
+    let data = match (matches.value_of("method"), matches.value_of("data")) {
+        (Some(method), None) => {
+            let (name, types) = parse_method_signature(method)?;
+            let args: Vec<_> = matches.values_of("arg").map(|vals| vals.collect()).unwrap_or_default();
+            if args.len() != types.len() {
+                return Err(format!("expected {} arguments, got {}", types.len(), args.len()).into());
+            }
+            let tokens = types
+                .iter()
+                .zip(args)
+                .map(|(param, arg)| tokenize_arg(param, arg))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let mut data = ethabi::short_signature(name, &types).to_vec();
+            data.extend(ethabi::encode(&tokens));
+            data
+        }
+        (None, Some(data)) => hex::decode(data.trim_start_matches("0x"))?,
+        _ => return Err("either --method or --data is required".into()),
+    };
+
+    let mut cntr = contract_pb::TriggerSmartContract {
+        owner_address: key.address().as_bytes().to_vec(),
+        contract_address: contract.as_bytes().to_vec(),
+        data,
+        ..Default::default()
+    };
+    if let Some(value) = matches.value_of("value") {
+        cntr.call_value = value.parse()?;
+    }
+    sign_and_send(client, matches, &key, &cntr)
+}
+
+/// Append a signature to a (partially) signed transaction.
+fn sign(client: &GraphQLClient, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let key = key_of(matches)?;
+    let raw = hex::decode(matches.value_of("TRANSACTION").expect("required in cli.yml; qed"))?;
+    let mut txn = IndexedTransaction::from_raw(Transaction::decode(&raw[..])?).ok_or("invalid transaction")?;
+    txn.sign(key.private())?;
+
+    output(client, matches, &txn)
+}
+
+fn broadcast(client: &GraphQLClient, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let raw = hex::decode(matches.value_of("TRANSACTION").expect("required in cli.yml; qed"))?;
+    // Decode first, to fail early.
+    Transaction::decode(&raw[..])?;
+    println!("{}", client.send_raw_transaction(&raw)?);
+    Ok(())
+}
+
+pub fn main(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let client = GraphQLClient::new(matches.value_of("endpoint").expect("has default in cli.yml; qed"));
+
+    match matches.subcommand() {
+        ("keygen", Some(arg_matches)) => keygen(arg_matches),
+        ("address", Some(arg_matches)) => address(arg_matches),
+        ("transfer", Some(arg_matches)) => transfer(&client, arg_matches),
+        ("freeze", Some(arg_matches)) => freeze(&client, arg_matches),
+        ("vote", Some(arg_matches)) => vote(&client, arg_matches),
+        ("trigger", Some(arg_matches)) => trigger(&client, arg_matches),
+        ("sign", Some(arg_matches)) => sign(&client, arg_matches),
+        ("broadcast", Some(arg_matches)) => broadcast(&client, arg_matches),
+        ("multisig", Some(arg_matches)) => multisig::main(&client, arg_matches),
+        _ => {
+            eprintln!("{}", matches.usage());
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_method_signature() {
+        let (name, types) = parse_method_signature("transfer(address,uint256)").unwrap();
+        assert_eq!(name, "transfer");
+        assert_eq!(types, vec![ParamType::Address, ParamType::Uint(256)]);
+
+        let (_, types) = parse_method_signature("f(uint256[2],bytes32[])").unwrap();
+        assert_eq!(
+            types,
+            vec![
+                ParamType::FixedArray(Box::new(ParamType::Uint(256)), 2),
+                ParamType::Array(Box::new(ParamType::FixedBytes(32)))
+            ]
+        );
+        assert_eq!(parse_method_signature("totalSupply()").unwrap().1, vec![]);
+    }
+
+    #[test]
+    fn test_tokenize_arg() {
+        let addr: Address = "TCCcBZEdTHmS1NfFtCYfwpjBKeTv515n71".parse().unwrap();
+        let token = Token::Address(ethabi::Address::from_slice(addr.as_tvm_bytes()));
+
+        let (_, types) = parse_method_signature("f(address,address[],(uint256,address))").unwrap();
+        assert_eq!(tokenize_arg(&types[0], &addr.to_string()).unwrap(), token);
+        assert_eq!(
+            tokenize_arg(&types[1], &format!("[{},0x{}]", addr, hex::encode(addr.as_tvm_bytes()))).unwrap(),
+            Token::Array(vec![token.clone(), token.clone()])
+        );
+        assert_eq!(
+            tokenize_arg(&types[2], &format!("(1,{})", addr)).unwrap(),
+            Token::Tuple(vec![Token::Uint(1u64.into()), token])
+        );
+        assert!(tokenize_arg(&types[1], "[TNotAnAddress]").is_err());
+    }
+}
@@ -0,0 +1,303 @@
+//! Shielded TRC20 client commands, talking to a running node's GraphQL API.
+
+use std::error::Error;
+use std::str::FromStr;
+
+use clap::ArgMatches;
+use keys::Address;
+use primitive_types::U256;
+use rand::rngs::OsRng;
+use serde_json::{json, Value};
+use zcash_primitives::merkle_tree::MerklePath;
+use zcash_primitives::note_encryption::Memo;
+use zcash_primitives::primitives::Rseed;
+use zcash_primitives::sapling::Node;
+use zcash_primitives::transaction::components::Amount;
+use ztron::builder::{load_tx_prover, parse_merkle_path, parse_rcm, Builder};
+use ztron::keys::{ZAddress, ZKey};
+
+use super::client::GraphQLClient;
+
+impl GraphQLClient {
+    /// Scaling factor between TRC20 and shielded values.
+    fn scaling_exponent(&self, contract: &Address) -> Result<u8, Box<dyn Error>> {
+        let method_id = ethabi::short_signature("scalingFactor", &[]);
+        let ret = self.call(contract, &method_id)?;
+        let factor = U256::from_big_endian(&ret);
+        (0..=77u8)
+            .find(|&exp| U256::exp10(exp as usize) == factor)
+            .ok_or_else(|| format!("invalid scaling factor: {}", factor).into())
+    }
+
+    /// Authentication path of the note at position, against the latest root.
+    fn merkle_path(&self, contract: &Address, position: u64) -> Result<MerklePath<Node>, Box<dyn Error>> {
+        let mut data = ethabi::short_signature("getPath", &[ethabi::ParamType::Uint(256)]).to_vec();
+        data.extend_from_slice(&[0u8; 24]);
+        data.extend_from_slice(&position.to_be_bytes());
+        // (bytes32 root, bytes32[32] path)
+        let ret = self.call(contract, &data)?;
+        if ret.len() != 33 * 32 {
+            return Err("invalid getPath result".into());
+        }
+        Ok(parse_merkle_path(&ret[32..], position)?)
+    }
+
+    fn shielded_notes(
+        &self,
+        contract: &Address,
+        zkey: &ZKey,
+        from_block: i64,
+        to_block: i64,
+    ) -> Result<Vec<Value>, Box<dyn Error>> {
+        let query = r#"query($contract: Address!, $ivk: Bytes32!, $ak: Bytes32, $nk: Bytes32, $from: Long, $to: Long) {
+            shieldedNotes(contract: $contract, ivk: $ivk, ak: $ak, nk: $nk, fromBlock: $from, toBlock: $to) {
+                position value paymentAddress rcm memo nullifier isSpent blockNumber transactionHash
+            }
+        }"#;
+        let variables = json!({
+            "contract": contract.to_string(),
+            "ivk": hex::encode(zkey.ivk()),
+            "ak": hex::encode(zkey.ak()),
+            "nk": hex::encode(zkey.nk()),
+            "from": from_block.to_string(),
+            "to": to_block.to_string(),
+        });
+        let mut resp = self.query(query, variables)?;
+        match resp["shieldedNotes"].take() {
+            Value::Array(notes) => Ok(notes),
+            _ => Err("invalid shieldedNotes result".into()),
+        }
+    }
+}
+
+fn zkey_of(matches: &ArgMatches) -> Result<ZKey, Box<dyn Error>> {
+    let sk = hex::decode(matches.value_of("sk").expect("required in cli.yml; qed"))?;
+    let d = hex::decode(matches.value_of("d").expect("required in cli.yml; qed"))?;
+    if sk.len() != 32 || d.len() != 11 {
+        return Err("invalid sk or d length".into());
+    }
+    ZKey::from_slice(&sk, &d).ok_or_else(|| "invalid diversifier".into())
+}
+
+fn contract_of(matches: &ArgMatches) -> Result<Address, Box<dyn Error>> {
+    Ok(matches
+        .value_of("contract")
+        .expect("required in cli.yml; qed")
+        .parse()?)
+}
+
+fn memo_of(matches: &ArgMatches) -> Result<Option<Memo>, Box<dyn Error>> {
+    match matches.value_of("memo") {
+        Some(text) => Ok(Some(Memo::from_bytes(text.as_bytes()).ok_or("memo is too long")?)),
+        None => Ok(None),
+    }
+}
+
+fn amount_of(matches: &ArgMatches) -> Result<u64, Box<dyn Error>> {
+    Ok(matches.value_of("amount").expect("required in cli.yml; qed").parse()?)
+}
+
+/// An unspent note to be spent, fetched by `--note BLOCK:POSITION`.
+struct InputNote {
+    zaddr: ZAddress,
+    value: u64,
+    rcm: Vec<u8>,
+    position: u64,
+}
+
+fn input_notes_of(
+    client: &GraphQLClient,
+    contract: &Address,
+    zkey: &ZKey,
+    matches: &ArgMatches,
+) -> Result<Vec<InputNote>, Box<dyn Error>> {
+    let mut inputs = vec![];
+    for spec in matches.values_of("note").expect("required in cli.yml; qed") {
+        let mut parts = spec.splitn(2, ':');
+        let block_number: i64 = parts.next().unwrap().parse()?;
+        let position: u64 = parts.next().ok_or("note should be BLOCK:POSITION")?.parse()?;
+
+        let notes = client.shielded_notes(contract, zkey, block_number, block_number)?;
+        let note = notes
+            .iter()
+            .find(|note| note["position"].as_str() == Some(&position.to_string()))
+            .ok_or_else(|| format!("note {} not found", spec))?;
+        if note["isSpent"].as_bool().unwrap_or(false) {
+            return Err(format!("note {} is already spent", spec).into());
+        }
+        inputs.push(InputNote {
+            zaddr: ZAddress::from_str(note["paymentAddress"].as_str().unwrap_or_default())?,
+            value: note["value"].as_str().unwrap_or_default().parse()?,
+            rcm: hex::decode(note["rcm"].as_str().unwrap_or_default())?,
+            position,
+        });
+    }
+    Ok(inputs)
+}
+
+fn add_spends(
+    builder: &mut Builder<OsRng>,
+    client: &GraphQLClient,
+    contract: &Address,
+    zkey: &ZKey,
+    inputs: &[InputNote],
+) -> Result<(), Box<dyn Error>> {
+    for input in inputs {
+        let rcm = parse_rcm(&input.rcm)?;
+        let note = input
+            .zaddr
+            .create_note(input.value, Rseed::BeforeZip212(rcm))
+            .ok_or("invalid note")?;
+        let merkle_path = client.merkle_path(contract, input.position)?;
+        builder.add_sapling_spend(zkey.expsk().clone(), *input.zaddr.diversifier(), note, merkle_path)?;
+    }
+    Ok(())
+}
+
+fn keygen() -> Result<(), Box<dyn Error>> {
+    let zkey = ZKey::generate();
+    println!("sk = {}", hex::encode(zkey.sk()));
+    println!("d = {}", hex::encode(zkey.d()));
+    println!("ask = {}", hex::encode(zkey.ask()));
+    println!("nsk = {}", hex::encode(zkey.nsk()));
+    println!("ovk = {}", hex::encode(&zkey.ovk().0[..]));
+    println!("ak = {}", hex::encode(zkey.ak()));
+    println!("nk = {}", hex::encode(zkey.nk()));
+    println!("ivk = {}", hex::encode(zkey.ivk()));
+    println!("pk_d = {}", hex::encode(zkey.pk_d()));
+    println!("payment_address = {}", zkey.payment_address());
+    Ok(())
+}
+
+fn notes(client: &GraphQLClient, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let zkey = zkey_of(matches)?;
+    let contract = contract_of(matches)?;
+    let from_block = matches.value_of("from").expect("required in cli.yml; qed").parse()?;
+    let to_block = match matches.value_of("to") {
+        Some(num) => num.parse()?,
+        None => from_block,
+    };
+
+    for note in client.shielded_notes(&contract, &zkey, from_block, to_block)? {
+        println!("{}", serde_json::to_string_pretty(&note)?);
+    }
+    Ok(())
+}
+
+fn mint(client: &GraphQLClient, matches: &ArgMatches) -> Result<Vec<u8>, Box<dyn Error>> {
+    let zkey = zkey_of(matches)?;
+    let contract = contract_of(matches)?;
+    let amount = amount_of(matches)?;
+    let to = match matches.value_of("to") {
+        Some(addr) => ZAddress::from_str(addr)?,
+        None => zkey.payment_address().clone(),
+    };
+    let scaling_exponent = client.scaling_exponent(&contract)?;
+
+    let mut builder = Builder::new(contract, scaling_exponent);
+    builder.add_transparent_input(U256::from(amount) * U256::exp10(scaling_exponent as usize))?;
+    builder.add_sapling_output(
+        *zkey.ovk(),
+        to,
+        Amount::from_u64(amount).map_err(|_| "invalid amount")?,
+        memo_of(matches)?,
+    )?;
+
+    build(builder, matches)
+}
+
+fn transfer(client: &GraphQLClient, matches: &ArgMatches) -> Result<Vec<u8>, Box<dyn Error>> {
+    let zkey = zkey_of(matches)?;
+    let contract = contract_of(matches)?;
+    let amount = amount_of(matches)?;
+    let to = ZAddress::from_str(matches.value_of("to").expect("required in cli.yml; qed"))?;
+    let scaling_exponent = client.scaling_exponent(&contract)?;
+
+    let inputs = input_notes_of(client, &contract, &zkey, matches)?;
+    let total: u64 = inputs.iter().map(|input| input.value).sum();
+    if total < amount {
+        return Err(format!("insufficient note value: {} < {}", total, amount).into());
+    }
+
+    let mut builder = Builder::new(contract, scaling_exponent);
+    add_spends(&mut builder, client, &contract, &zkey, &inputs)?;
+    builder.add_sapling_output(
+        *zkey.ovk(),
+        to,
+        Amount::from_u64(amount).map_err(|_| "invalid amount")?,
+        memo_of(matches)?,
+    )?;
+    if total > amount {
+        let change = zkey.payment_address().clone();
+        builder.add_sapling_output(
+            *zkey.ovk(),
+            change,
+            Amount::from_u64(total - amount).map_err(|_| "invalid amount")?,
+            None,
+        )?;
+    }
+
+    build(builder, matches)
+}
+
+fn burn(client: &GraphQLClient, matches: &ArgMatches) -> Result<Vec<u8>, Box<dyn Error>> {
+    let zkey = zkey_of(matches)?;
+    let contract = contract_of(matches)?;
+    let amount = amount_of(matches)?;
+    let to: Address = matches.value_of("to").expect("required in cli.yml; qed").parse()?;
+    let scaling_exponent = client.scaling_exponent(&contract)?;
+
+    let inputs = input_notes_of(client, &contract, &zkey, matches)?;
+    if inputs.len() != 1 {
+        return Err("burn must spend exactly 1 note".into());
+    }
+    let total = inputs[0].value;
+    if total < amount {
+        return Err(format!("insufficient note value: {} < {}", total, amount).into());
+    }
+
+    let mut builder = Builder::new(contract, scaling_exponent);
+    add_spends(&mut builder, client, &contract, &zkey, &inputs)?;
+    builder.add_transparent_output(&to, U256::from(amount) * U256::exp10(scaling_exponent as usize))?;
+    if total > amount {
+        let change = zkey.payment_address().clone();
+        builder.add_sapling_output(
+            *zkey.ovk(),
+            change,
+            Amount::from_u64(total - amount).map_err(|_| "invalid amount")?,
+            None,
+        )?;
+    }
+
+    build(builder, matches)
+}
+
+/// Generate proofs, returns the trigger data of `TriggerSmartContract`.
+fn build(builder: Builder<OsRng>, matches: &ArgMatches) -> Result<Vec<u8>, Box<dyn Error>> {
+    let prover = load_tx_prover(matches.value_of("params-dir").expect("has default in cli.yml; qed"))?;
+    let (txn_type, parameters) = builder.build(&prover)?;
+
+    let mut data = txn_type.method_id().to_vec();
+    data.extend_from_slice(&parameters);
+    eprintln!("method => {}", txn_type.method_signature());
+    Ok(data)
+}
+
+pub fn main(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let client = GraphQLClient::new(matches.value_of("endpoint").expect("has default in cli.yml; qed"));
+
+    let data = match matches.subcommand() {
+        ("keygen", _) => return keygen(),
+        ("notes", Some(arg_matches)) => return notes(&client, arg_matches),
+        ("mint", Some(arg_matches)) => mint(&client, arg_matches)?,
+        ("transfer", Some(arg_matches)) => transfer(&client, arg_matches)?,
+        ("burn", Some(arg_matches)) => burn(&client, arg_matches)?,
+        _ => {
+            eprintln!("{}", matches.usage());
+            return Ok(());
+        }
+    };
+    // Ready to be wrapped in a TriggerSmartContract to the shielded contract.
+    println!("{}", hex::encode(data));
+    Ok(())
+}
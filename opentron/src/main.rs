@@ -38,7 +38,20 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let _scope_guard = slog_scope::set_global_logger(logger);
     let _log_guard = slog_stdlog::init().unwrap();
 
-    let config_file = matches.value_of("config").expect("has default in cli.yml; qed");
+    // Client commands, without a node context.
+    match matches.subcommand() {
+        ("ztron", Some(arg_matches)) => return opentron::commands::ztron::main(arg_matches),
+        ("wallet", Some(arg_matches)) => return opentron::commands::wallet::main(arg_matches),
+        ("init", Some(arg_matches)) => return opentron::commands::init::main(arg_matches),
+        _ => {}
+    }
+
+    // The developer chain has its own config.
+    let config_file = match matches.subcommand() {
+        ("devnet", Some(arg_matches)) => opentron::commands::devnet::init_datadir(arg_matches)?,
+        _ => matches.value_of("config").expect("has default in cli.yml; qed").into(),
+    };
+    let config_file = config_file.to_str().ok_or("invalid config path")?;
 
     // ! #[tokio::main] runner
     let rt = tokio::runtime::Builder::new_multi_thread()
@@ -48,7 +61,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .build()?;
 
     slog_info!(slog_scope::logger(), "use config file"; "path" => config_file);
-    let mut ctx = AppContext::from_config(config_file)?;
+    let mut ctx = match matches.subcommand() {
+        ("api", Some(arg_matches)) => opentron::commands::api::open_context(config_file, arg_matches)?,
+        _ => AppContext::from_config(config_file)?,
+    };
     let outbound_ip = get_my_ip().unwrap_or("127.0.0.1".into());
     info!("outbound ip address: {}", outbound_ip);
     ctx.outbound_ip = outbound_ip;
@@ -64,8 +80,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             let fut = opentron::commands::fix::main(ctx, arg_matches);
             rt.block_on(fut)
         }
-        ("dev", Some(_)) => {
-            let fut = opentron::commands::dev::main(ctx);
+        ("dev", Some(arg_matches)) => {
+            let fut = opentron::commands::dev::main(ctx, arg_matches);
+            rt.block_on(fut)
+        }
+        ("devnet", Some(arg_matches)) => {
+            let fut = opentron::commands::devnet::main(ctx, arg_matches);
+            rt.block_on(fut)
+        }
+        ("api", Some(arg_matches)) => {
+            let fut = opentron::commands::api::main(ctx, arg_matches);
             rt.block_on(fut)
         }
         _ => {
@@ -11,15 +11,23 @@ use primitive_types::H256;
 use ::state::keys;
 use chain::{IndexedBlockHeader, IndexedTransaction};
 use context::AppContext;
+use manager::governance::maintenance::MaintenanceUtil;
+use manager::governance::reward::RewardUtil;
+use manager::resource::{BandwidthUtil, EnergyUtil};
+use manager::Manager;
 use proto::state;
+use ztron::keys::ZAddress;
+use ztron::scanner::{memo_bytes, NoteScanner, SentNote};
 
-use super::contract::{AccountType, Contract};
+use super::contract::{AccountType, Contract, ResourceCode};
 use super::model::NodeInfo;
 use super::scalar::{Address, Bytes, Bytes32, Long};
 
 const CODE_VERSION: &'static str = "0.1.0";
 const API_VERSION: &'static str = "0.1.0";
 const MAX_NUMBER_OF_BATCH_ITEMS_PER_REQUEST: i64 = 1000;
+/// All operations allowed, except AccountPermissionUpdateContract. Same as java-tron.
+const DEFAULT_ACTIVE_OPERATIONS: &str = "7fff1fc0033e0000000000000000000000000000000000000000000000000000";
 
 /// Account is an Tron account.
 pub struct Account {
@@ -30,7 +38,7 @@ pub struct Account {
 impl Account {
     fn require_inner(&self, ctx: &Context<'_>) -> Result<()> {
         if self.inner.read().unwrap().is_none() {
-            let ref manager = ctx.data_unchecked::<Arc<AppContext>>().manager.read().unwrap();
+            let manager = ctx.data_unchecked::<Arc<Manager>>();
             let acct = manager
                 .state()
                 .get(&keys::Account(self.address.0))?
@@ -63,7 +71,7 @@ impl Account {
         if inner.as_ref().unwrap().r#type != state::AccountType::Contract as i32 {
             return Ok(Bytes(vec![]));
         }
-        let ref manager = ctx.data_unchecked::<Arc<AppContext>>().manager.read().unwrap();
+        let manager = ctx.data_unchecked::<Arc<Manager>>();
         manager
             .state()
             .get(&keys::ContractCode(self.address.0))
@@ -80,7 +88,7 @@ impl Account {
         if inner.as_ref().unwrap().r#type != state::AccountType::Contract as i32 {
             return Ok(Bytes32::from(H256::zero()));
         }
-        let ref manager = ctx.data_unchecked::<Arc<AppContext>>().manager.read().unwrap();
+        let manager = ctx.data_unchecked::<Arc<Manager>>();
         let val = manager
             .state()
             .get(&keys::ContractStorage(self.address.0, slot.0))?
@@ -129,6 +137,259 @@ impl Account {
         let inner = self.inner.read().unwrap();
         Ok(inner.as_ref().unwrap().tron_power().into())
     }
+
+    /// TRX frozen by the account for itself.
+    async fn frozen(&self, ctx: &Context<'_>) -> Result<Vec<FrozenBalance>> {
+        self.require_inner(ctx)?;
+        let manager = ctx.data_unchecked::<Arc<Manager>>();
+        let mut frozen = vec![];
+        if let Some(del) = manager
+            .state()
+            .get(&keys::ResourceDelegation(self.address.0, self.address.0))?
+        {
+            if del.amount_for_bandwidth > 0 {
+                frozen.push(FrozenBalance {
+                    resource: ResourceCode::Bandwidth,
+                    amount: del.amount_for_bandwidth.into(),
+                    expiration: del.expiration_timestamp_for_bandwidth.into(),
+                });
+            }
+            if del.amount_for_energy > 0 {
+                frozen.push(FrozenBalance {
+                    resource: ResourceCode::Energy,
+                    amount: del.amount_for_energy.into(),
+                    expiration: del.expiration_timestamp_for_energy.into(),
+                });
+            }
+        }
+        Ok(frozen)
+    }
+
+    /// TRX frozen by the account, delegating resource to others.
+    async fn delegated_out(&self, ctx: &Context<'_>) -> Result<Vec<ResourceDelegation>> {
+        self.require_inner(ctx)?;
+        let manager = ctx.data_unchecked::<Arc<Manager>>();
+        let to_addrs = manager
+            .state()
+            .get(&keys::ResourceDelegationIndex(self.address.0))?
+            .unwrap_or_default();
+        let mut delegations = vec![];
+        for to_addr in to_addrs.into_iter().filter(|addr| addr != &self.address.0) {
+            if let Some(del) = manager
+                .state()
+                .get(&keys::ResourceDelegation(self.address.0, to_addr))?
+            {
+                delegations.push(ResourceDelegation::from(&del));
+            }
+        }
+        Ok(delegations)
+    }
+
+    /// TRX frozen by others, delegating resource to the account.
+    async fn delegated_in(&self, ctx: &Context<'_>) -> Result<Vec<ResourceDelegation>> {
+        self.require_inner(ctx)?;
+        let manager = ctx.data_unchecked::<Arc<Manager>>();
+        let from_addrs = manager
+            .state()
+            .get(&keys::ResourceDelegationReverseIndex(self.address.0))?
+            .unwrap_or_default();
+        let mut delegations = vec![];
+        for from_addr in from_addrs.into_iter().filter(|addr| addr != &self.address.0) {
+            if let Some(del) = manager
+                .state()
+                .get(&keys::ResourceDelegation(from_addr, self.address.0))?
+            {
+                delegations.push(ResourceDelegation::from(&del));
+            }
+        }
+        Ok(delegations)
+    }
+
+    /// Bandwidth and energy usage at the current block, decayed as in resource consumption.
+    async fn resource(&self, ctx: &Context<'_>) -> Result<AccountResource> {
+        self.require_inner(ctx)?;
+        let inner = self.inner.read().unwrap();
+        let acct = inner.as_ref().unwrap();
+        let manager = ctx.data_unchecked::<Arc<Manager>>();
+        let bandwidth_util = BandwidthUtil::new(manager);
+        let energy_util = EnergyUtil::new(manager);
+        Ok(AccountResource {
+            free_bandwidth_used: bandwidth_util.get_free_bandwidth_usage(acct).into(),
+            free_bandwidth_limit: bandwidth_util.get_free_bandwidth_limit().into(),
+            bandwidth_used: bandwidth_util.get_frozen_bandwidth_usage(acct).into(),
+            bandwidth_limit: bandwidth_util.calculate_global_bandwidth_limit(acct).into(),
+            energy_used: energy_util.get_energy_usage(acct).into(),
+            energy_limit: energy_util.calculate_global_energy_limit(acct).into(),
+            delegated_in_for_bandwidth: acct.delegated_frozen_amount_for_bandwidth.into(),
+            delegated_in_for_energy: acct.delegated_frozen_amount_for_energy.into(),
+            delegated_out: acct.delegated_out_amount.into(),
+        })
+    }
+
+    /// Votes cast by the account.
+    async fn votes(&self, ctx: &Context<'_>) -> Result<Vec<AccountVote>> {
+        self.require_inner(ctx)?;
+        let manager = ctx.data_unchecked::<Arc<Manager>>();
+        Ok(manager
+            .state()
+            .get(&keys::Votes(self.address.0))?
+            .map(|votes| {
+                votes
+                    .votes
+                    .iter()
+                    .map(|vote| AccountVote {
+                        witness: TryFrom::try_from(&vote.vote_address).map(Address).unwrap(),
+                        count: vote.vote_count.into(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// Unclaimed voting reward, in sun. Withdrawable together with the allowance.
+    async fn reward(&self, ctx: &Context<'_>) -> Result<Long> {
+        self.require_inner(ctx)?;
+        let manager = ctx.data_unchecked::<Arc<Manager>>();
+        Ok(RewardUtil::new(manager).query_reward(self.address.0)?.into())
+    }
+
+    /// Permissions of the account, the owner(id=0) and actives(id>=2).
+    ///
+    /// Accounts without permission settings have default permissions, with the account itself as the only key.
+    async fn permissions(&self, ctx: &Context<'_>) -> Result<Vec<AccountPermission>> {
+        self.require_inner(ctx)?;
+        let inner = self.inner.read().unwrap();
+        let acct = inner.as_ref().unwrap();
+        let default_keys = || {
+            vec![AccountPermissionKey {
+                address: self.address,
+                weight: 1.into(),
+            }]
+        };
+        let to_keys = |keys: &[state::PermissionKey]| {
+            keys.iter()
+                .map(|key| AccountPermissionKey {
+                    address: TryFrom::try_from(&key.address).map(Address).unwrap(),
+                    weight: key.weight.into(),
+                })
+                .collect()
+        };
+
+        let mut permissions = vec![match acct.owner_permission {
+            Some(ref perm) => AccountPermission {
+                id: 0,
+                name: "owner".into(),
+                threshold: perm.threshold.into(),
+                operations: None,
+                keys: to_keys(&perm.keys),
+            },
+            None => AccountPermission {
+                id: 0,
+                name: "owner".into(),
+                threshold: 1.into(),
+                operations: None,
+                keys: default_keys(),
+            },
+        }];
+        if acct.active_permissions.is_empty() {
+            permissions.push(AccountPermission {
+                id: 2,
+                name: "active".into(),
+                threshold: 1.into(),
+                operations: Some(Bytes(hex::decode(DEFAULT_ACTIVE_OPERATIONS).unwrap())),
+                keys: default_keys(),
+            });
+        }
+        for perm in &acct.active_permissions {
+            permissions.push(AccountPermission {
+                id: perm.id,
+                name: perm.permission_name.clone(),
+                threshold: perm.threshold.into(),
+                operations: Some(Bytes(perm.operations.clone())),
+                keys: to_keys(&perm.keys),
+            });
+        }
+        Ok(permissions)
+    }
+}
+
+/// FrozenBalance is TRX frozen by an account for itself.
+#[derive(SimpleObject)]
+pub struct FrozenBalance {
+    resource: ResourceCode,
+    /// Frozen amount, in sun.
+    amount: Long,
+    /// Unfreezable after the timestamp, in milliseconds.
+    expiration: Long,
+}
+
+/// ResourceDelegation is TRX frozen by an account, delegating resource to another account.
+#[derive(SimpleObject)]
+pub struct ResourceDelegation {
+    from: Address,
+    to: Address,
+    amount_for_bandwidth: Long,
+    expiration_for_bandwidth: Long,
+    amount_for_energy: Long,
+    expiration_for_energy: Long,
+}
+
+impl From<&state::ResourceDelegation> for ResourceDelegation {
+    fn from(del: &state::ResourceDelegation) -> Self {
+        ResourceDelegation {
+            from: TryFrom::try_from(&del.from_address).map(Address).unwrap(),
+            to: TryFrom::try_from(&del.to_address).map(Address).unwrap(),
+            amount_for_bandwidth: del.amount_for_bandwidth.into(),
+            expiration_for_bandwidth: del.expiration_timestamp_for_bandwidth.into(),
+            amount_for_energy: del.amount_for_energy.into(),
+            expiration_for_energy: del.expiration_timestamp_for_energy.into(),
+        }
+    }
+}
+
+/// AccountResource is the bandwidth and energy of an account.
+#[derive(SimpleObject)]
+pub struct AccountResource {
+    free_bandwidth_used: Long,
+    free_bandwidth_limit: Long,
+    /// Bandwidth used from frozen and delegated-in TRX.
+    bandwidth_used: Long,
+    bandwidth_limit: Long,
+    energy_used: Long,
+    energy_limit: Long,
+    /// Total TRX delegated by others for bandwidth, in sun.
+    delegated_in_for_bandwidth: Long,
+    /// Total TRX delegated by others for energy, in sun.
+    delegated_in_for_energy: Long,
+    /// Total TRX delegated to others, in sun.
+    delegated_out: Long,
+}
+
+/// AccountVote is the votes for a witness.
+#[derive(SimpleObject)]
+pub struct AccountVote {
+    witness: Address,
+    count: Long,
+}
+
+/// AccountPermission is an owner or active permission of an account.
+#[derive(SimpleObject)]
+pub struct AccountPermission {
+    /// Permission id, 0 for owner, 2 or greater for actives.
+    id: i32,
+    name: String,
+    /// Minimum total weight of signatures.
+    threshold: Long,
+    /// Bitmap of allowed contract types, null for owner(all allowed).
+    operations: Option<Bytes>,
+    keys: Vec<AccountPermissionKey>,
+}
+
+/// AccountPermissionKey is a key of a permission.
+#[derive(SimpleObject)]
+pub struct AccountPermissionKey {
+    address: Address,
+    weight: Long,
 }
 
 /// Asset is a TRC10 token.
@@ -178,7 +439,7 @@ impl Asset {
 
     /// Returns the amount of tokens owned by account.
     async fn balance_of(&self, ctx: &Context<'_>, account: Address) -> Result<Long> {
-        let ref manager = ctx.data_unchecked::<Arc<AppContext>>().manager.read().unwrap();
+        let manager = ctx.data_unchecked::<Arc<Manager>>();
         let acct = manager
             .state()
             .get(&keys::Account(account.0))?
@@ -201,6 +462,15 @@ enum VmStatus {
     Unknown = 13,
 }
 
+/// TIP-191 message signing scheme.
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+enum MessageVersion {
+    /// Legacy `trx.sign` of a hex string, with a fixed length in the prefix.
+    V1,
+    /// `signMessageV2`.
+    V2,
+}
+
 /// Log is a Tron event log.
 pub struct Log {
     index: i32,
@@ -520,7 +790,7 @@ impl Transaction {
 
     // NOTE: for debug
     async fn receipt(&self, ctx: &Context<'_>) -> Result<String> {
-        let ref manager = ctx.data_unchecked::<Arc<AppContext>>().manager.read().unwrap();
+        let manager = ctx.data_unchecked::<Arc<Manager>>();
         if let Some(receipt) = manager.state().get(&keys::TransactionReceipt(self.inner.hash))? {
             Ok(format!(
                 "resource_receipt={:?} vm_logs={}",
@@ -723,7 +993,7 @@ impl Block {
     /// Logs returns a filtered set of logs from this block.
     async fn logs(&self, ctx: &Context<'_>, filter: BlockFilterCriteria) -> Result<Vec<Log>> {
         self.require_txns(ctx)?;
-        let ref manager = ctx.data_unchecked::<Arc<AppContext>>().manager.read().unwrap();
+        let manager = ctx.data_unchecked::<Arc<Manager>>();
         let mut logs = vec![];
         for (index, txn) in self.transactions.read().unwrap().as_ref().unwrap().iter().enumerate() {
             if let Some(receipt) = manager.state().get(&keys::TransactionReceipt(txn.hash))? {
@@ -776,7 +1046,7 @@ pub struct Chain;
 impl Chain {
     /// Chain parameters.
     async fn parameters(&self, ctx: &Context<'_>) -> Result<Vec<ChainParameter>> {
-        let ref manager = ctx.data_unchecked::<Arc<AppContext>>().manager.read().unwrap();
+        let manager = ctx.data_unchecked::<Arc<Manager>>();
         let mut params = Vec::with_capacity(50);
         {
             let params = &mut params;
@@ -793,7 +1063,7 @@ impl Chain {
 
     /// Get a chain parameter.
     async fn parameter(&self, ctx: &Context<'_>, id: i32) -> Result<ChainParameter> {
-        let ref manager = ctx.data_unchecked::<Arc<AppContext>>().manager.read().unwrap();
+        let manager = ctx.data_unchecked::<Arc<Manager>>();
         let param = keys::ChainParameter::from_i32(id).ok_or_else(|| "invalid parameter id")?;
         let value = manager.state().must_get(&param);
         Ok(ChainParameter {
@@ -805,12 +1075,188 @@ impl Chain {
 
     /// Next maintenance time.
     async fn next_maintenance_time(&self, ctx: &Context<'_>) -> DateTime<Utc> {
-        let ref manager = ctx.data_unchecked::<Arc<AppContext>>().manager.read().unwrap();
+        let manager = ctx.data_unchecked::<Arc<Manager>>();
         let ts = manager.state().must_get(&keys::DynamicProperty::NextMaintenanceTime);
         Utc.timestamp(ts / 1_000, ts as u32 % 1_000 * 1_000_000)
     }
 }
 
+/// ShieldedNote is a note of a shielded TRC20 contract, received by an incoming viewing key.
+#[derive(SimpleObject)]
+pub struct ShieldedNote {
+    /// Position of the note commitment in the merkle tree.
+    position: Long,
+    /// Value of the note, in the shielded unit(before scaling).
+    value: Long,
+    /// Receiver payment address.
+    payment_address: String,
+    /// Note commitment randomness.
+    rcm: Bytes32,
+    /// Memo of the note, with trailing zeros stripped.
+    memo: Bytes,
+    /// Nullifier of the note, only available when ak and nk are provided.
+    nullifier: Option<Bytes32>,
+    /// Whether the note is spent in the scanned block range.
+    is_spent: bool,
+    /// Block number containing the note.
+    block_number: Long,
+    /// Hash of the transaction creating the note.
+    transaction_hash: Bytes32,
+}
+
+/// ShieldedOutgoingNote is a note sent by an outgoing viewing key, either shielded or transparent(burn).
+#[derive(SimpleObject)]
+pub struct ShieldedOutgoingNote {
+    /// Position of the note commitment in the merkle tree, null for a transparent output.
+    position: Option<Long>,
+    /// Value of the note, in the shielded unit for shielded notes, or in the TRC20 unit for burns.
+    value: String,
+    /// Receiver payment address of a shielded note.
+    payment_address: Option<String>,
+    /// Receiver address of a burn.
+    transparent_address: Option<Address>,
+    /// Memo of a shielded note, with trailing zeros stripped.
+    memo: Option<Bytes>,
+    /// Block number containing the note.
+    block_number: Long,
+    /// Hash of the transaction creating the note.
+    transaction_hash: Bytes32,
+}
+
+/// ProfileStat is the accumulated execution statistics of a contract type, a smart contract, a precompile or an opcode.
+#[derive(SimpleObject)]
+pub struct ProfileStat {
+    /// Contract type, address of the smart contract, address of the precompile, or the opcode in hex.
+    name: String,
+    /// Number of executions.
+    count: Long,
+    /// Total execution time, in microseconds.
+    elapsed: Long,
+    /// Total energy used. Always 0 for opcodes.
+    energy: Long,
+    /// Number of StateDB reads. Always 0 for precompiles and opcodes.
+    reads: Long,
+    /// Number of StateDB writes. Always 0 for precompiles and opcodes.
+    writes: Long,
+}
+
+impl ProfileStat {
+    fn new(name: String, stat: &manager::profiler::Stat) -> Self {
+        ProfileStat {
+            name,
+            count: Long(stat.count as i64),
+            elapsed: Long((stat.elapsed_nanos / 1_000) as i64),
+            energy: Long(stat.energy),
+            reads: Long(stat.reads as i64),
+            writes: Long(stat.writes as i64),
+        }
+    }
+}
+
+/// Profile is the top-N report of the execution profiler, the most time consuming first.
+#[derive(SimpleObject)]
+pub struct Profile {
+    /// Number of blocks profiled.
+    num_of_blocks: Long,
+    contract_types: Vec<ProfileStat>,
+    contracts: Vec<ProfileStat>,
+    precompiles: Vec<ProfileStat>,
+    opcodes: Vec<ProfileStat>,
+}
+
+/// Witness is an SR candidate, which produces blocks when voted into the active schedule.
+#[derive(SimpleObject)]
+pub struct Witness {
+    address: Address,
+    url: String,
+    vote_count: Long,
+    /// Percentage of rewards kept by the witness, 0 to 100. The rest goes to voters.
+    brokerage: i32,
+    total_produced: Long,
+    total_missed: Long,
+    latest_block_number: Long,
+    latest_slot_number: Long,
+    latest_block_version: i32,
+    /// Whether the witness is in the active witnesses of current schedule.
+    is_active: bool,
+}
+
+impl Witness {
+    fn new(wit: &state::Witness, is_active: bool) -> Self {
+        Witness {
+            address: TryFrom::try_from(&wit.address).map(Address).unwrap(),
+            url: wit.url.clone(),
+            vote_count: wit.vote_count.into(),
+            brokerage: wit.brokerage,
+            total_produced: wit.total_produced.into(),
+            total_missed: wit.total_missed.into(),
+            latest_block_number: wit.latest_block_number.into(),
+            latest_slot_number: wit.latest_slot_number.into(),
+            latest_block_version: wit.latest_block_version,
+            is_active,
+        }
+    }
+}
+
+/// ScheduledWitness is a standby witness in the witness schedule.
+#[derive(SimpleObject)]
+pub struct ScheduledWitness {
+    address: Address,
+    vote_count: Long,
+    brokerage: i32,
+    /// Whether the witness is one of the top 27 active witnesses.
+    is_active: bool,
+}
+
+/// WitnessSchedule is the preview of standby witnesses after the next maintenance, ordered by votes.
+#[derive(SimpleObject)]
+pub struct WitnessSchedule {
+    /// Timestamp of the next maintenance, in milliseconds.
+    next_maintenance_time: Long,
+    /// Witnesses are re-scheduled only if there are new votes in current epoch.
+    has_new_votes: bool,
+    witnesses: Vec<ScheduledWitness>,
+}
+
+/// Feed logs of the shielded TRC20 contract in the block range to the scanner.
+fn scan_shielded_logs(
+    ctx: &Context<'_>,
+    contract: &Address,
+    from_block: Option<Long>,
+    to_block: Option<Long>,
+    scanner: &mut NoteScanner,
+) -> Result<()> {
+    let ref db = ctx.data_unchecked::<Arc<AppContext>>().chain_db;
+    let manager = ctx.data_unchecked::<Arc<Manager>>();
+    let defaut_block = manager.latest_block_number();
+
+    let from_block = from_block.unwrap_or(defaut_block.into()).0;
+    let to_block = to_block.unwrap_or(defaut_block.into()).0;
+
+    if from_block > to_block {
+        return Err("fromBlock should be lower than toBlock".into());
+    }
+    if to_block - from_block > MAX_NUMBER_OF_BATCH_ITEMS_PER_REQUEST {
+        return Err(Error::from("exceeds the maximum number of blocks per request"));
+    }
+
+    for block_num in from_block..=to_block {
+        let txn_hashes = db.get_transaction_hashes_by_block_number(block_num)?;
+        for txn_hash in txn_hashes {
+            if let Some(receipt) = manager.state().get(&keys::TransactionReceipt(txn_hash))? {
+                for log_entry in receipt
+                    .vm_logs
+                    .iter()
+                    .filter(|log_entry| contract.0.as_bytes() == &log_entry.address[..])
+                {
+                    scanner.scan_log(block_num, txn_hash.as_fixed_bytes(), &log_entry.topics, &log_entry.data);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 pub struct QueryRoot;
 
 #[Object]
@@ -893,7 +1339,7 @@ impl QueryRoot {
     /// Logs returns log entries matching the provided filter.
     async fn logs(&self, ctx: &Context<'_>, filter: FilterCriteria) -> Result<Vec<Log>> {
         let ref db = ctx.data_unchecked::<Arc<AppContext>>().chain_db;
-        let ref manager = ctx.data_unchecked::<Arc<AppContext>>().manager.read().unwrap();
+        let manager = ctx.data_unchecked::<Arc<Manager>>();
         let defaut_block = manager.latest_block_number();
 
         let from_block = filter.from_block.unwrap_or(defaut_block.into()).0;
@@ -932,7 +1378,7 @@ impl QueryRoot {
     /// Syncing returns information on the current synchronisation state.
     async fn syncing(&self, ctx: &Context<'_>) -> SyncState {
         let ref db = ctx.data_unchecked::<Arc<AppContext>>().chain_db;
-        let ref manager = ctx.data_unchecked::<Arc<AppContext>>().manager.read().unwrap();
+        let manager = ctx.data_unchecked::<Arc<Manager>>();
 
         SyncState {
             current_block: Long(db.get_block_height()),
@@ -949,7 +1395,7 @@ impl QueryRoot {
 
     /// Account fetches an Tron account at the current block's state.
     async fn account(&self, ctx: &Context<'_>, address: Address) -> Result<Account> {
-        let ref manager = ctx.data_unchecked::<Arc<AppContext>>().manager.read().unwrap();
+        let manager = ctx.data_unchecked::<Arc<Manager>>();
         let acct = manager
             .state()
             .get(&keys::Account(address.0))?
@@ -975,10 +1421,11 @@ impl QueryRoot {
             call_token_value: data.token_value.map(|val| val.0).unwrap_or_default(),
         };
 
-        let ref mut manager = ctx.data_unchecked::<Arc<AppContext>>().manager.write().unwrap();
+        // Executed on a scratch layer of the snapshot, changes are dropped.
+        let mut manager = ctx.data_unchecked::<Arc<Manager>>().snapshot();
         let energy_limit = data.energy_limit.map(|val| val.0).unwrap_or(100_000_000);
 
-        let receipt = TransactionExecutor::new(manager).execute_smart_contract(&trigger, energy_limit)?;
+        let receipt = TransactionExecutor::new(&mut manager).execute_smart_contract(&trigger, energy_limit)?;
         Ok(CallResult { receipt })
     }
 
@@ -1005,7 +1452,7 @@ impl QueryRoot {
 
     /// Asset fetches an Tron asset(TRC10 token).
     async fn asset(&self, ctx: &Context<'_>, issuer: Option<Address>, id: Option<i64>) -> Result<Asset> {
-        let ref manager = ctx.data_unchecked::<Arc<AppContext>>().manager.read().unwrap();
+        let manager = ctx.data_unchecked::<Arc<Manager>>();
         let token_id = match (issuer, id) {
             (None, Some(token_id)) => token_id,
             (Some(issuer_addr), None) => {
@@ -1024,10 +1471,240 @@ impl QueryRoot {
         Ok(Asset(asset))
     }
 
+    /// VerifyMessage recovers the signer address of a TIP-191 signed message.
+    ///
+    /// Version defaults to V2. For the legacy V1 scheme, the message is the hex string that was signed.
+    async fn verify_message(
+        &self,
+        message: String,
+        signature: Bytes,
+        version: Option<MessageVersion>,
+    ) -> Result<Address> {
+        let signature = ::keys::Signature::try_from(signature.0)?;
+        let public = match version.unwrap_or(MessageVersion::V2) {
+            MessageVersion::V1 => {
+                let raw = hex::decode(message.trim_start_matches("0x"))?;
+                ::keys::Public::recover_message_v1(&raw, &signature)?
+            }
+            MessageVersion::V2 => ::keys::Public::recover_message(message.as_bytes(), &signature)?,
+        };
+        Ok(::keys::Address::from_public(&public).into())
+    }
+
+    /// Shielded notes of a shielded TRC20 contract received by the incoming viewing key.
+    ///
+    /// Provide ak and nk to compute nullifiers, so that notes spent in the block range are marked.
+    async fn shielded_notes(
+        &self,
+        ctx: &Context<'_>,
+        contract: Address,
+        ivk: Bytes32,
+        ak: Option<Bytes32>,
+        nk: Option<Bytes32>,
+        from_block: Option<Long>,
+        to_block: Option<Long>,
+    ) -> Result<Vec<ShieldedNote>> {
+        let mut scanner = NoteScanner::from_ivk(ivk.0.as_bytes()).ok_or_else(|| "invalid ivk")?;
+        match (ak, nk) {
+            (Some(ak), Some(nk)) => {
+                if !scanner.with_nullifier_key(ak.0.as_bytes(), nk.0.as_bytes()) {
+                    return Err("ak and nk do not match ivk".into());
+                }
+            }
+            (None, None) => {}
+            _ => return Err("ak and nk should be provided together".into()),
+        }
+
+        scan_shielded_logs(ctx, &contract, from_block, to_block, &mut scanner)?;
+
+        Ok(scanner
+            .into_received_notes()
+            .into_iter()
+            .map(|note| ShieldedNote {
+                position: (note.position as i64).into(),
+                value: (note.value() as i64).into(),
+                payment_address: ZAddress::from(note.to.clone()).to_string(),
+                rcm: Bytes32(H256::from(note.rcm())),
+                memo: Bytes(memo_bytes(&note.memo).to_vec()),
+                nullifier: note.nullifier.map(|nf| Bytes32(H256::from(nf))),
+                is_spent: note.is_spent,
+                block_number: note.block_number.into(),
+                transaction_hash: Bytes32(H256::from(note.txid)),
+            })
+            .collect())
+    }
+
+    /// Shielded notes of a shielded TRC20 contract sent by the outgoing viewing key.
+    async fn shielded_outgoing_notes(
+        &self,
+        ctx: &Context<'_>,
+        contract: Address,
+        ovk: Bytes32,
+        from_block: Option<Long>,
+        to_block: Option<Long>,
+    ) -> Result<Vec<ShieldedOutgoingNote>> {
+        let mut scanner = NoteScanner::from_ovk(ovk.0.as_bytes()).ok_or_else(|| "invalid ovk")?;
+
+        scan_shielded_logs(ctx, &contract, from_block, to_block, &mut scanner)?;
+
+        Ok(scanner
+            .into_sent_notes()
+            .into_iter()
+            .map(|sent| match sent {
+                SentNote::Shielded {
+                    block_number,
+                    txid,
+                    position,
+                    note,
+                    to,
+                    memo,
+                } => ShieldedOutgoingNote {
+                    position: Some((position as i64).into()),
+                    value: note.value.to_string(),
+                    payment_address: Some(ZAddress::from(to).to_string()),
+                    transparent_address: None,
+                    memo: Some(Bytes(memo_bytes(&memo).to_vec())),
+                    block_number: block_number.into(),
+                    transaction_hash: Bytes32(H256::from(txid)),
+                },
+                SentNote::Transparent {
+                    block_number,
+                    txid,
+                    to,
+                    value,
+                } => ShieldedOutgoingNote {
+                    position: None,
+                    value: value.to_string(),
+                    payment_address: None,
+                    transparent_address: Some(Address(to)),
+                    memo: None,
+                    block_number: block_number.into(),
+                    transaction_hash: Bytes32(H256::from(txid)),
+                },
+            })
+            .collect())
+    }
+
+    /// Witnesses, SR candidates ordered by votes.
+    async fn witnesses(&self, ctx: &Context<'_>) -> Result<Vec<Witness>> {
+        let manager = ctx.data_unchecked::<Arc<Manager>>();
+        let active_addrs: Vec<_> = MaintenanceUtil::new(manager)
+            .active_witnesses()
+            .into_iter()
+            .map(|(addr, _, _)| addr)
+            .collect();
+
+        let mut witnesses = vec![];
+        manager.state().for_each(|key: &keys::Witness, wit: &state::Witness| {
+            witnesses.push(Witness::new(wit, active_addrs.contains(&key.0)));
+        });
+        witnesses.sort_by(|a, b| b.vote_count.0.cmp(&a.vote_count.0));
+        Ok(witnesses)
+    }
+
+    /// Active witnesses of current schedule, in schedule order. Empty before the first maintenance.
+    async fn active_witnesses(&self, ctx: &Context<'_>) -> Result<Vec<Witness>> {
+        let manager = ctx.data_unchecked::<Arc<Manager>>();
+        MaintenanceUtil::new(manager)
+            .active_witnesses()
+            .into_iter()
+            .map(|(addr, _, _)| -> Result<Witness> {
+                let wit = manager
+                    .state()
+                    .get(&keys::Witness(addr))?
+                    .ok_or_else(|| "witness not found")?;
+                Ok(Witness::new(&wit, true))
+            })
+            .collect()
+    }
+
+    /// Witness fetches a witness by address.
+    async fn witness(&self, ctx: &Context<'_>, address: Address) -> Result<Witness> {
+        let manager = ctx.data_unchecked::<Arc<Manager>>();
+        let wit = manager
+            .state()
+            .get(&keys::Witness(address.0))?
+            .ok_or_else(|| "witness not found")?;
+        let is_active = MaintenanceUtil::new(manager)
+            .active_witnesses()
+            .iter()
+            .any(|&(addr, _, _)| addr == address.0);
+        Ok(Witness::new(&wit, is_active))
+    }
+
+    /// Witness schedule after the next maintenance, computed from votes at the current block's state.
+    async fn next_schedule(&self, ctx: &Context<'_>) -> Result<WitnessSchedule> {
+        let manager = ctx.data_unchecked::<Arc<Manager>>();
+        let next_maintenance_time = manager
+            .state()
+            .get(&keys::DynamicProperty::NextMaintenanceTime)?
+            .unwrap_or_default()
+            .into();
+        let has_new_votes = manager
+            .state()
+            .get(&keys::DynamicProperty::HasNewVotesInCurrentEpoch)?
+            .unwrap_or_default() !=
+            0;
+        let witnesses = MaintenanceUtil::new(manager)
+            .next_witness_schedule()
+            .into_iter()
+            .enumerate()
+            .map(|(i, (addr, vote_count, brokerage))| ScheduledWitness {
+                address: Address(addr),
+                vote_count: vote_count.into(),
+                brokerage: brokerage as i32,
+                is_active: i < constants::MAX_NUM_OF_ACTIVE_WITNESSES,
+            })
+            .collect();
+
+        Ok(WitnessSchedule {
+            next_maintenance_time,
+            has_new_votes,
+            witnesses,
+        })
+    }
+
     /// Chain query.
     async fn chain(&self) -> Chain {
         Chain
     }
+
+    /// Profile of block execution, or null if the profiler is not enabled. Top defaults to 10.
+    async fn profile(&self, ctx: &Context<'_>, top: Option<i32>) -> Option<Profile> {
+        let top = top.unwrap_or(10).max(0) as usize;
+        // Copied out, not to block block execution.
+        let profiler = ctx
+            .data_unchecked::<Arc<AppContext>>()
+            .manager
+            .read()
+            .unwrap()
+            .profiler()?
+            .clone();
+
+        Some(Profile {
+            num_of_blocks: Long(profiler.num_of_blocks as i64),
+            contract_types: profiler
+                .top_contract_types(top)
+                .iter()
+                .map(|(cntr_type, stat)| ProfileStat::new(format!("{:?}", cntr_type), stat))
+                .collect(),
+            contracts: profiler
+                .top_contracts(top)
+                .iter()
+                .map(|(addr, stat)| ProfileStat::new(addr.to_string(), stat))
+                .collect(),
+            precompiles: profiler
+                .top_precompiles(top)
+                .iter()
+                .map(|(addr, stat)| ProfileStat::new(format!("{:#x}", addr.to_low_u64_be()), stat))
+                .collect(),
+            opcodes: profiler
+                .top_opcodes(top)
+                .iter()
+                .map(|(opcode, stat)| ProfileStat::new(format!("{:#04x}", opcode), stat))
+                .collect(),
+        })
+    }
 }
 
 pub struct MutationRoot;
@@ -1035,8 +1712,30 @@ pub struct MutationRoot;
 #[Object]
 impl MutationRoot {
     /// SendRawTransaction sends an protobuf-encoded transaction to the network.
-    async fn send_raw_transaction(&self, _data: Bytes) -> Result<Bytes32> {
-        unimplemented!()
+    ///
+    /// NOTE: Only supported by the developer chain for now, where the transaction is packed into the next block.
+    async fn send_raw_transaction(&self, ctx: &Context<'_>, data: Bytes) -> Result<Bytes32> {
+        use chain::IndexedTransaction;
+        use prost::Message;
+        use proto::chain::Transaction;
+
+        let ctx = ctx.data_unchecked::<Arc<AppContext>>();
+        if !ctx.config.chain.dev_mode {
+            return Err("broadcasting transactions is not supported yet".into());
+        }
+
+        let txn = Transaction::decode(&*data.0)?;
+        let indexed_txn = IndexedTransaction::from_raw(txn).ok_or("invalid transaction")?;
+        // Reject early. Transactions conflicting with others are dropped when sealing the block.
+        ctx.manager
+            .write()
+            .unwrap()
+            .validate_pending_transaction(&indexed_txn)?;
+
+        let hash = indexed_txn.hash;
+        ctx.pending_transactions.lock().unwrap().push(indexed_txn);
+        ctx.new_transaction_signal.notify_one();
+        Ok(hash.into())
     }
 
     /// DryRunRawTransaction runs an protobuf-encoded transaction and returns the receipt as json.
@@ -1045,7 +1744,7 @@ impl MutationRoot {
         use prost::Message;
         use proto::chain::Transaction;
 
-        let ref mut manager = ctx.data_unchecked::<Arc<AppContext>>().manager.write().unwrap();
+        let mut manager = ctx.data_unchecked::<Arc<Manager>>().snapshot();
 
         let txn = Transaction::decode(&*data.0)?;
         let indexed_txn = IndexedTransaction::from_raw(txn).ok_or("invalid transaction")?;
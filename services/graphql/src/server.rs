@@ -28,13 +28,19 @@ pub async fn graphql_server(ctx: Arc<AppContext>, mut shutdown_signal: broadcast
         .expect("malformed endpoint address for graphql server");
 
     let schema = Schema::build(QueryRoot, MutationRoot, EmptySubscription)
-        .data(ctx)
+        .data(ctx.clone())
         .finish();
 
     let graphql_post = async_graphql_warp::graphql(schema).and_then(
-        |(schema, request): (Schema<_, _, _>, async_graphql::Request)| async move {
-            trace!("req: {:?}", request.query);
-            Ok::<_, Infallible>(async_graphql_warp::Response::from(schema.execute(request).await))
+        move |(schema, request): (Schema<_, _, _>, async_graphql::Request)| {
+            let ctx = ctx.clone();
+            async move {
+                let _guard = ctx.catch_up_lock.read().await;
+                // All resolvers of a request read from the same state snapshot.
+                let request = request.data(ctx.state_snapshot());
+                trace!("req: {:?}", request.query);
+                Ok::<_, Infallible>(async_graphql_warp::Response::from(schema.execute(request).await))
+            }
         },
     );
     let graphql_playground = warp::path::end().and(warp::get()).map(|| {
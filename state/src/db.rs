@@ -3,7 +3,10 @@
 use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::io;
 use std::iter;
+use std::mem;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 
 use ::keys::Address;
 use config::genesis::GenesisConfig;
@@ -12,91 +15,50 @@ use log::info;
 use proto::common::AccountType;
 use proto::state as state_pb;
 use rocks::prelude::*;
+use storage::{KeyValueDB, MemoryDB, RocksDB, WriteBatch};
 
 use super::keys;
 use super::parameter::default_parameters_from_config;
+use super::property::CURRENT_DB_VERSION;
 use super::DynamicProperty;
 
 pub type BoxError = Box<dyn ::std::error::Error>;
 
-pub trait KeyValueDB {
-    type Column;
-
-    /// Get a value by key.
-    fn get(&self, col: &Self::Column, key: &[u8]) -> io::Result<Option<Vec<u8>>>;
-
-    /// Get the first value matching the given prefix.
-    fn get_by_prefix(&self, col: &Self::Column, prefix: &[u8]) -> Option<Box<[u8]>>;
-
-    /// Write a batch of changes to the backing store.
-    fn write(&self, batch: &WriteBatch) -> io::Result<()>;
-
-    /// Iterate over the data for a given column.
-    fn iter<'a>(&'a self, col: &Self::Column) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a>;
-
-    /// Iterate over the data for a given column, returning all key/value pairs
-    /// where the key starts with the given prefix.
-    fn iter_with_prefix<'a>(
-        &'a self,
-        col: &Self::Column,
-        prefix: &'a [u8],
-    ) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a>;
-}
-
+#[derive(Clone)]
 pub struct OverlayWriteBatch {
-    wb: WriteBatch,
     // CF => (Key => Value)
     // TODO: replace with VecMap
-    cache: HashMap<u32, BTreeMap<Vec<u8>, Option<Vec<u8>>>>,
-}
-
-impl std::ops::Deref for OverlayWriteBatch {
-    type Target = WriteBatch;
-    fn deref(&self) -> &Self::Target {
-        &self.wb
-    }
+    cache: HashMap<usize, BTreeMap<Vec<u8>, Option<Vec<u8>>>>,
 }
 
 impl OverlayWriteBatch {
     pub fn new() -> Self {
-        OverlayWriteBatch {
-            wb: WriteBatch::new(),
-            cache: HashMap::new(),
-        }
-    }
-
-    pub fn with_capacity(cap: usize) -> Self {
-        OverlayWriteBatch {
-            wb: WriteBatch::with_reserved_bytes(cap),
-            cache: HashMap::new(),
-        }
+        OverlayWriteBatch { cache: HashMap::new() }
     }
 
-    pub fn put(&mut self, col: &ColumnFamilyHandle, key: &[u8], value: &[u8]) {
-        self.wb.put_cf(col, key, value);
+    pub fn put(&mut self, col: usize, key: &[u8], value: &[u8]) {
         self.cache
-            .entry(col.id())
+            .entry(col)
             .or_default()
             .insert(key.to_owned(), Some(value.to_owned()));
     }
 
-    pub fn delete(&mut self, col: &ColumnFamilyHandle, key: &[u8]) {
-        self.wb.delete_cf(col, key);
-        self.cache.entry(col.id()).or_default().insert(key.to_owned(), None);
+    pub fn delete(&mut self, col: usize, key: &[u8]) {
+        self.cache.entry(col).or_default().insert(key.to_owned(), None);
     }
 
     // Ok(None) => deleted
     // Err(_)   => non-exist
-    pub fn get(&self, col: &ColumnFamilyHandle, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    pub fn get(&self, col: usize, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
         self.cache
-            .get(&col.id())
+            .get(&col)
             .and_then(|cf| cf.get(key).cloned())
             .ok_or(io::Error::new(io::ErrorKind::NotFound, ""))
     }
 
     // None => deleted or not-found
-    pub fn get_by_prefix(&self, col: &ColumnFamilyHandle, prefix: &[u8]) -> Option<Box<[u8]>> {
-        self.cache.get(&col.id()).and_then(|cf| {
+    pub fn get_by_prefix(&self, col: usize, prefix: &[u8]) -> Option<Box<[u8]>> {
+        self.cache.get(&col).and_then(|cf| {
             cf.iter()
                 .filter(|(key, value)| key.starts_with(prefix) && value.is_some())
                 .map(|(_, value)| value.clone().unwrap().into_boxed_slice())
@@ -104,9 +66,9 @@ impl OverlayWriteBatch {
         })
     }
 
-    pub fn iter<'a>(&'a self, col: &ColumnFamilyHandle) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+    pub fn iter<'a>(&'a self, col: usize) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
         self.cache
-            .get(&col.id())
+            .get(&col)
             .map(|cf| {
                 Box::new(cf.iter().filter(|(_, value)| value.is_some()).map(|(key, value)| {
                     (
@@ -122,11 +84,11 @@ impl OverlayWriteBatch {
     /// where the key starts with the given prefix.
     pub fn iter_with_prefix<'a>(
         &'a self,
-        col: &ColumnFamilyHandle,
+        col: usize,
         prefix: &'a [u8],
     ) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
         self.cache
-            .get(&col.id())
+            .get(&col)
             .map(|cf| {
                 Box::new(
                     cf.iter()
@@ -141,17 +103,56 @@ impl OverlayWriteBatch {
             })
             .unwrap_or_else(|| Box::new(iter::empty()) as Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)>>)
     }
+
+    /// Whether the key is put or deleted.
+    pub fn contains_key(&self, col: usize, key: &[u8]) -> bool {
+        self.cache.get(&col).map(|cf| cf.contains_key(key)).unwrap_or(false)
+    }
+
+    /// Whether any key starting with the prefix is put or deleted.
+    pub fn contains_prefix(&self, col: usize, prefix: &[u8]) -> bool {
+        self.cache
+            .get(&col)
+            .and_then(|cf| cf.range::<[u8], _>(prefix..).next())
+            .map(|(key, _)| key.starts_with(prefix))
+            .unwrap_or(false)
+    }
+
+    /// Merge changes of a newer batch.
+    pub fn merge(&mut self, other: &OverlayWriteBatch) {
+        for (&col, cf) in &other.cache {
+            let this = self.cache.entry(col).or_default();
+            for (key, value) in cf {
+                this.insert(key.clone(), value.clone());
+            }
+        }
+    }
+
+    /// Convert to a WriteBatch of the storage engine.
+    pub fn to_write_batch(&self) -> WriteBatch {
+        let mut wb = WriteBatch::with_capacity(self.cache.values().map(|cf| cf.len()).sum());
+        for (&col, cf) in &self.cache {
+            for (key, value) in cf {
+                match value {
+                    Some(val) => wb.put(col, key, val),
+                    None => wb.delete(col, key),
+                }
+            }
+        }
+        wb
+    }
 }
 
 pub struct OverlayDB {
-    inner: DB,
+    inner: Box<dyn KeyValueDB>,
     // Use push_back to add to the queue, and pop_front to remove from the queue.
     // push_back to add a new layer, pop_front to sync a layer to db, clear to discard all layers.
-    layers: VecDeque<OverlayWriteBatch>,
+    // Layers are shared with snapshots, and copied only when written while shared, which is the top layer.
+    layers: VecDeque<Arc<OverlayWriteBatch>>,
 }
 
 impl OverlayDB {
-    pub fn new(inner: DB) -> Self {
+    pub fn new(inner: Box<dyn KeyValueDB>) -> Self {
         OverlayDB {
             inner,
             // ceiling(27 - 27 * 70%) = 9
@@ -159,19 +160,35 @@ impl OverlayDB {
         }
     }
 
+    /// A snapshot of the db, with layers frozen. Layers are shared, not copied.
+    pub fn snapshot(&self) -> Self {
+        OverlayDB {
+            inner: self.inner.snapshot(),
+            layers: self.layers.clone(),
+        }
+    }
+
     /// Fake `write` an OverlayWriteBath.
     pub fn write(&mut self, wb: OverlayWriteBatch) -> io::Result<()> {
-        self.layers.push_back(wb);
+        self.layers.push_back(Arc::new(wb));
         Ok(())
     }
 
     pub fn push_layer(&mut self, wb: OverlayWriteBatch) {
-        self.layers.push_back(wb);
+        self.layers.push_back(Arc::new(wb));
+    }
+
+    /// The last layer, for writing. Copied if shared with snapshots.
+    fn last_layer_mut(&mut self) -> io::Result<&mut OverlayWriteBatch> {
+        self.layers
+            .back_mut()
+            .map(Arc::make_mut)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "no db layers found"))
     }
 
     pub fn solidify_layers(&mut self) -> Result<(), BoxError> {
         for layer in self.layers.drain(..) {
-            self.inner.write(WriteOptions::default_instance(), &layer.wb)?;
+            self.inner.write(&layer.to_write_batch())?;
         }
         Ok(())
     }
@@ -182,41 +199,26 @@ impl OverlayDB {
     }
 
     /// Get a value by key.
-    pub fn get(&self, col: &ColumnFamilyHandle, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
-        for layer in self.layers.iter().rev() {
-            if let Ok(val) = layer.get(col, key) {
-                return Ok(val);
-            }
-        }
-        match self.inner.get_cf(ReadOptions::default_instance(), col, key) {
-            Ok(val) => Ok(Some(val.to_vec())),
-            Err(e) if e.is_not_found() => Ok(None),
-            // Err(e) if e.is_not_found() => Err(io::Error::new(io::ErrorKind::NotFound, "")),
-            Err(e) => Err(io::Error::new(io::ErrorKind::Other, e.to_string())),
-        }
+    pub fn get(&self, col: usize, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        self.get_skipped(0, col, key)
     }
 
     /// Get a value by key, skip top n layers.
-    pub fn get_skipped(&self, n: usize, col: &ColumnFamilyHandle, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    pub fn get_skipped(&self, n: usize, col: usize, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
         for layer in self.layers.iter().rev().skip(n) {
             if let Ok(val) = layer.get(col, key) {
                 return Ok(val);
             }
         }
-        match self.inner.get_cf(ReadOptions::default_instance(), col, key) {
-            Ok(val) => Ok(Some(val.to_vec())),
-            Err(e) if e.is_not_found() => Ok(None),
-            // Err(e) if e.is_not_found() => Err(io::Error::new(io::ErrorKind::NotFound, "")),
-            Err(e) => Err(io::Error::new(io::ErrorKind::Other, e.to_string())),
-        }
+        self.inner.get(col, key)
     }
 
     /// Get the first value matching the given prefix.
-    pub fn get_by_prefix(&self, col: &ColumnFamilyHandle, prefix: &[u8]) -> Option<Box<[u8]>> {
+    pub fn get_by_prefix(&self, col: usize, prefix: &[u8]) -> Option<Box<[u8]>> {
         let mut deleted = HashSet::<&[u8]>::new();
 
         for layer in self.layers.iter().rev() {
-            if let Some(cache) = layer.cache.get(&col.id()) {
+            if let Some(cache) = layer.cache.get(&col) {
                 for (key, value) in cache.iter().filter(|(key, _)| key.starts_with(prefix)) {
                     if deleted.contains(&**key) {
                         continue;
@@ -233,29 +235,28 @@ impl OverlayDB {
             }
         }
 
-        for (key, value) in self
-            .inner
-            .new_iterator_cf(&ReadOptions::default().iterate_lower_bound(prefix), col)
-        {
+        let mut found = None;
+        self.inner.for_each_from(col, prefix, &mut |key, value| {
             if !key.starts_with(prefix) {
-                return None;
+                return false;
             }
             if deleted.contains(key) {
-                continue;
+                return true;
             }
-            return Some(value.to_vec().into_boxed_slice());
-        }
-        None
+            found = Some(value.to_vec().into_boxed_slice());
+            false
+        });
+        found
     }
 
-    pub fn for_each<F>(&self, col: &ColumnFamilyHandle, mut func: F)
+    pub fn for_each<F>(&self, col: usize, mut func: F)
     where
         F: FnMut(&[u8], &[u8]) -> (),
     {
         let mut visited: HashSet<&[u8]> = HashSet::new();
 
         for layer in self.layers.iter().rev() {
-            if let Some(cache) = layer.cache.get(&col.id()) {
+            if let Some(cache) = layer.cache.get(&col) {
                 for (key, value) in cache.iter() {
                     if visited.contains(&**key) {
                         continue;
@@ -271,24 +272,24 @@ impl OverlayDB {
             }
         }
 
-        for (key, value) in self.inner.new_iterator_cf(&ReadOptions::default(), col) {
-            if visited.contains(key) {
-                continue;
+        self.inner.for_each_from(col, &[], &mut |key, value| {
+            if !visited.contains(key) {
+                func(key, value);
             }
-            func(key, value);
-        }
+            true
+        });
     }
 
     /// Iterate over the data for a given column, returning all key/value pairs
     /// where the key starts with the given prefix.
-    pub fn for_each_by_prefix<F>(&self, col: &ColumnFamilyHandle, prefix: &[u8], mut func: F)
+    pub fn for_each_by_prefix<F>(&self, col: usize, prefix: &[u8], mut func: F)
     where
         F: FnMut(&[u8], &[u8]) -> (),
     {
         let mut visited = HashSet::<&[u8]>::new();
 
         for layer in self.layers.iter().rev() {
-            if let Some(cache) = layer.cache.get(&col.id()) {
+            if let Some(cache) = layer.cache.get(&col) {
                 for (key, value) in cache.iter().filter(|(key, _)| key.starts_with(prefix)) {
                     if !key.starts_with(prefix) {
                         continue;
@@ -307,35 +308,28 @@ impl OverlayDB {
             }
         }
 
-        for (key, value) in self
-            .inner
-            .new_iterator_cf(&ReadOptions::default().iterate_lower_bound(prefix), col)
-        {
+        self.inner.for_each_from(col, prefix, &mut |key, value| {
             if !key.starts_with(prefix) {
-                return;
+                return false;
             }
-            if visited.contains(key) {
-                continue;
+            if !visited.contains(key) {
+                func(key, value);
             }
-            func(key, value);
-        }
+            true
+        });
     }
 
-    pub fn delete(&mut self, col: &ColumnFamilyHandle, key: &[u8]) -> io::Result<()> {
-        let wb = self
-            .layers
-            .back_mut()
-            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "no db layers found"))?;
-        wb.delete(col, key);
+    pub fn delete(&mut self, col: usize, key: &[u8]) -> io::Result<()> {
+        self.last_layer_mut()?.delete(col, key);
         Ok(())
     }
 
-    pub fn delete_by_prefix(&mut self, col: &ColumnFamilyHandle, prefix: &[u8]) -> io::Result<()> {
+    pub fn delete_by_prefix(&mut self, col: usize, prefix: &[u8]) -> io::Result<()> {
         let mut visited = HashSet::<&[u8]>::new();
         let mut deleted = HashSet::<Vec<u8>>::new();
 
         for layer in self.layers.iter().rev() {
-            if let Some(cache) = layer.cache.get(&col.id()) {
+            if let Some(cache) = layer.cache.get(&col) {
                 for (key, value) in cache.iter().filter(|(key, _)| key.starts_with(prefix)) {
                     if !key.starts_with(prefix) {
                         continue;
@@ -354,19 +348,15 @@ impl OverlayDB {
             }
         }
 
-        for key in self
-            .inner
-            .new_iterator_cf(&ReadOptions::default().iterate_lower_bound(prefix), col)
-            .keys()
-        {
+        self.inner.for_each_from(col, prefix, &mut |key, _| {
             if !key.starts_with(prefix) {
-                return Ok(());
+                return false;
             }
-            if visited.contains(key) {
-                continue;
+            if !visited.contains(key) {
+                deleted.insert(key.to_vec());
             }
-            deleted.insert(key.to_vec());
-        }
+            true
+        });
         for key in &deleted {
             self.delete(col, key)?;
         }
@@ -393,15 +383,48 @@ pub const COL_TRANSACTION_LOG: usize = 13;
 pub const COL_ACCOUNT_INDEX: usize = 14;
 pub const COL_VOTER_REWARD: usize = 15;
 pub const COL_EXCHANGE: usize = 16;
+/// Spent shielded notes.
+pub const COL_SHIELDED_NULLIFIER: usize = 17;
+/// Valid roots of the note commitment tree.
+pub const COL_SHIELDED_ANCHOR: usize = 18;
+pub const COL_RESOURCE_DELEGATION_REVERSE_INDEX: usize = 19;
+
+pub const NUM_OF_COLUMNS: usize = COL_RESOURCE_DELEGATION_REVERSE_INDEX + 1;
+
+/// Keys read from a StateDB, for conflict detection of parallel transaction execution.
+#[derive(Debug, Default)]
+pub struct ReadSet {
+    keys: HashSet<(usize, Vec<u8>)>,
+    // By iteration.
+    prefixes: HashSet<(usize, Vec<u8>)>,
+}
+
+impl ReadSet {
+    /// Whether any key read is changed by the batch.
+    pub fn conflicts_with(&self, wb: &OverlayWriteBatch) -> bool {
+        self.keys.iter().any(|(col, key)| wb.contains_key(*col, key)) ||
+            self.prefixes
+                .iter()
+                .any(|(col, prefix)| wb.contains_prefix(*col, prefix))
+    }
+}
 
 /// The State DB derived from Chain DB.
 pub struct StateDB {
     db: OverlayDB,
-    cols: Vec<ColumnFamily>,
+    is_snapshot: bool,
+    /// Shared with snapshots, so that reads of a derived state are recorded too.
+    reads: Option<Arc<Mutex<ReadSet>>>,
+    // Number of keys read and written, for profiling.
+    num_of_reads: AtomicU64,
+    num_of_writes: u64,
 }
 
 impl Drop for StateDB {
     fn drop(&mut self) {
+        if self.is_snapshot {
+            return;
+        }
         info!("state-db closed successfully, all cached layers will be droped");
     }
 }
@@ -428,7 +451,7 @@ fn col_descs_for_state_db() -> Vec<ColumnFamilyDescriptor> {
             "resource-delegation",
             ColumnFamilyOptions::default().optimize_for_point_lookup(128),
         ),
-        // to_address => [from_address]
+        // from_address => [to_address]
         ColumnFamilyDescriptor::new(
             "resource-delegation-index",
             ColumnFamilyOptions::default().optimize_for_point_lookup(128),
@@ -508,40 +531,89 @@ fn col_descs_for_state_db() -> Vec<ColumnFamilyDescriptor> {
                 .optimize_for_small_db()
                 .optimize_for_point_lookup(16),
         ),
+        // <<nullifier: H256>> => txid: H256
+        ColumnFamilyDescriptor::new(
+            "shielded-nullifier",
+            ColumnFamilyOptions::default().optimize_for_point_lookup(16),
+        ),
+        // <<root: H256>> => tree_size: i64
+        ColumnFamilyDescriptor::new(
+            "shielded-anchor",
+            ColumnFamilyOptions::default().optimize_for_point_lookup(16),
+        ),
+        // to_address => [from_address]
+        ColumnFamilyDescriptor::new(
+            "resource-delegation-reverse-index",
+            ColumnFamilyOptions::default().optimize_for_point_lookup(128),
+        ),
     ]
 }
 
 impl StateDB {
+    /// Open the StateDB of RocksDB engine.
     pub fn new<P: AsRef<Path>>(db_path: P) -> StateDB {
-        let db_options = DBOptions::default()
-            .create_if_missing(true)
-            .create_missing_column_families(true)
-            .increase_parallelism(num_cpus::get() as _)
-            .allow_mmap_reads(true) // for Cuckoo table
-            .max_open_files(1024);
+        let db = RocksDB::open(db_path, col_descs_for_state_db()).unwrap();
+        StateDB::with_backend(Box::new(db))
+    }
 
-        let column_families = col_descs_for_state_db();
+    /// Open the StateDB of a running node as a RocksDB secondary, which is read-only.
+    ///
+    /// `secondary_path` keeps the info logs of the secondary, and must not be shared with other instances.
+    pub fn new_secondary<P1: AsRef<Path>, P2: AsRef<Path>>(
+        db_path: P1,
+        secondary_path: P2,
+    ) -> Result<StateDB, BoxError> {
+        let db = RocksDB::open_as_secondary(db_path, secondary_path, col_descs_for_state_db())?;
+        Ok(StateDB::with_backend(Box::new(db)))
+    }
 
-        let (db, cols) = DB::open_with_column_families(&db_options, db_path, column_families).unwrap();
+    /// An empty StateDB in memory.
+    pub fn new_in_memory() -> StateDB {
+        StateDB::with_backend(Box::new(MemoryDB::new(NUM_OF_COLUMNS)))
+    }
 
+    pub fn with_backend(db: Box<dyn KeyValueDB>) -> StateDB {
         StateDB {
             db: OverlayDB::new(db),
-            cols,
+            is_snapshot: false,
+            reads: None,
+            num_of_reads: AtomicU64::new(0),
+            num_of_writes: 0,
+        }
+    }
+
+    /// Catch up with the primary, for a StateDB opened by `new_secondary`.
+    pub fn catch_up_with_primary(&self) -> Result<(), BoxError> {
+        self.db.inner.try_catch_up_with_primary()?;
+        Ok(())
+    }
+
+    /// A consistent read-only view of the current state, including layers not solidified yet.
+    ///
+    /// New layers can be pushed to the snapshot as scratch space, but can never be solidified. Reads of the snapshot
+    /// are recorded to the read set of this db, if tracked.
+    pub fn snapshot(&self) -> StateDB {
+        StateDB {
+            db: self.db.snapshot(),
+            is_snapshot: true,
+            reads: self.reads.clone(),
+            num_of_reads: AtomicU64::new(0),
+            num_of_writes: 0,
         }
     }
 }
 
 impl StateDB {
     pub fn new_layer(&mut self) -> &mut OverlayWriteBatch {
-        self.db.push_layer(OverlayWriteBatch::with_capacity(4 * 1024));
-        self.db.layers.back_mut().unwrap()
+        self.db.push_layer(OverlayWriteBatch::new());
+        self.db.last_layer_mut().unwrap()
     }
 
     pub fn solidify_layer(&mut self) {
         self.db
             .layers
             .pop_front()
-            .map(|wb| self.db.inner.write(WriteOptions::default_instance(), &wb));
+            .map(|wb| self.db.inner.write(&wb.to_write_batch()));
     }
 
     pub fn discard_last_layer(&mut self) -> io::Result<()> {
@@ -552,51 +624,108 @@ impl StateDB {
         Ok(())
     }
 
+    /// Changes of the top n layers, merged into one.
+    pub fn merged_top_layers(&self, n: usize) -> OverlayWriteBatch {
+        let mut merged = OverlayWriteBatch::new();
+        for layer in self.db.layers.iter().skip(self.db.layers.len().saturating_sub(n)) {
+            merged.merge(layer);
+        }
+        merged
+    }
+
+    /// Write changes to the last layer.
+    pub fn write_to_last_layer(&mut self, wb: &OverlayWriteBatch) -> Result<(), BoxError> {
+        self.db.last_layer_mut()?.merge(wb);
+        Ok(())
+    }
+
+    /// Start recording keys read, until `take_reads`.
+    pub fn track_reads(&mut self) {
+        self.reads = Some(Arc::new(Mutex::new(ReadSet::default())));
+    }
+
+    /// Keys read since `track_reads`, including those read by snapshots of this db.
+    pub fn take_reads(&mut self) -> ReadSet {
+        self.reads
+            .take()
+            .map(|reads| mem::take(&mut *reads.lock().unwrap()))
+            .unwrap_or_default()
+    }
+
+    /// Number of reads, including iterations, since opened.
+    pub fn num_of_reads(&self) -> u64 {
+        self.num_of_reads.load(Ordering::Relaxed)
+    }
+
+    /// Number of writes, including deletions, since opened.
+    pub fn num_of_writes(&self) -> u64 {
+        self.num_of_writes
+    }
+
+    fn record_read<T, K: keys::Key<T>>(&self, key: &K) {
+        self.num_of_reads.fetch_add(1, Ordering::Relaxed);
+        if let Some(ref reads) = self.reads {
+            reads.lock().unwrap().keys.insert((K::COL, key.key().as_ref().to_vec()));
+        }
+    }
+
+    fn record_prefix_read(&self, col: usize, prefix: &[u8]) {
+        self.num_of_reads.fetch_add(1, Ordering::Relaxed);
+        if let Some(ref reads) = self.reads {
+            reads.lock().unwrap().prefixes.insert((col, prefix.to_vec()));
+        }
+    }
+
     pub fn put_key<T, K: keys::Key<T>>(&mut self, key: K, value: T) -> Result<(), BoxError> {
-        let wb = self
-            .db
-            .layers
-            .back_mut()
-            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "no db layers found"))?;
-        wb.put(&self.cols[K::COL], key.key().as_ref(), &*K::value(&value));
+        let wb = self.db.last_layer_mut()?;
+        wb.put(K::COL, key.key().as_ref(), &*K::value(&value));
+        self.num_of_writes += 1;
         Ok(())
     }
 
     pub fn delete_key<T, K: keys::Key<T>>(&mut self, key: &K) -> Result<(), BoxError> {
-        self.db.delete(&self.cols[K::COL], key.key().as_ref())?;
+        self.db.delete(K::COL, key.key().as_ref())?;
+        self.num_of_writes += 1;
         Ok(())
     }
 
-    pub fn delete_by_prefix(&mut self, col: &ColumnFamilyHandle, prefix: &[u8]) -> Result<(), BoxError> {
-        self.db.delete_by_prefix(col, prefix)?;
+    /// Delete all keys of a column starting with the given prefix.
+    pub fn delete_by_prefix<T, K: keys::Key<T>>(&mut self, prefix: &[u8]) -> Result<(), BoxError> {
+        self.record_prefix_read(K::COL, prefix);
+        self.db.delete_by_prefix(K::COL, prefix)?;
+        self.num_of_writes += 1;
         Ok(())
     }
 
     pub fn get<T, K: keys::Key<T>>(&self, key: &K) -> Result<Option<T>, BoxError> {
+        self.record_read(key);
         self.db
-            .get(&self.cols[K::COL], key.key().as_ref())
+            .get(K::COL, key.key().as_ref())
             .map(|maybe_raw| maybe_raw.map(|raw| K::parse_value(&raw)))
             .map_err(|e| e.into())
     }
 
     pub fn get_skipped<T, K: keys::Key<T>>(&self, n: usize, key: &K) -> Result<Option<T>, BoxError> {
+        self.record_read(key);
         self.db
-            .get_skipped(n, &self.cols[K::COL], key.key().as_ref())
+            .get_skipped(n, K::COL, key.key().as_ref())
             .map(|maybe_raw| maybe_raw.map(|raw| K::parse_value(&raw)))
             .map_err(|e| e.into())
     }
 
     pub fn must_get_skipped<T, K: keys::Key<T>>(&self, n: usize, key: &K) -> T {
+        self.record_read(key);
         self.db
-            .get_skipped(n, &self.cols[K::COL], key.key().as_ref())
+            .get_skipped(n, K::COL, key.key().as_ref())
             .map(|maybe_raw| maybe_raw.map(|raw| K::parse_value(&raw)))
             .expect("corrupted db")
             .expect("key must exist")
     }
 
     pub fn must_get<T, K: keys::Key<T>>(&self, key: &K) -> T {
+        self.record_read(key);
         self.db
-            .get(&self.cols[K::COL], key.key().as_ref())
+            .get(K::COL, key.key().as_ref())
             .map(|maybe_raw| maybe_raw.map(|raw| K::parse_value(&raw)))
             .expect("corrupted db")
             .expect("key must exist")
@@ -613,7 +742,8 @@ impl StateDB {
     where
         F: FnMut(&K, &T) -> (),
     {
-        self.db.for_each(&self.cols[K::COL], move |key, value| {
+        self.record_prefix_read(K::COL, &[]);
+        self.db.for_each(K::COL, move |key, value| {
             if let Some(key) = K::parse_key(key) {
                 func(&key, &K::parse_value(value));
             }
@@ -624,23 +754,25 @@ impl StateDB {
     where
         F: FnMut(&K, &T) -> (),
     {
-        self.db
-            .for_each_by_prefix(&self.cols[K::COL], prefix, move |key, value| {
-                if let Some(key) = K::parse_key(key) {
-                    func(&key, &K::parse_value(value));
-                }
-            });
+        self.record_prefix_read(K::COL, prefix);
+        self.db.for_each_by_prefix(K::COL, prefix, move |key, value| {
+            if let Some(key) = K::parse_key(key) {
+                func(&key, &K::parse_value(value));
+            }
+        });
     }
 
     pub fn init_genesis(&mut self, genesis: &GenesisConfig, chain: &ChainConfig) -> Result<(), BoxError> {
         if let Some(db_ver) = self.get(&keys::DynamicProperty::DbVersion)? {
-            // TODO: check migration here
             let latest_block_hash = self.must_get(&keys::LatestBlockHash);
             let latest_block_numer = self.must_get(&DynamicProperty::LatestBlockNumber);
             info!(
                 "state-db is already inited, db version: {}, block number: {}, block hash: {:?}",
                 db_ver, latest_block_numer, latest_block_hash
             );
+            if db_ver < CURRENT_DB_VERSION {
+                self.migrate(db_ver)?;
+            }
 
             return Ok(());
         }
@@ -663,6 +795,33 @@ impl StateDB {
         Ok(())
     }
 
+    /// Migrate a state-db of an older version to `CURRENT_DB_VERSION`.
+    fn migrate(&mut self, db_ver: i64) -> Result<(), BoxError> {
+        self.new_layer();
+
+        if db_ver < 2 {
+            // Backfill the reverse index of resource delegation from the forward index.
+            let mut reverse_index: HashMap<Address, Vec<Address>> = HashMap::new();
+            self.for_each(|key: &keys::ResourceDelegationIndex, to_addrs: &Vec<Address>| {
+                for to in to_addrs {
+                    reverse_index.entry(*to).or_default().push(key.0);
+                }
+            });
+            info!(
+                "backfill resource delegation reverse index of {} accounts",
+                reverse_index.len()
+            );
+            for (to, from_addrs) in reverse_index {
+                self.put_key(keys::ResourceDelegationReverseIndex(to), from_addrs)?;
+            }
+        }
+
+        self.put_key(DynamicProperty::DbVersion, CURRENT_DB_VERSION)?;
+        self.db.solidify_layers()?;
+        info!("state-db is migrated from version {} to {}", db_ver, CURRENT_DB_VERSION);
+        Ok(())
+    }
+
     fn apply_genesis_config(&mut self, genesis: &GenesisConfig) -> Result<(), BoxError> {
         let mut witnesses: Vec<(Address, i64)> = vec![];
         for witness in &genesis.witnesses {
@@ -722,42 +881,3 @@ impl StateDB {
         Ok(())
     }
 }
-
-pub struct ReadOnlySolidStateDB {
-    db: DB,
-    cols: Vec<ColumnFamily>,
-}
-
-unsafe impl Send for ReadOnlySolidStateDB {}
-unsafe impl Sync for ReadOnlySolidStateDB {}
-
-impl ReadOnlySolidStateDB {
-    pub fn new<P1: AsRef<Path>, P2: AsRef<Path>>(db_path: P1, tmp_path: P2) -> StateDB {
-        let db_options = DBOptions::default()
-            .increase_parallelism(num_cpus::get() as _)
-            .allow_mmap_reads(true) // for Cuckoo table
-            .max_open_files(1024);
-
-        let column_families = col_descs_for_state_db();
-
-        let (db, cols) =
-            DB::open_as_secondary_with_column_families(&db_options, db_path, tmp_path, column_families).unwrap();
-
-        StateDB {
-            db: OverlayDB::new(db),
-            cols,
-        }
-    }
-
-    pub fn get<T, K: keys::Key<T>>(&self, key: &K) -> Result<Option<T>, BoxError> {
-        self.db
-            .get_cf(ReadOptions::default_instance(), &self.cols[K::COL], key.key().as_ref())
-            .map(|raw| Some(K::parse_value(&raw)))
-            .or_else(|e| if e.is_not_found() { Ok(None) } else { Err(e) })
-            .map_err(|e| e.into())
-    }
-
-    pub fn catch_up_with_primary(&self) {
-        let _ = self.db.try_catch_up_with_primary();
-    }
-}
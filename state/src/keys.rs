@@ -284,9 +284,16 @@ impl Key<pb::ResourceDelegation> for ResourceDelegation {
     fn parse_value(raw: &[u8]) -> pb::ResourceDelegation {
         pb::ResourceDelegation::decode(raw).unwrap()
     }
+
+    fn parse_key(raw: &[u8]) -> Option<Self> {
+        Some(ResourceDelegation(
+            *Address::from_bytes(&raw[..21]),
+            *Address::from_bytes(&raw[21..]),
+        ))
+    }
 }
 
-/// Reverse index for resource delegation info, to_address.
+/// Index for resource delegation info, from_address => [to_address].
 #[derive(Debug)]
 pub struct ResourceDelegationIndex(pub Address);
 
@@ -315,6 +322,31 @@ impl Key<Vec<Address>> for ResourceDelegationIndex {
             .collect::<Result<Vec<_>, _>>()
             .unwrap()
     }
+
+    fn parse_key(raw: &[u8]) -> Option<Self> {
+        Some(ResourceDelegationIndex(*Address::from_bytes(raw)))
+    }
+}
+
+/// Reverse index for resource delegation info, to_address => [from_address].
+#[derive(Debug)]
+pub struct ResourceDelegationReverseIndex(pub Address);
+
+impl Key<Vec<Address>> for ResourceDelegationReverseIndex {
+    type Target = Vec<u8>;
+    const COL: usize = super::db::COL_RESOURCE_DELEGATION_REVERSE_INDEX;
+
+    fn key(&self) -> Self::Target {
+        self.0.as_bytes().to_vec()
+    }
+
+    fn value(val: &Vec<Address>) -> Cow<[u8]> {
+        ResourceDelegationIndex::value(val)
+    }
+
+    fn parse_value(raw: &[u8]) -> Vec<Address> {
+        ResourceDelegationIndex::parse_value(raw)
+    }
 }
 
 /// `<<Address>> => Votes { epoch: i64, votes: [Votes] }`
@@ -561,3 +593,88 @@ impl Key<pb::TransactionLog> for TransactionLog {
         pb::TransactionLog::decode(raw).unwrap()
     }
 }
+
+/// Serialized Sapling note commitment tree of shielded TRC10 transactions.
+#[derive(Debug)]
+pub struct ShieldedCommitmentTree;
+
+impl Key<Vec<u8>> for ShieldedCommitmentTree {
+    type Target = &'static str;
+    const COL: usize = super::db::COL_DEFAULT;
+
+    // Same as DynamicProperty
+    fn key(&self) -> Self::Target {
+        "kShieldedCommitmentTree"
+    }
+
+    fn value(val: &Vec<u8>) -> Cow<[u8]> {
+        (&val[..]).into()
+    }
+
+    fn parse_value(raw: &[u8]) -> Vec<u8> {
+        raw.to_vec()
+    }
+}
+
+/// Serialized incremental note commitment tree of a followed shielded TRC20 contract, with witnesses.
+#[derive(Debug)]
+pub struct ShieldedNoteTree(pub Address);
+
+impl Key<Vec<u8>> for ShieldedNoteTree {
+    type Target = Vec<u8>;
+    const COL: usize = super::db::COL_DEFAULT;
+
+    fn key(&self) -> Self::Target {
+        [&b"kShieldedNoteTree"[..], self.0.as_bytes()].concat()
+    }
+
+    fn value(val: &Vec<u8>) -> Cow<[u8]> {
+        (&val[..]).into()
+    }
+
+    fn parse_value(raw: &[u8]) -> Vec<u8> {
+        raw.to_vec()
+    }
+}
+
+/// `<<nullifier: H256>> => txid: H256`
+#[derive(Debug)]
+pub struct ShieldedNullifier(pub H256);
+
+impl Key<H256> for ShieldedNullifier {
+    type Target = Vec<u8>;
+    const COL: usize = super::db::COL_SHIELDED_NULLIFIER;
+
+    fn key(&self) -> Self::Target {
+        self.0.as_bytes().to_vec()
+    }
+
+    fn value(val: &H256) -> Cow<[u8]> {
+        Cow::Borrowed(val.as_bytes())
+    }
+
+    fn parse_value(raw: &[u8]) -> H256 {
+        H256::from_slice(raw)
+    }
+}
+
+/// `<<root: H256>> => tree_size: i64`
+#[derive(Debug)]
+pub struct ShieldedAnchor(pub H256);
+
+impl Key<i64> for ShieldedAnchor {
+    type Target = Vec<u8>;
+    const COL: usize = super::db::COL_SHIELDED_ANCHOR;
+
+    fn key(&self) -> Self::Target {
+        self.0.as_bytes().to_vec()
+    }
+
+    fn value(val: &i64) -> Cow<[u8]> {
+        Cow::Owned(val.to_be_bytes().to_vec())
+    }
+
+    fn parse_value(raw: &[u8]) -> i64 {
+        BE::read_u64(raw) as _
+    }
+}
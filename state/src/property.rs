@@ -3,7 +3,9 @@ use std::collections::HashMap;
 use super::ChainParameter;
 
 /// Used for DB migrations. Corresponding key is `DynamicProperty::DbVersion`.
-const CURRENT_DB_VERSION: i64 = 1;
+///
+/// - 2: reverse index of resource delegation
+pub(crate) const CURRENT_DB_VERSION: i64 = 2;
 
 /// Dynamic properties of a living chain.
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -0,0 +1,105 @@
+//! Key-value storage engines of ChainDB and StateDB.
+//!
+//! Data is organized in columns, addressed by index. Keys are iterated in bytewise order.
+
+use std::io;
+
+pub use self::memory::MemoryDB;
+pub use self::rocksdb::RocksDB;
+
+mod memory;
+mod rocksdb;
+
+/// A batch of changes, written atomically.
+#[derive(Debug, Default, Clone)]
+pub struct WriteBatch {
+    // (column, key, value), None for deletion
+    ops: Vec<(usize, Vec<u8>, Option<Vec<u8>>)>,
+}
+
+impl WriteBatch {
+    pub fn new() -> Self {
+        WriteBatch::default()
+    }
+
+    pub fn with_capacity(cap: usize) -> Self {
+        WriteBatch {
+            ops: Vec::with_capacity(cap),
+        }
+    }
+
+    pub fn put(&mut self, col: usize, key: &[u8], value: &[u8]) {
+        self.ops.push((col, key.to_vec(), Some(value.to_vec())));
+    }
+
+    /// Put with key and value concatenated from slices.
+    pub fn putv(&mut self, col: usize, key: &[&[u8]], value: &[&[u8]]) {
+        self.ops.push((col, key.concat(), Some(value.concat())));
+    }
+
+    pub fn delete(&mut self, col: usize, key: &[u8]) {
+        self.ops.push((col, key.to_vec(), None));
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Changes in written order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &[u8], Option<&[u8]>)> {
+        self.ops
+            .iter()
+            .map(|(col, key, value)| (*col, &key[..], value.as_ref().map(|val| &val[..])))
+    }
+}
+
+fn read_only_error() -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, "snapshot is read-only")
+}
+
+pub trait KeyValueDB: Send + Sync {
+    /// Get a value by key.
+    fn get(&self, col: usize, key: &[u8]) -> io::Result<Option<Vec<u8>>>;
+
+    /// Visit key/value pairs of a column in key order, starting from `lower_bound`, until `func` returns false.
+    ///
+    /// `func` is allowed to read from or write to the db.
+    fn for_each_from(&self, col: usize, lower_bound: &[u8], func: &mut dyn FnMut(&[u8], &[u8]) -> bool);
+
+    /// Write a batch of changes to the backing store.
+    fn write(&self, batch: &WriteBatch) -> io::Result<()>;
+
+    /// A consistent view of the db at this moment, unaffected by later writes. Snapshots are read-only.
+    fn snapshot(&self) -> Box<dyn KeyValueDB>;
+
+    /// Integer property of the engine, like `rocksdb.num-running-flushes`. 0 if not supported.
+    fn int_property(&self, _name: &str) -> u64 {
+        0
+    }
+
+    /// Integer property summed over all columns. 0 if not supported.
+    fn accumulated_int_property(&self, _name: &str) -> u64 {
+        0
+    }
+
+    /// Catch up with the primary instance, if opened as a secondary.
+    fn try_catch_up_with_primary(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Compact all columns.
+    fn compact(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Flush memory buffers and stop background jobs, before exit.
+    ///
+    /// # Safety
+    ///
+    /// The db must not be used afterwards.
+    unsafe fn prepare_close(&self) {}
+}
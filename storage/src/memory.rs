@@ -0,0 +1,152 @@
+//! The in-memory storage engine.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::ops::Bound;
+use std::sync::{Arc, RwLock};
+
+use super::{read_only_error, KeyValueDB, WriteBatch};
+
+type Column = BTreeMap<Vec<u8>, Vec<u8>>;
+
+/// Storage engine backed by BTreeMaps. Nothing is persisted.
+pub struct MemoryDB {
+    // Columns are copied on write while shared with snapshots.
+    cols: RwLock<Vec<Arc<Column>>>,
+}
+
+impl MemoryDB {
+    pub fn new(num_columns: usize) -> Self {
+        MemoryDB {
+            cols: RwLock::new(vec![Arc::new(Column::new()); num_columns]),
+        }
+    }
+
+    fn first_entry(&self, col: usize, lower_bound: Bound<&[u8]>) -> Option<(Vec<u8>, Vec<u8>)> {
+        self.cols.read().unwrap()[col]
+            .range::<[u8], _>((lower_bound, Bound::Unbounded))
+            .next()
+            .map(|(key, value)| (key.clone(), value.clone()))
+    }
+}
+
+impl KeyValueDB for MemoryDB {
+    fn get(&self, col: usize, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.cols.read().unwrap()[col].get(key).cloned())
+    }
+
+    fn for_each_from(&self, col: usize, lower_bound: &[u8], func: &mut dyn FnMut(&[u8], &[u8]) -> bool) {
+        // NOTE: The lock is not held while calling `func`, so that `func` can write to the db.
+        let mut entry = self.first_entry(col, Bound::Included(lower_bound));
+        while let Some((key, value)) = entry {
+            if !func(&key, &value) {
+                break;
+            }
+            entry = self.first_entry(col, Bound::Excluded(&key[..]));
+        }
+    }
+
+    fn write(&self, batch: &WriteBatch) -> io::Result<()> {
+        let mut cols = self.cols.write().unwrap();
+        for (col, key, value) in batch.iter() {
+            let cf = cols
+                .get_mut(col)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid column"))?;
+            let cf = Arc::make_mut(cf);
+            match value {
+                Some(val) => {
+                    cf.insert(key.to_vec(), val.to_vec());
+                }
+                None => {
+                    cf.remove(key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn snapshot(&self) -> Box<dyn KeyValueDB> {
+        Box::new(MemorySnapshot {
+            cols: Arc::new(self.cols.read().unwrap().clone()),
+        })
+    }
+}
+
+/// Read-only snapshot of a MemoryDB.
+struct MemorySnapshot {
+    cols: Arc<Vec<Arc<Column>>>,
+}
+
+impl KeyValueDB for MemorySnapshot {
+    fn get(&self, col: usize, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.cols[col].get(key).cloned())
+    }
+
+    fn for_each_from(&self, col: usize, lower_bound: &[u8], func: &mut dyn FnMut(&[u8], &[u8]) -> bool) {
+        for (key, value) in self.cols[col].range::<[u8], _>((Bound::Included(lower_bound), Bound::Unbounded)) {
+            if !func(key, value) {
+                break;
+            }
+        }
+    }
+
+    fn write(&self, _batch: &WriteBatch) -> io::Result<()> {
+        Err(read_only_error())
+    }
+
+    fn snapshot(&self) -> Box<dyn KeyValueDB> {
+        Box::new(MemorySnapshot {
+            cols: self.cols.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_db() {
+        let db = MemoryDB::new(2);
+        let mut wb = WriteBatch::new();
+        wb.put(1, b"key-b", b"2");
+        wb.putv(1, &[b"key", b"-a"], &[b"1"]);
+        wb.put(1, b"key-c", b"3");
+        wb.put(0, b"key-a", b"0");
+        wb.delete(1, b"key-c");
+        db.write(&wb).unwrap();
+
+        assert_eq!(db.get(1, b"key-a").unwrap(), Some(b"1".to_vec()));
+        assert_eq!(db.get(1, b"key-c").unwrap(), None);
+
+        let mut visited = vec![];
+        db.for_each_from(1, b"key-", &mut |key, _| {
+            visited.push(key.to_vec());
+            // writing while iterating
+            let mut wb = WriteBatch::new();
+            wb.put(1, b"key-z", b"26");
+            db.write(&wb).unwrap();
+            visited.len() < 3
+        });
+        assert_eq!(visited, vec![b"key-a".to_vec(), b"key-b".to_vec(), b"key-z".to_vec()]);
+    }
+
+    #[test]
+    fn test_memory_db_snapshot() {
+        let db = MemoryDB::new(1);
+        let mut wb = WriteBatch::new();
+        wb.put(0, b"key", b"1");
+        db.write(&wb).unwrap();
+
+        let snapshot = db.snapshot();
+        let mut wb = WriteBatch::new();
+        wb.put(0, b"key", b"2");
+        wb.put(0, b"new-key", b"3");
+        db.write(&wb).unwrap();
+
+        assert_eq!(db.get(0, b"key").unwrap(), Some(b"2".to_vec()));
+        assert_eq!(snapshot.get(0, b"key").unwrap(), Some(b"1".to_vec()));
+        assert_eq!(snapshot.get(0, b"new-key").unwrap(), None);
+        assert!(snapshot.write(&wb).is_err());
+    }
+}
@@ -0,0 +1,200 @@
+//! The RocksDB storage engine.
+
+use std::io;
+use std::mem;
+use std::path::Path;
+use std::sync::Arc;
+
+use log::info;
+use rocks::prelude::*;
+
+use super::{read_only_error, KeyValueDB, WriteBatch};
+
+fn other_error<E: ToString>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e.to_string())
+}
+
+/// Storage engine backed by RocksDB, columns are column families.
+pub struct RocksDB {
+    // Shared with snapshots.
+    db: Arc<DB>,
+    cols: Arc<Vec<ColumnFamily>>,
+    is_secondary: bool,
+}
+
+impl RocksDB {
+    /// Open or create the db, column families are created if missing.
+    pub fn open<P: AsRef<Path>>(db_path: P, column_families: Vec<ColumnFamilyDescriptor>) -> io::Result<RocksDB> {
+        let db_options = DBOptions::default()
+            .create_if_missing(true)
+            .create_missing_column_families(true)
+            .increase_parallelism(num_cpus::get() as _)
+            .allow_mmap_reads(true) // for Cuckoo table
+            .max_open_files(1024);
+
+        let (db, cols) = DB::open_with_column_families(&db_options, db_path, column_families).map_err(other_error)?;
+        Ok(RocksDB {
+            db: Arc::new(db),
+            cols: Arc::new(cols),
+            is_secondary: false,
+        })
+    }
+
+    /// Open the db as a secondary instance, which follows the primary via `try_catch_up_with_primary`.
+    pub fn open_as_secondary<P1: AsRef<Path>, P2: AsRef<Path>>(
+        db_path: P1,
+        tmp_path: P2,
+        column_families: Vec<ColumnFamilyDescriptor>,
+    ) -> io::Result<RocksDB> {
+        let db_options = DBOptions::default()
+            .increase_parallelism(num_cpus::get() as _)
+            .allow_mmap_reads(true) // for Cuckoo table
+            .max_open_files(1024);
+
+        let (db, cols) = DB::open_as_secondary_with_column_families(&db_options, db_path, tmp_path, column_families)
+            .map_err(other_error)?;
+        Ok(RocksDB {
+            db: Arc::new(db),
+            cols: Arc::new(cols),
+            is_secondary: true,
+        })
+    }
+}
+
+fn get_cf(db: &DB, ropts: &ReadOptions, cf: &ColumnFamily, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    match db.get_cf(ropts, cf, key) {
+        Ok(val) => Ok(Some(val.to_vec())),
+        Err(e) if e.is_not_found() => Ok(None),
+        Err(e) => Err(other_error(e)),
+    }
+}
+
+fn for_each_cf(
+    db: &DB,
+    ropts: ReadOptions,
+    cf: &ColumnFamily,
+    lower_bound: &[u8],
+    func: &mut dyn FnMut(&[u8], &[u8]) -> bool,
+) {
+    let ropts = if lower_bound.is_empty() {
+        ropts
+    } else {
+        ropts.iterate_lower_bound(lower_bound)
+    };
+    for (key, value) in db.new_iterator_cf(&ropts, cf) {
+        if !func(key, value) {
+            break;
+        }
+    }
+}
+
+impl KeyValueDB for RocksDB {
+    fn get(&self, col: usize, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        get_cf(&self.db, ReadOptions::default_instance(), &self.cols[col], key)
+    }
+
+    fn for_each_from(&self, col: usize, lower_bound: &[u8], func: &mut dyn FnMut(&[u8], &[u8]) -> bool) {
+        for_each_cf(&self.db, ReadOptions::default(), &self.cols[col], lower_bound, func)
+    }
+
+    fn write(&self, batch: &WriteBatch) -> io::Result<()> {
+        let mut wb = rocks::prelude::WriteBatch::with_reserved_bytes(1024);
+        for (col, key, value) in batch.iter() {
+            match value {
+                Some(val) => wb.put_cf(&self.cols[col], key, val),
+                None => wb.delete_cf(&self.cols[col], key),
+            };
+        }
+        self.db
+            .write(WriteOptions::default_instance(), &wb)
+            .map_err(other_error)
+    }
+
+    fn snapshot(&self) -> Box<dyn KeyValueDB> {
+        // NOTE: Secondaries do not support snapshots. They only change on `try_catch_up_with_primary`.
+        let snapshot = if self.is_secondary {
+            None
+        } else {
+            let snapshot = self.db.get_snapshot().expect("snapshot is supported");
+            // The snapshot borrows the db, which is kept alive by `db`.
+            Some(unsafe { mem::transmute::<Snapshot<'_>, Snapshot<'static>>(snapshot) })
+        };
+        Box::new(RocksSnapshot {
+            inner: Arc::new(SnapshotRef {
+                snapshot,
+                db: self.db.clone(),
+                cols: self.cols.clone(),
+            }),
+        })
+    }
+
+    fn try_catch_up_with_primary(&self) -> io::Result<()> {
+        if self.is_secondary {
+            self.db.try_catch_up_with_primary().map_err(other_error)?;
+        }
+        Ok(())
+    }
+
+    fn int_property(&self, name: &str) -> u64 {
+        self.db.get_int_property(name).unwrap_or_default()
+    }
+
+    fn accumulated_int_property(&self, name: &str) -> u64 {
+        self.cols
+            .iter()
+            .map(|cf| cf.get_int_property(name).unwrap_or_default())
+            .sum()
+    }
+
+    fn compact(&self) -> io::Result<()> {
+        for cf in self.cols.iter() {
+            cf.compact_range(&Default::default(), ..).map_err(other_error)?;
+        }
+        Ok(())
+    }
+
+    unsafe fn prepare_close(&self) {
+        info!("flush db ... {:?}", self.db.flush(&FlushOptions::default()));
+        info!("cancal background work ...");
+        self.db.cancel_background_work(/* wait: */ true);
+        info!("syncing WAL ... {:?}", self.db.sync_wal());
+    }
+}
+
+struct SnapshotRef {
+    // NOTE: Fields are dropped in declaration order, the snapshot must be released before the db.
+    snapshot: Option<Snapshot<'static>>,
+    db: Arc<DB>,
+    cols: Arc<Vec<ColumnFamily>>,
+}
+
+// RocksDB snapshots are immutable, and safe to be read from multiple threads.
+unsafe impl Send for SnapshotRef {}
+unsafe impl Sync for SnapshotRef {}
+
+/// Read-only snapshot of a RocksDB.
+struct RocksSnapshot {
+    inner: Arc<SnapshotRef>,
+}
+
+impl KeyValueDB for RocksSnapshot {
+    fn get(&self, col: usize, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        let ropts = ReadOptions::default().snapshot(self.inner.snapshot.as_ref());
+        get_cf(&self.inner.db, &ropts, &self.inner.cols[col], key)
+    }
+
+    fn for_each_from(&self, col: usize, lower_bound: &[u8], func: &mut dyn FnMut(&[u8], &[u8]) -> bool) {
+        let ropts = ReadOptions::default().snapshot(self.inner.snapshot.as_ref());
+        for_each_cf(&self.inner.db, ropts, &self.inner.cols[col], lower_bound, func)
+    }
+
+    fn write(&self, _batch: &WriteBatch) -> io::Result<()> {
+        Err(read_only_error())
+    }
+
+    fn snapshot(&self) -> Box<dyn KeyValueDB> {
+        Box::new(RocksSnapshot {
+            inner: self.inner.clone(),
+        })
+    }
+}
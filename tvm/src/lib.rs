@@ -7,6 +7,7 @@ use self::backend::Backend;
 
 pub mod backend;
 pub mod precompile;
+pub mod profile;
 
 /// Handle TVM upgrades.
 #[derive(Debug, Clone, Default)]
@@ -40,6 +41,9 @@ impl TvmUpgrade {
     pub fn precompile(
         &self,
     ) -> fn(H160, &[u8], Option<usize>, &dyn Backend) -> Option<Result<(ExitSucceed, Vec<u8>, usize), ExitError>> {
+        if self.istanbul {
+            return self::precompile::tron_precompile_istanbul;
+        }
         return self::precompile::tron_precompile;
     }
 
@@ -60,7 +64,18 @@ impl TvmUpgrade {
         if self.solidity059 {
             config.allow_tvm_solidity059();
         }
-        // TODO: handle 4.1 update.
+        if self.istanbul {
+            // CHAINID, SELFBALANCE, and CREATE2 with the correct address scheme.
+            config.allow_tvm_istanbul();
+        }
+        if self.stake {
+            // STAKE, UNSTAKE, WITHDRAWREWARD, REWARDBALANCE, backed by `Backend` system hooks.
+            config.allow_tvm_stake();
+        }
+        if self.asset_issue {
+            // ASSETISSUE, UPDATEASSET, backed by `Backend` system hooks.
+            config.allow_tvm_asset_issue();
+        }
         config
     }
 }
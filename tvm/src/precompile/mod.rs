@@ -9,7 +9,9 @@ use num_traits::Zero;
 use primitive_types::{H160, H256, U256};
 use sha2::Sha256;
 use std::convert::TryFrom;
-use ztron::precompiles::{pedersen_hash, verify_burn_proof, verify_mint_proof, verify_transfer_proof};
+use ztron::precompiles::{
+    pedersen_hash, verify_burn_proof, verify_mint_proof, verify_transfer_proof, Error as ZtronError,
+};
 
 mod alt_bn128;
 pub mod helper;
@@ -45,10 +47,56 @@ const WORD_SIZE: usize = 32;
 // 0000000000000000000000000000000000000000000000000000000001000003 - verifyBurnProof
 // 0000000000000000000000000000000000000000000000000000000001000004 - pedersenHash
 pub fn tron_precompile(
+    address: H160,
+    input: &[u8],
+    target_gas: Option<usize>,
+    backend: &dyn Backend,
+) -> Option<Result<(ExitSucceed, Vec<u8>, usize), ExitError>> {
+    crate::profile::measure(address, || {
+        precompile_with_pricing(address, input, target_gas, backend, &ALT_BN128_PRICING)
+    })
+}
+
+/// Precompiles after AllowTvmIstanbulUpgrade, with EIP-1108 alt_bn128 energy costs.
+pub fn tron_precompile_istanbul(
+    address: H160,
+    input: &[u8],
+    target_gas: Option<usize>,
+    backend: &dyn Backend,
+) -> Option<Result<(ExitSucceed, Vec<u8>, usize), ExitError>> {
+    crate::profile::measure(address, || {
+        precompile_with_pricing(address, input, target_gas, backend, &ALT_BN128_PRICING_ISTANBUL)
+    })
+}
+
+/// Energy costs of alt_bn128 precompiles, changed by the Istanbul upgrade.
+struct AltBn128Pricing {
+    add: usize,
+    mul: usize,
+    pairing_base: usize,
+    pairing_per_pair: usize,
+}
+
+const ALT_BN128_PRICING: AltBn128Pricing = AltBn128Pricing {
+    add: 500,
+    mul: 40000,
+    pairing_base: 100000,
+    pairing_per_pair: 80000,
+};
+
+const ALT_BN128_PRICING_ISTANBUL: AltBn128Pricing = AltBn128Pricing {
+    add: 150,
+    mul: 6000,
+    pairing_base: 45000,
+    pairing_per_pair: 34000,
+};
+
+fn precompile_with_pricing(
     address: H160,
     input: &[u8],
     _target_gas: Option<usize>,
     backend: &dyn Backend,
+    bn128_pricing: &AltBn128Pricing,
 ) -> Option<Result<(ExitSucceed, Vec<u8>, usize), ExitError>> {
     if address > H160::from_low_u64_be(0xffffffff) {
         return None;
@@ -132,22 +180,17 @@ pub fn tron_precompile(
             Some(Ok((ExitSucceed::Returned, ret_with_leading_zeros, cost)))
         }
         0x6 => {
-            const COST: usize = 500;
-
             let ret = alt_bn128::ecadd(input).unwrap_or_default();
-            Some(Ok((ExitSucceed::Returned, ret, COST)))
+            Some(Ok((ExitSucceed::Returned, ret, bn128_pricing.add)))
         }
         0x7 => {
-            const COST: usize = 40000;
-
             let ret = alt_bn128::ecmul(input).unwrap_or_default();
-            Some(Ok((ExitSucceed::Returned, ret, COST)))
+            Some(Ok((ExitSucceed::Returned, ret, bn128_pricing.mul)))
         }
         0x8 => {
-            const COST: usize = 100000;
             const PAIR_SIZE: usize = 192;
 
-            let cost = COST + 80000 * (input.len() / PAIR_SIZE);
+            let cost = bn128_pricing.pairing_base + bn128_pricing.pairing_per_pair * (input.len() / PAIR_SIZE);
             let ret = alt_bn128::ecpairing(input).unwrap_or_default();
 
             Some(Ok((ExitSucceed::Returned, ret, cost)))
@@ -187,6 +230,8 @@ pub fn tron_precompile(
                         ret.extend_from_slice(&raw);
                         Some(Ok((ExitSucceed::Returned, ret, COST)))
                     }
+                    // Unavailable sapling parameters is a node error, not a contract error.
+                    Err(ZtronError::Params(e)) => Some(Err(ExitError::Other(e.to_string().into()))),
                     Err(e) => {
                         eprintln!("verifymintproof error: {:?}", e);
                         Some(Ok((ExitSucceed::Returned, H256::zero().as_bytes().to_owned(), COST)))
@@ -212,6 +257,7 @@ pub fn tron_precompile(
                         ret.extend_from_slice(&raw);
                         Some(Ok((ExitSucceed::Returned, ret, COST)))
                     }
+                    Err(ZtronError::Params(e)) => Some(Err(ExitError::Other(e.to_string().into()))),
                     Err(e) => {
                         eprintln!("verifytransferproof error: {:?}", e);
                         Some(Ok((ExitSucceed::Returned, H256::zero().as_bytes().to_owned(), COST)))
@@ -234,6 +280,7 @@ pub fn tron_precompile(
                         H256::from_low_u64_be(1).as_bytes().to_owned(),
                         COST,
                     ))),
+                    Err(ZtronError::Params(e)) => Some(Err(ExitError::Other(e.to_string().into()))),
                     Err(e) => {
                         eprintln!("verifyburnproof error: {:?}", e);
                         Some(Ok((ExitSucceed::Returned, H256::zero().as_bytes().to_owned(), COST)))
@@ -251,3 +298,4 @@ pub fn tron_precompile(
         _ => None,
     }
 }
+
@@ -0,0 +1,201 @@
+//! Profiling of precompiles and opcodes.
+//!
+//! Profiles are collected per thread, so that the executing thread can attribute precompile calls and opcodes to the
+//! transaction being executed. Disabled by default, with no overhead other than a thread-local check.
+//!
+//! Opcodes are recorded by the `step` hook of `Backend`, called by the interpreter before each step. A step lasts
+//! until the next step, so the time of CALL and CREATE excludes the callee, and the time of the last step includes
+//! the exit of the execution.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::Instant;
+
+use primitive_types::H160;
+
+/// Statistics of a precompile.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PrecompileStat {
+    pub count: u64,
+    pub elapsed_nanos: u64,
+    pub energy: u64,
+}
+
+/// Statistics of an opcode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpcodeStat {
+    pub count: u64,
+    pub elapsed_nanos: u64,
+}
+
+#[derive(Default)]
+struct OpcodeProfile {
+    stats: HashMap<u8, OpcodeStat>,
+    /// The step being executed, and when it started.
+    current: Option<(u8, Instant)>,
+}
+
+impl OpcodeProfile {
+    fn finish_step(&mut self, now: Instant) {
+        if let Some((opcode, started_at)) = self.current.take() {
+            let stat = self.stats.entry(opcode).or_default();
+            stat.count += 1;
+            stat.elapsed_nanos += now.duration_since(started_at).as_nanos() as u64;
+        }
+    }
+}
+
+thread_local! {
+    static PRECOMPILE_PROFILE: RefCell<Option<HashMap<H160, PrecompileStat>>> = RefCell::new(None);
+    static OPCODE_PROFILE: RefCell<Option<OpcodeProfile>> = RefCell::new(None);
+}
+
+/// Start profiling precompiles called by the current thread.
+pub fn enable_precompile_profile() {
+    PRECOMPILE_PROFILE.with(|profile| {
+        profile.borrow_mut().get_or_insert_with(HashMap::new);
+    });
+}
+
+/// Stop profiling precompiles called by the current thread, and discard the statistics.
+pub fn disable_precompile_profile() {
+    PRECOMPILE_PROFILE.with(|profile| *profile.borrow_mut() = None);
+}
+
+/// Take statistics collected so far by the current thread. Profiling stays enabled.
+pub fn take_precompile_profile() -> HashMap<H160, PrecompileStat> {
+    PRECOMPILE_PROFILE.with(|profile| {
+        profile
+            .borrow_mut()
+            .as_mut()
+            .map(|stats| stats.drain().collect())
+            .unwrap_or_default()
+    })
+}
+
+/// Start profiling opcodes executed by the current thread.
+pub fn enable_opcode_profile() {
+    OPCODE_PROFILE.with(|profile| {
+        profile.borrow_mut().get_or_insert_with(OpcodeProfile::default);
+    });
+}
+
+/// Stop profiling opcodes executed by the current thread, and discard the statistics.
+pub fn disable_opcode_profile() {
+    OPCODE_PROFILE.with(|profile| *profile.borrow_mut() = None);
+}
+
+/// Take statistics collected so far by the current thread, finishing the current step. Profiling stays enabled.
+pub fn take_opcode_profile() -> HashMap<u8, OpcodeStat> {
+    OPCODE_PROFILE.with(|profile| {
+        profile
+            .borrow_mut()
+            .as_mut()
+            .map(|profile| {
+                profile.finish_step(Instant::now());
+                profile.stats.drain().collect()
+            })
+            .unwrap_or_default()
+    })
+}
+
+/// Record a step of the interpreter, if profiling is enabled. Called by the `step` hook of `Backend`.
+pub fn record_step(opcode: u8) {
+    OPCODE_PROFILE.with(|profile| {
+        if let Some(profile) = profile.borrow_mut().as_mut() {
+            let now = Instant::now();
+            profile.finish_step(now);
+            profile.current = Some((opcode, now));
+        }
+    });
+}
+
+/// Run a precompile, recording elapsed time and energy cost if profiling is enabled.
+pub(crate) fn measure<T, E, F>(address: H160, f: F) -> Option<Result<(T, Vec<u8>, usize), E>>
+where
+    F: FnOnce() -> Option<Result<(T, Vec<u8>, usize), E>>,
+{
+    let enabled = PRECOMPILE_PROFILE.with(|profile| profile.borrow().is_some());
+    if !enabled {
+        return f();
+    }
+
+    let started_at = Instant::now();
+    let ret = f();
+    let elapsed_nanos = started_at.elapsed().as_nanos() as u64;
+
+    // Not a precompile.
+    if ret.is_none() {
+        return ret;
+    }
+    let energy = match ret {
+        Some(Ok((_, _, cost))) => cost as u64,
+        _ => 0,
+    };
+    PRECOMPILE_PROFILE.with(|profile| {
+        if let Some(stats) = profile.borrow_mut().as_mut() {
+            let stat = stats.entry(address).or_default();
+            stat.count += 1;
+            stat.elapsed_nanos += elapsed_nanos;
+            stat.energy += energy;
+        }
+    });
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(input: &[u8]) -> Option<Result<((), Vec<u8>, usize), ()>> {
+        Some(Ok(((), input.to_vec(), 15 + 3 * ((input.len() + 31) / 32))))
+    }
+
+    #[test]
+    fn test_precompile_profile() {
+        let address = H160::from_low_u64_be(0x4);
+
+        let _ = measure(address, || identity(b"not profiled"));
+        assert!(take_precompile_profile().is_empty());
+
+        enable_precompile_profile();
+        let _ = measure(address, || identity(b"hello"));
+        let _ = measure(address, || identity(b"world"));
+        let _ = measure(H160::from_low_u64_be(0xff), || None::<Result<((), Vec<u8>, usize), ()>>);
+
+        let profile = take_precompile_profile();
+        assert_eq!(profile.len(), 1);
+        assert_eq!(profile[&address].count, 2);
+        assert_eq!(profile[&address].energy, 36);
+        assert!(take_precompile_profile().is_empty());
+
+        disable_precompile_profile();
+        let _ = measure(address, || identity(b"not profiled"));
+        assert!(take_precompile_profile().is_empty());
+    }
+
+    #[test]
+    fn test_opcode_profile() {
+        const PUSH1: u8 = 0x60;
+        const SSTORE: u8 = 0x55;
+
+        record_step(PUSH1);
+        assert!(take_opcode_profile().is_empty());
+
+        enable_opcode_profile();
+        record_step(PUSH1);
+        record_step(PUSH1);
+        record_step(SSTORE);
+
+        let profile = take_opcode_profile();
+        assert_eq!(profile.len(), 2);
+        assert_eq!(profile[&PUSH1].count, 2);
+        // The last step is finished when taken.
+        assert_eq!(profile[&SSTORE].count, 1);
+        assert!(take_opcode_profile().is_empty());
+
+        disable_opcode_profile();
+        record_step(PUSH1);
+        assert!(take_opcode_profile().is_empty());
+    }
+}
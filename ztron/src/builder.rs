@@ -3,7 +3,6 @@
 use crypto_api_chachapoly::ChachaPolyIetf;
 use ff::{Field, PrimeField};
 use keys::Address;
-use lazy_static::lazy_static;
 use primitive_types::U256;
 use rand::{rngs::OsRng, CryptoRng, RngCore};
 use sha2::{Digest, Sha256};
@@ -20,19 +19,7 @@ use zcash_proofs::prover::LocalTxProver;
 use group::GroupEncoding;
 
 use crate::keys::ZAddress;
-
-lazy_static! {
-    pub static ref TX_PROVER: LocalTxProver = {
-        use std::path::Path;
-
-        let spend_path = "./ztron-params/sapling-spend.params";
-        let output_path = "./ztron-params/sapling-output.params";
-
-        eprintln!("loading local tx prover");
-
-        LocalTxProver::new(Path::new(spend_path), Path::new(output_path))
-    };
-}
+use crate::params;
 
 // pub use zcash_primitives::transaction::builder::Error;
 #[derive(Debug, PartialEq)]
@@ -77,6 +64,19 @@ pub fn parse_rcm(value: &[u8]) -> Result<jubjub::Fr, Error> {
     Ok(jubjub::Fr::from_bytes(&r).unwrap())
 }
 
+/// Load a prover from sapling parameters in `dir`.
+pub fn load_tx_prover<P: AsRef<std::path::Path>>(dir: P) -> Result<LocalTxProver, params::Error> {
+    let spend_path = dir.as_ref().join(params::SAPLING_SPEND_PARAMS_FILE);
+    let output_path = dir.as_ref().join(params::SAPLING_OUTPUT_PARAMS_FILE);
+    // LocalTxProver panics on missing files.
+    for path in &[&spend_path, &output_path] {
+        if !path.exists() {
+            return Err(params::Error::NotFound(path.to_path_buf()));
+        }
+    }
+    Ok(LocalTxProver::new(&spend_path, &output_path))
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum TransactionType {
     Mint,
@@ -84,6 +84,53 @@ pub enum TransactionType {
     Burn,
 }
 
+impl TransactionType {
+    /// Method signature in the ShieldedTRC20 contract.
+    pub fn method_signature(&self) -> &'static str {
+        match *self {
+            TransactionType::Mint => "mint(uint256,bytes32[9],bytes32[2],bytes32[21])",
+            TransactionType::Transfer => "transfer(bytes32[10][],bytes32[2][],bytes32[9][],bytes32[2],bytes32[21][])",
+            TransactionType::Burn => {
+                "burn(bytes32[10],bytes32[2],uint256,bytes32[2],address,bytes32[3],bytes32[9][],bytes32[21][])"
+            }
+        }
+    }
+
+    /// The 4-byte method id, to be prepended to the built parameters.
+    pub fn method_id(&self) -> [u8; 4] {
+        use ethabi::ParamType::{Address, Array, FixedArray, FixedBytes, Uint};
+
+        let words = |n| FixedArray(Box::new(FixedBytes(32)), n);
+        let array_of_words = |n| Array(Box::new(words(n)));
+        match *self {
+            TransactionType::Mint => ethabi::short_signature("mint", &[Uint(256), words(9), words(2), words(21)]),
+            TransactionType::Transfer => ethabi::short_signature(
+                "transfer",
+                &[
+                    array_of_words(10),
+                    array_of_words(2),
+                    array_of_words(9),
+                    words(2),
+                    array_of_words(21),
+                ],
+            ),
+            TransactionType::Burn => ethabi::short_signature(
+                "burn",
+                &[
+                    words(10),
+                    words(2),
+                    Uint(256),
+                    words(2),
+                    Address,
+                    words(3),
+                    array_of_words(9),
+                    array_of_words(21),
+                ],
+            ),
+        }
+    }
+}
+
 struct TransparentInput {
     amount: U256,
 }
@@ -580,7 +627,7 @@ impl<R: RngCore + CryptoRng> Builder<R> {
 
         let mut parameter = vec![0u8; 32];
 
-        let raw_value = U256::from(shielded_output_value) * U256::exp10(18); // value * scaleFactor
+        let raw_value = U256::from(shielded_output_value) * self.scaling_factor;
         raw_value.to_big_endian(&mut parameter[..32]);
 
         parameter.extend_from_slice(output_desc.cmu.to_repr().as_ref());
@@ -651,9 +698,6 @@ impl<R: RngCore + CryptoRng> Builder<R> {
         for (desc, spend) in spend_descs.iter_mut().zip(self.spends.iter()) {
             desc.generate_spend_sig(spend, sighash.as_ref());
         }
-        for desc in &spend_descs {
-            println!("!!! => {:?}", desc.spend_auth_sig);
-        }
 
         let binding_sig = prover
             .binding_sig(&mut ctx, self.value_balance, sighash.as_ref())
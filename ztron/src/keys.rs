@@ -56,6 +56,12 @@ impl FromStr for ZAddress {
     }
 }
 
+impl From<PaymentAddress> for ZAddress {
+    fn from(addr: PaymentAddress) -> Self {
+        ZAddress(addr)
+    }
+}
+
 impl Hash for ZAddress {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.0.to_bytes().hash(state);
@@ -1,3 +1,7 @@
 pub mod keys;
 pub mod builder;
+pub mod params;
 pub mod precompiles;
+pub mod scanner;
+pub mod tree;
+pub mod verifier;
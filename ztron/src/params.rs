@@ -0,0 +1,134 @@
+//! Sapling zk-SNARK parameters.
+//!
+//! Loaded once at startup from `ztron.params-dir` in config, validated against known BLAKE2b-512 hashes.
+//! Validating nodes only need the verifying keys, proving parameters are loaded by `builder::load_tx_prover`.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use bellman::groth16::{prepare_verifying_key, PreparedVerifyingKey, VerifyingKey};
+use blake2b_simd::State as Blake2bState;
+use bls12_381::Bls12;
+use lazy_static::lazy_static;
+
+pub const SAPLING_SPEND_PARAMS_FILE: &str = "sapling-spend.params";
+pub const SAPLING_OUTPUT_PARAMS_FILE: &str = "sapling-output.params";
+
+// Same as zcash_proofs.
+const SAPLING_SPEND_HASH: &str = "8270785a1a0d0bc77196f000ee6d221c9c9894f55307bd9357c3f0105d31ca63991ab91324160d8f53e2bbd3c2633a6eb8bdf5205d822e7f3f73edac51b2b70c";
+const SAPLING_OUTPUT_HASH: &str = "657e3d38dbb5cb5e7dd2970e8b03d69b4787dd907285b5a7f0790dcc8072f60bf593b32cc2d1c030e00ff5ae64bf84c5c3beb84ddc841d48264b4a171744d028";
+
+lazy_static! {
+    static ref SAPLING_PARAMETERS: RwLock<Option<Arc<SaplingParameters>>> = RwLock::new(None);
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// Parameter file is missing.
+    NotFound(PathBuf),
+    /// Parameter file is corrupted or of wrong version.
+    ChecksumMismatch(PathBuf),
+    /// `init_sapling_parameters` is not called.
+    NotInitialized,
+    Io(io::Error),
+}
+
+impl ::std::fmt::Display for Error {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        match *self {
+            Error::NotFound(ref path) => write!(f, "sapling parameter file {:?} not found", path),
+            Error::ChecksumMismatch(ref path) => write!(f, "sapling parameter file {:?} checksum mismatch", path),
+            Error::NotInitialized => write!(f, "sapling parameters are not initialized"),
+            Error::Io(ref e) => write!(f, "io error while loading sapling parameters: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(inner: io::Error) -> Self {
+        Error::Io(inner)
+    }
+}
+
+/// Hashes everything read through it.
+struct HashReader<R> {
+    reader: R,
+    hasher: Blake2bState,
+}
+
+impl<R: Read> HashReader<R> {
+    fn new(reader: R) -> Self {
+        HashReader {
+            reader,
+            hasher: Blake2bState::new(),
+        }
+    }
+
+    /// Consume the rest of the reader, returns the hex digest.
+    fn finalize(mut self) -> io::Result<String> {
+        io::copy(&mut self, &mut io::sink())?;
+        Ok(self.hasher.finalize().to_hex().to_string())
+    }
+}
+
+impl<R: Read> Read for HashReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.reader.read(buf)?;
+        self.hasher.update(&buf[..len]);
+        Ok(len)
+    }
+}
+
+/// Load the verifying key of a parameter file. The rest of the file(proving parameters) is only hashed.
+fn load_verifying_key(path: &Path, expected_hash: &str) -> Result<PreparedVerifyingKey<Bls12>, Error> {
+    let file = File::open(path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => Error::NotFound(path.to_owned()),
+        _ => Error::Io(e),
+    })?;
+    let mut reader = HashReader::new(BufReader::with_capacity(1024 * 1024, file));
+
+    // The verifying key is at the head of the parameter file.
+    let vk = VerifyingKey::<Bls12>::read(&mut reader)?;
+
+    if reader.finalize()? != expected_hash {
+        return Err(Error::ChecksumMismatch(path.to_owned()));
+    }
+    Ok(prepare_verifying_key(&vk))
+}
+
+pub struct SaplingParameters {
+    pub spend_vk: PreparedVerifyingKey<Bls12>,
+    pub output_vk: PreparedVerifyingKey<Bls12>,
+}
+
+impl SaplingParameters {
+    /// Load and validate parameter files in `dir`.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Self, Error> {
+        let dir = dir.as_ref();
+        Ok(SaplingParameters {
+            spend_vk: load_verifying_key(&dir.join(SAPLING_SPEND_PARAMS_FILE), SAPLING_SPEND_HASH)?,
+            output_vk: load_verifying_key(&dir.join(SAPLING_OUTPUT_PARAMS_FILE), SAPLING_OUTPUT_HASH)?,
+        })
+    }
+}
+
+/// Load sapling parameters for later `sapling_parameters()` calls, should be called at startup.
+pub fn init_sapling_parameters<P: AsRef<Path>>(dir: P) -> Result<(), Error> {
+    let params = SaplingParameters::load(dir)?;
+    *SAPLING_PARAMETERS.write().unwrap() = Some(Arc::new(params));
+    Ok(())
+}
+
+/// The loaded sapling parameters.
+pub fn sapling_parameters() -> Result<Arc<SaplingParameters>, Error> {
+    SAPLING_PARAMETERS
+        .read()
+        .unwrap()
+        .as_ref()
+        .cloned()
+        .ok_or(Error::NotInitialized)
+}
@@ -4,52 +4,22 @@
 // 0000000000000000000000000000000000000000000000000000000001000003 - verifyBurnProof
 // 0000000000000000000000000000000000000000000000000000000001000004 - pedersenHash
 
-use bellman::groth16::{Parameters, PreparedVerifyingKey, Proof};
+use bellman::groth16::Proof;
 use bls12_381::Bls12;
 use ff::PrimeField;
 use group::GroupEncoding;
-use lazy_static::lazy_static;
 use primitive_types::U256;
 use zcash_primitives::merkle_tree::Hashable;
 use zcash_primitives::redjubjub::{PublicKey, Signature};
 use zcash_primitives::sapling::{merkle_hash, Node};
 use zcash_primitives::transaction::components::Amount;
-use zcash_proofs::load_parameters;
 use zcash_proofs::sapling::SaplingVerificationContext;
 
 use self::helper::AbiArgIterator;
+use crate::params::{self, sapling_parameters};
 
 pub mod helper;
 
-#[allow(dead_code)]
-struct SaplingParameters {
-    spend_vk: PreparedVerifyingKey<Bls12>,
-    output_vk: PreparedVerifyingKey<Bls12>,
-    spend_params: Parameters<Bls12>,
-    output_params: Parameters<Bls12>,
-}
-
-lazy_static! {
-    static ref SAPLING_PARAMETERS: SaplingParameters = {
-        use std::path::Path;
-
-        eprintln!("loading sapling parameters ...");
-
-        let spend_path = "./ztron-params/sapling-spend.params";
-        let output_path = "./ztron-params/sapling-output.params";
-
-        let (spend_params, spend_vk, output_params, output_vk, _) =
-            load_parameters(Path::new(spend_path), Path::new(output_path), None);
-
-        SaplingParameters {
-            spend_vk,
-            output_vk,
-            spend_params,
-            output_params,
-        }
-    };
-}
-
 /// Error while running a precompile.
 #[derive(Debug)]
 pub enum Error {
@@ -65,6 +35,8 @@ pub enum Error {
     FinalCheck,
     /// Io error.
     Io(std::io::Error),
+    /// Sapling parameters are unavailable.
+    Params(params::Error),
     /// Customized error message.
     Runtime(&'static str),
 }
@@ -81,6 +53,12 @@ impl From<std::io::Error> for Error {
     }
 }
 
+impl From<params::Error> for Error {
+    fn from(inner: params::Error) -> Self {
+        Error::Params(inner)
+    }
+}
+
 /// Get frontier slot from leaf index, i.e. current leaf count, from 0.
 fn get_frontier_slot(index: usize) -> usize {
     let mut slot = 0;
@@ -225,14 +203,15 @@ pub fn verify_mint_proof(data: &[u8]) -> Result<Vec<u8>, Error> {
     assert!(it.is_ended());
 
     // librustzcashSaplingCheckOutput
-    let cm = bls12_381::Scalar::from_bytes(&cm).unwrap(); // todo
-    let cv = jubjub::ExtendedPoint::from_bytes(&cv).unwrap();
-    let epk = jubjub::ExtendedPoint::from_bytes(&epk).unwrap();
+    let cm = Option::from(bls12_381::Scalar::from_bytes(&cm)).ok_or(Error::InvalidValue)?;
+    let cv = Option::from(jubjub::ExtendedPoint::from_bytes(&cv)).ok_or(Error::InvalidValue)?;
+    let epk = Option::from(jubjub::ExtendedPoint::from_bytes(&epk)).ok_or(Error::InvalidValue)?;
     let zkproof = Proof::<Bls12>::read(zkproof)?;
 
+    let params = sapling_parameters()?;
     let mut ctx = SaplingVerificationContext::new();
 
-    if !ctx.check_output(cv, cm, epk, zkproof, &SAPLING_PARAMETERS.output_vk) {
+    if !ctx.check_output(cv, cm, epk, zkproof, &params.output_vk) {
         return Err(Error::ChecknOutput);
     }
 
@@ -278,6 +257,7 @@ pub fn verify_transfer_proof(data: &[u8]) -> Result<Vec<u8>, Error> {
     let frontier = it.next_fixed_words(33)?;
     let leaf_count = it.next_u256()?;
 
+    let params = sapling_parameters()?;
     let mut ctx = SaplingVerificationContext::new();
 
     // check spend - librustzcashSaplingCheckSpendNew
@@ -291,8 +271,8 @@ pub fn verify_transfer_proof(data: &[u8]) -> Result<Vec<u8>, Error> {
         let rk = iit.next_byte32()?;
         let zkproof = iit.next_fixed_words(6)?;
 
-        let cv = jubjub::ExtendedPoint::from_bytes(&cv).unwrap();
-        let anchor = bls12_381::Scalar::from_bytes(&anchor).unwrap();
+        let cv = Option::from(jubjub::ExtendedPoint::from_bytes(&cv)).ok_or(Error::InvalidValue)?;
+        let anchor = Option::from(bls12_381::Scalar::from_bytes(&anchor)).ok_or(Error::InvalidValue)?;
 
         let rk = PublicKey::read(rk)?;
         let spend_auth_sig = Signature::read(spend_auth_sig)?;
@@ -307,7 +287,7 @@ pub fn verify_transfer_proof(data: &[u8]) -> Result<Vec<u8>, Error> {
             &sighash,
             spend_auth_sig,
             zkproof,
-            &SAPLING_PARAMETERS.spend_vk,
+            &params.spend_vk,
         ) {
             return Err(Error::CheckSpend);
         }
@@ -324,12 +304,12 @@ pub fn verify_transfer_proof(data: &[u8]) -> Result<Vec<u8>, Error> {
         let epk = oit.next_byte32_as_array()?;
         let zkproof = oit.next_fixed_words(6)?;
 
-        let cm = bls12_381::Scalar::from_bytes(&cm).unwrap(); // todo
-        let cv = jubjub::ExtendedPoint::from_bytes(&cv).unwrap();
-        let epk = jubjub::ExtendedPoint::from_bytes(&epk).unwrap();
+        let cm = Option::from(bls12_381::Scalar::from_bytes(&cm)).ok_or(Error::InvalidValue)?;
+        let cv = Option::from(jubjub::ExtendedPoint::from_bytes(&cv)).ok_or(Error::InvalidValue)?;
+        let epk = Option::from(jubjub::ExtendedPoint::from_bytes(&epk)).ok_or(Error::InvalidValue)?;
         let zkproof = Proof::<Bls12>::read(zkproof)?;
 
-        if !ctx.check_output(cv, cm, epk, zkproof, &SAPLING_PARAMETERS.output_vk) {
+        if !ctx.check_output(cv, cm, epk, zkproof, &params.output_vk) {
             return Err(Error::ChecknOutput);
         }
 
@@ -376,12 +356,13 @@ pub fn verify_burn_proof(data: &[u8]) -> Result<(), Error> {
     let binding_sig = it.next_fixed_words(2)?;
     let sighash = it.next_byte32_as_array()?;
 
-    let cv = jubjub::ExtendedPoint::from_bytes(&cv).unwrap();
-    let anchor = bls12_381::Scalar::from_bytes(&anchor).unwrap();
+    let cv = Option::from(jubjub::ExtendedPoint::from_bytes(&cv)).ok_or(Error::InvalidValue)?;
+    let anchor = Option::from(bls12_381::Scalar::from_bytes(&anchor)).ok_or(Error::InvalidValue)?;
     let rk = PublicKey::read(rk)?;
     let spend_auth_sig = Signature::read(spend_auth_sig)?;
     let zkproof = Proof::<Bls12>::read(zkproof)?;
 
+    let params = sapling_parameters()?;
     let mut ctx = SaplingVerificationContext::new();
 
     // librustzcashSaplingCheckSpend
@@ -393,7 +374,7 @@ pub fn verify_burn_proof(data: &[u8]) -> Result<(), Error> {
         &sighash,
         spend_auth_sig,
         zkproof,
-        &SAPLING_PARAMETERS.spend_vk,
+        &params.spend_vk,
     ) {
         return Err(Error::CheckSpend);
     }
@@ -416,8 +397,8 @@ pub fn pedersen_hash(data: &[u8]) -> Result<Vec<u8>, Error> {
     let mut it = AbiArgIterator::new(data);
 
     let level: usize = it.next_u256()?.try_into().map_err(|s| Error::Runtime(s))?;
-    let left = it.next_byte32_as_array().unwrap();
-    let right = it.next_byte32_as_array().unwrap();
+    let left = it.next_byte32_as_array()?;
+    let right = it.next_byte32_as_array()?;
 
     let result = merkle_hash(level, &left, &right);
     Ok(result.as_ref().to_vec())
@@ -0,0 +1,455 @@
+//! Scanner of shielded TRC20 contract events, finding notes belonging to a viewing key.
+//!
+//! Events of the ShieldedTRC20 contract:
+//!
+//! ```solidity
+//! event NewLeaf(uint256 position, bytes32 cm, bytes32 cv, bytes32 epk, bytes32[21] c);
+//! event MintNewLeaf(uint256 position, bytes32 cm, bytes32 cv, bytes32 epk, bytes32[21] c);
+//! event TransferNewLeaf(uint256 position, bytes32 cm, bytes32 cv, bytes32 epk, bytes32[21] c);
+//! event BurnNewLeaf(uint256 position, bytes32 cm, bytes32 cv, bytes32 epk, bytes32[21] c);
+//! event TokenMint(address from, uint256 value);
+//! event TokenBurn(address to, uint256 value, bytes32[3] ciphertext);
+//! event NoteSpent(bytes32 nf);
+//! ```
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use crypto_api_chachapoly::ChachaPolyIetf;
+use ethabi::ParamType;
+use ff::PrimeField;
+use group::GroupEncoding;
+use keys::Address;
+use lazy_static::lazy_static;
+use primitive_types::U256;
+use zcash_primitives::consensus::{BlockHeight, MainNetwork};
+use zcash_primitives::keys::OutgoingViewingKey;
+use zcash_primitives::note_encryption::{try_sapling_note_decryption, try_sapling_output_recovery, Memo};
+use zcash_primitives::primitives::{Note, PaymentAddress, Rseed, ViewingKey};
+
+use crate::keys::ZKey;
+
+/// Size of enc_ciphertext.
+const ENC_CIPHERTEXT_SIZE: usize = 580;
+/// Size of out_ciphertext.
+const OUT_CIPHERTEXT_SIZE: usize = 80;
+/// Size of the burn cipher, encrypted by ovk.
+const BURN_CIPHERTEXT_SIZE: usize = 80;
+
+// Shielded TRC20 notes are always in the pre-ZIP212 format(lead byte 0x01).
+const PRE_CANOPY_HEIGHT: u32 = 0;
+
+lazy_static! {
+    static ref NEW_LEAF_EVENT_TOPICS: Vec<[u8; 32]> = {
+        let params = [
+            ParamType::Uint(256),
+            ParamType::FixedBytes(32),
+            ParamType::FixedBytes(32),
+            ParamType::FixedBytes(32),
+            ParamType::FixedArray(Box::new(ParamType::FixedBytes(32)), 21),
+        ];
+        ["NewLeaf", "MintNewLeaf", "TransferNewLeaf", "BurnNewLeaf"]
+            .iter()
+            .map(|name| event_topic(name, &params))
+            .collect()
+    };
+    static ref TOKEN_MINT_EVENT_TOPIC: [u8; 32] = event_topic("TokenMint", &[ParamType::Address, ParamType::Uint(256)]);
+    static ref TOKEN_BURN_EVENT_TOPIC: [u8; 32] = event_topic(
+        "TokenBurn",
+        &[
+            ParamType::Address,
+            ParamType::Uint(256),
+            ParamType::FixedArray(Box::new(ParamType::FixedBytes(32)), 3),
+        ],
+    );
+    static ref NOTE_SPENT_EVENT_TOPIC: [u8; 32] = event_topic("NoteSpent", &[ParamType::FixedBytes(32)]);
+}
+
+fn event_topic(name: &str, params: &[ParamType]) -> [u8; 32] {
+    let mut topic = [0u8; 32];
+    topic.copy_from_slice(ethabi::long_signature(name, params).as_bytes());
+    topic
+}
+
+fn to_array32(raw: &[u8]) -> [u8; 32] {
+    let mut arr = [0u8; 32];
+    arr.copy_from_slice(&raw[..32]);
+    arr
+}
+
+/// A decoded event of the shielded TRC20 contract.
+#[derive(Debug, Clone)]
+pub enum ShieldedEvent {
+    /// A note commitment is appended to the tree, by mint, transfer or burn.
+    NewLeaf {
+        position: u64,
+        cm: [u8; 32],
+        cv: [u8; 32],
+        epk: [u8; 32],
+        enc_ciphertext: Vec<u8>,
+        out_ciphertext: Vec<u8>,
+    },
+    TokenMint {
+        from: Address,
+        value: U256,
+    },
+    TokenBurn {
+        to: Address,
+        value: U256,
+        ciphertext: Vec<u8>,
+    },
+    NoteSpent {
+        nullifier: [u8; 32],
+    },
+}
+
+impl ShieldedEvent {
+    /// Decode from a raw log entry. Returns `None` for unrelated or malformed logs.
+    pub fn from_log(topics: &[Vec<u8>], data: &[u8]) -> Option<Self> {
+        let topic = topics.first()?;
+        if topic.len() != 32 {
+            return None;
+        }
+
+        if NEW_LEAF_EVENT_TOPICS.iter().any(|t| &t[..] == &topic[..]) {
+            // position, cm, cv, epk, c[21]
+            if data.len() != 25 * 32 {
+                return None;
+            }
+            let position = U256::from_big_endian(&data[..32]);
+            if position > U256::from(u64::max_value()) {
+                return None;
+            }
+            let c = &data[4 * 32..];
+            Some(ShieldedEvent::NewLeaf {
+                position: position.low_u64(),
+                cm: to_array32(&data[32..64]),
+                cv: to_array32(&data[64..96]),
+                epk: to_array32(&data[96..128]),
+                enc_ciphertext: c[..ENC_CIPHERTEXT_SIZE].to_vec(),
+                out_ciphertext: c[ENC_CIPHERTEXT_SIZE..ENC_CIPHERTEXT_SIZE + OUT_CIPHERTEXT_SIZE].to_vec(),
+            })
+        } else if &topic[..] == &TOKEN_MINT_EVENT_TOPIC[..] {
+            if data.len() != 2 * 32 {
+                return None;
+            }
+            Some(ShieldedEvent::TokenMint {
+                from: Address::from_tvm_bytes(&data[12..32]),
+                value: U256::from_big_endian(&data[32..64]),
+            })
+        } else if &topic[..] == &TOKEN_BURN_EVENT_TOPIC[..] {
+            if data.len() != 5 * 32 {
+                return None;
+            }
+            Some(ShieldedEvent::TokenBurn {
+                to: Address::from_tvm_bytes(&data[12..32]),
+                value: U256::from_big_endian(&data[32..64]),
+                ciphertext: data[64..64 + BURN_CIPHERTEXT_SIZE].to_vec(),
+            })
+        } else if &topic[..] == &NOTE_SPENT_EVENT_TOPIC[..] {
+            if data.len() != 32 {
+                return None;
+            }
+            Some(ShieldedEvent::NoteSpent {
+                nullifier: to_array32(data),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// `(position, cm)` of a NewLeaf event, to be appended to `IncrementalNoteTree`.
+    pub fn leaf(&self) -> Option<(u64, [u8; 32])> {
+        match *self {
+            ShieldedEvent::NewLeaf { position, cm, .. } => Some((position, cm)),
+            _ => None,
+        }
+    }
+}
+
+/// A note received by the incoming viewing key.
+#[derive(Debug, Clone)]
+pub struct ReceivedNote {
+    /// Block number of the note.
+    pub block_number: i64,
+    /// Transaction hash of the note.
+    pub txid: [u8; 32],
+    /// Position in the note commitment tree.
+    pub position: u64,
+    pub note: Note,
+    pub to: PaymentAddress,
+    pub memo: Memo,
+    /// Only available when the full viewing key(ak, nk) is known.
+    pub nullifier: Option<[u8; 32]>,
+    pub is_spent: bool,
+}
+
+impl ReceivedNote {
+    pub fn value(&self) -> u64 {
+        self.note.value
+    }
+
+    /// Note commitment randomness, required for spending the note.
+    pub fn rcm(&self) -> [u8; 32] {
+        match self.note.rseed {
+            Rseed::BeforeZip212(rcm) => rcm.to_bytes(),
+            Rseed::AfterZip212(_) => unreachable!("shielded TRC20 notes are pre-ZIP212"),
+        }
+    }
+}
+
+/// A note sent by the outgoing viewing key, a shielded output or a burn(unshielding).
+#[derive(Debug, Clone)]
+pub enum SentNote {
+    Shielded {
+        block_number: i64,
+        txid: [u8; 32],
+        position: u64,
+        note: Note,
+        to: PaymentAddress,
+        memo: Memo,
+    },
+    Transparent {
+        block_number: i64,
+        txid: [u8; 32],
+        to: Address,
+        value: U256,
+    },
+}
+
+/// Trial-decrypts shielded TRC20 events of one contract.
+///
+/// Events must be fed in chain order, so that spending is detected.
+pub struct NoteScanner {
+    ivk: Option<jubjub::Fr>,
+    vk: Option<ViewingKey>,
+    ovk: Option<OutgoingViewingKey>,
+    received: Vec<ReceivedNote>,
+    sent: Vec<SentNote>,
+    /// Nullifier => index of received notes.
+    nullifiers: HashMap<[u8; 32], usize>,
+}
+
+impl NoteScanner {
+    /// Scan notes received by an incoming viewing key.
+    pub fn from_ivk(ivk: &[u8]) -> Option<Self> {
+        if ivk.len() != 32 {
+            return None;
+        }
+        let ivk = Option::from(jubjub::Fr::from_bytes(&to_array32(ivk)))?;
+        Some(NoteScanner {
+            ivk: Some(ivk),
+            vk: None,
+            ovk: None,
+            received: vec![],
+            sent: vec![],
+            nullifiers: HashMap::new(),
+        })
+    }
+
+    /// Scan notes sent by an outgoing viewing key.
+    pub fn from_ovk(ovk: &[u8]) -> Option<Self> {
+        if ovk.len() != 32 {
+            return None;
+        }
+        Some(NoteScanner {
+            ivk: None,
+            vk: None,
+            ovk: Some(OutgoingViewingKey(to_array32(ovk))),
+            received: vec![],
+            sent: vec![],
+            nullifiers: HashMap::new(),
+        })
+    }
+
+    /// Scan both received and sent notes, with spending tracked.
+    pub fn from_zkey(zkey: &ZKey) -> Self {
+        let mut scanner = NoteScanner::from_ovk(&zkey.ovk().0[..]).unwrap();
+        scanner.ivk = Option::from(jubjub::Fr::from_bytes(&to_array32(&zkey.ivk())));
+        scanner.vk = Some(ViewingKey {
+            ak: Option::from(jubjub::SubgroupPoint::from_bytes(&to_array32(&zkey.ak()))).unwrap(),
+            nk: Option::from(jubjub::SubgroupPoint::from_bytes(&to_array32(&zkey.nk()))).unwrap(),
+        });
+        scanner
+    }
+
+    /// Set ak and nk, so that nullifiers of received notes can be computed.
+    ///
+    /// Returns `false` if they don't match the incoming viewing key.
+    pub fn with_nullifier_key(&mut self, ak: &[u8], nk: &[u8]) -> bool {
+        if ak.len() != 32 || nk.len() != 32 {
+            return false;
+        }
+        let ak: Option<jubjub::SubgroupPoint> = jubjub::SubgroupPoint::from_bytes(&to_array32(ak)).into();
+        let nk: Option<jubjub::SubgroupPoint> = jubjub::SubgroupPoint::from_bytes(&to_array32(nk)).into();
+        match (ak, nk) {
+            (Some(ak), Some(nk)) => {
+                let vk = ViewingKey { ak, nk };
+                if self.ivk.map(|ivk| ivk != vk.ivk()).unwrap_or(false) {
+                    return false;
+                }
+                self.vk = Some(vk);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Feed an event of the contract.
+    pub fn scan_event(&mut self, block_number: i64, txid: &[u8; 32], event: &ShieldedEvent) {
+        match *event {
+            ShieldedEvent::NewLeaf {
+                position,
+                ref cm,
+                ref cv,
+                ref epk,
+                ref enc_ciphertext,
+                ref out_ciphertext,
+            } => {
+                let cmu = match bls12_381::Scalar::from_repr(*cm) {
+                    Some(cmu) => cmu,
+                    None => return,
+                };
+                let epk = match Option::<jubjub::SubgroupPoint>::from(jubjub::SubgroupPoint::from_bytes(epk)) {
+                    Some(epk) => epk,
+                    None => return,
+                };
+                let height = BlockHeight::from_u32(PRE_CANOPY_HEIGHT);
+
+                if let Some(ref ivk) = self.ivk {
+                    if let Some((note, to, memo)) =
+                        try_sapling_note_decryption(&MainNetwork, height, ivk, &epk, &cmu, enc_ciphertext)
+                    {
+                        let nullifier = self.vk.as_ref().map(|vk| to_array32(&note.nf(vk, position)));
+                        if let Some(nf) = nullifier {
+                            self.nullifiers.insert(nf, self.received.len());
+                        }
+                        self.received.push(ReceivedNote {
+                            block_number,
+                            txid: *txid,
+                            position,
+                            note,
+                            to,
+                            memo,
+                            nullifier,
+                            is_spent: false,
+                        });
+                    }
+                }
+
+                if let Some(ref ovk) = self.ovk {
+                    let cv = match Option::<jubjub::ExtendedPoint>::from(jubjub::ExtendedPoint::from_bytes(cv)) {
+                        Some(cv) => cv,
+                        None => return,
+                    };
+                    if let Some((note, to, memo)) = try_sapling_output_recovery(
+                        &MainNetwork,
+                        height,
+                        ovk,
+                        &cv,
+                        &cmu,
+                        &epk,
+                        enc_ciphertext,
+                        out_ciphertext,
+                    ) {
+                        self.sent.push(SentNote::Shielded {
+                            block_number,
+                            txid: *txid,
+                            position,
+                            note,
+                            to,
+                            memo,
+                        });
+                    }
+                }
+            }
+            ShieldedEvent::TokenBurn { ref ciphertext, .. } => {
+                if let Some(ref ovk) = self.ovk {
+                    if let Some((to, value)) = decrypt_burn_message(ovk, ciphertext) {
+                        self.sent.push(SentNote::Transparent {
+                            block_number,
+                            txid: *txid,
+                            to,
+                            value,
+                        });
+                    }
+                }
+            }
+            ShieldedEvent::NoteSpent { ref nullifier } => {
+                if let Some(&idx) = self.nullifiers.get(nullifier) {
+                    self.received[idx].is_spent = true;
+                }
+            }
+            // Transparent input of mint, nothing to decrypt.
+            ShieldedEvent::TokenMint { .. } => {}
+        }
+    }
+
+    /// Feed a raw log entry of the contract.
+    pub fn scan_log(&mut self, block_number: i64, txid: &[u8; 32], topics: &[Vec<u8>], data: &[u8]) {
+        if let Some(event) = ShieldedEvent::from_log(topics, data) {
+            self.scan_event(block_number, txid, &event);
+        }
+    }
+
+    /// Notes received by the incoming viewing key, in position order.
+    pub fn received_notes(&self) -> &[ReceivedNote] {
+        &self.received
+    }
+
+    /// Notes sent by the outgoing viewing key, in chain order.
+    pub fn sent_notes(&self) -> &[SentNote] {
+        &self.sent
+    }
+
+    pub fn into_received_notes(self) -> Vec<ReceivedNote> {
+        self.received
+    }
+
+    pub fn into_sent_notes(self) -> Vec<SentNote> {
+        self.sent
+    }
+}
+
+/// Memo content with trailing zeros stripped.
+pub fn memo_bytes(memo: &Memo) -> &[u8] {
+    let raw = memo.as_bytes();
+    let len = raw.iter().rposition(|&b| b != 0).map(|pos| pos + 1).unwrap_or(0);
+    &raw[..len]
+}
+
+/// Reverse of `Builder::encrypt_burn_message`.
+fn decrypt_burn_message(ovk: &OutgoingViewingKey, ciphertext: &[u8]) -> Option<(Address, U256)> {
+    if ciphertext.len() < BURN_CIPHERTEXT_SIZE {
+        return None;
+    }
+    let mut plaintext = [0u8; 64];
+    ChachaPolyIetf::aead_cipher()
+        .open_to(
+            &mut plaintext,
+            &ciphertext[..BURN_CIPHERTEXT_SIZE],
+            &[],
+            &ovk.0[..],
+            &[0u8; 12],
+        )
+        .ok()?;
+    let value = U256::from_big_endian(&plaintext[..32]);
+    let to = Address::try_from(&plaintext[32..32 + 21]).ok()?;
+    Some((to, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_note_spent() {
+        let nf = [0x42u8; 32];
+        let event = ShieldedEvent::from_log(&[NOTE_SPENT_EVENT_TOPIC.to_vec()], &nf).unwrap();
+        match event {
+            ShieldedEvent::NoteSpent { nullifier } => assert_eq!(nullifier, nf),
+            _ => unreachable!(),
+        }
+        assert!(ShieldedEvent::from_log(&[[0u8; 32].to_vec()], &nf).is_none());
+    }
+}
@@ -0,0 +1,398 @@
+//! Sapling note commitment tree.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, Read, Write};
+
+use zcash_primitives::merkle_tree::{CommitmentTree, Hashable, IncrementalWitness, MerklePath};
+use zcash_primitives::sapling::Node;
+
+/// The incremental Sapling note commitment tree, of depth 32.
+#[derive(Clone)]
+pub struct SaplingCommitmentTree {
+    inner: CommitmentTree<Node>,
+}
+
+impl Default for SaplingCommitmentTree {
+    fn default() -> Self {
+        SaplingCommitmentTree {
+            inner: CommitmentTree::new(),
+        }
+    }
+}
+
+impl SaplingCommitmentTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a tree saved by `to_bytes`.
+    pub fn from_bytes(raw: &[u8]) -> io::Result<Self> {
+        Ok(SaplingCommitmentTree {
+            inner: CommitmentTree::read(raw)?,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut raw = Vec::with_capacity(1024);
+        self.inner.write(&mut raw).expect("write to vec");
+        raw
+    }
+
+    /// Number of note commitments in the tree.
+    pub fn size(&self) -> usize {
+        self.inner.size()
+    }
+
+    /// Append a note commitment. Fails when the tree is full.
+    pub fn append(&mut self, cm: [u8; 32]) -> Result<(), ()> {
+        self.inner.append(Node::new(cm))
+    }
+
+    /// The tree root, i.e. the anchor used in spend descriptions.
+    pub fn root(&self) -> [u8; 32] {
+        let mut raw = [0u8; 32];
+        self.inner.root().write(&mut raw[..]).expect("length is 32 bytes");
+        raw
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// Leaves must be appended in position order, without gaps.
+    PositionMismatch {
+        expected: u64,
+        found: u64,
+    },
+    /// Blocks must be appended in increasing order.
+    BlockOutOfOrder {
+        latest: i64,
+        found: i64,
+    },
+    /// The rollback target is older than the oldest checkpoint.
+    RollbackTooDeep {
+        limit: i64,
+        found: i64,
+    },
+    TreeFull,
+}
+
+impl ::std::fmt::Display for Error {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Tree state before a block with leaves is applied.
+#[derive(Clone)]
+struct Checkpoint {
+    block_number: i64,
+    tree: CommitmentTree<Node>,
+    witnesses: BTreeMap<u64, IncrementalWitness<Node>>,
+}
+
+/// Commitment tree of a shielded TRC20 contract, following its leaves block by block.
+///
+/// Authentication paths are maintained for watched positions(notes we care about), so that
+/// they can be spent. Up to `max_rollback` blocks with leaves can be rolled back on fork.
+pub struct IncrementalNoteTree {
+    tree: CommitmentTree<Node>,
+    witnesses: BTreeMap<u64, IncrementalWitness<Node>>,
+    latest_block_number: i64,
+    checkpoints: VecDeque<Checkpoint>,
+    max_rollback: usize,
+    /// Blocks before this can't be rolled back to.
+    rollback_limit: i64,
+}
+
+impl IncrementalNoteTree {
+    /// An empty tree, before the contract is created.
+    pub fn new(max_rollback: usize) -> Self {
+        Self::from_tree(SaplingCommitmentTree::new(), -1, max_rollback)
+    }
+
+    /// Start following from a known tree(frontier) at the end of `block_number`.
+    ///
+    /// Only notes appended after it can be witnessed.
+    pub fn from_tree(tree: SaplingCommitmentTree, block_number: i64, max_rollback: usize) -> Self {
+        IncrementalNoteTree {
+            tree: tree.inner,
+            witnesses: BTreeMap::new(),
+            latest_block_number: block_number,
+            checkpoints: VecDeque::new(),
+            max_rollback,
+            rollback_limit: block_number,
+        }
+    }
+
+    /// Block number of the last appended block.
+    pub fn latest_block_number(&self) -> i64 {
+        self.latest_block_number
+    }
+
+    /// Number of leaves in the tree, i.e. position of the next leaf.
+    pub fn size(&self) -> u64 {
+        self.tree.size() as u64
+    }
+
+    /// The current tree root, used as anchor when spending.
+    pub fn anchor(&self) -> [u8; 32] {
+        node_to_bytes(&self.tree.root())
+    }
+
+    /// Append leaves of a block, as `(position, cm)` in position order.
+    ///
+    /// `watch` decides whether a position should be witnessed, i.e. belongs to us.
+    pub fn append_block<F>(&mut self, block_number: i64, leaves: &[(u64, [u8; 32])], mut watch: F) -> Result<(), Error>
+    where
+        F: FnMut(u64) -> bool,
+    {
+        if block_number <= self.latest_block_number {
+            return Err(Error::BlockOutOfOrder {
+                latest: self.latest_block_number,
+                found: block_number,
+            });
+        }
+        if leaves.is_empty() {
+            self.latest_block_number = block_number;
+            return Ok(());
+        }
+
+        let checkpoint = Checkpoint {
+            block_number,
+            tree: self.tree.clone(),
+            witnesses: self.witnesses.clone(),
+        };
+
+        for &(position, cm) in leaves {
+            if let Err(e) = self.append(position, cm, &mut watch) {
+                self.restore(checkpoint);
+                return Err(e);
+            }
+        }
+
+        self.checkpoints.push_back(checkpoint);
+        while self.checkpoints.len() > self.max_rollback {
+            let pruned = self.checkpoints.pop_front().unwrap();
+            self.rollback_limit = pruned.block_number;
+        }
+        self.latest_block_number = block_number;
+        Ok(())
+    }
+
+    fn append<F>(&mut self, position: u64, cm: [u8; 32], watch: &mut F) -> Result<(), Error>
+    where
+        F: FnMut(u64) -> bool,
+    {
+        if position != self.size() {
+            return Err(Error::PositionMismatch {
+                expected: self.size(),
+                found: position,
+            });
+        }
+        let node = Node::new(cm);
+        self.tree.append(node).map_err(|_| Error::TreeFull)?;
+        for witness in self.witnesses.values_mut() {
+            witness.append(node).map_err(|_| Error::TreeFull)?;
+        }
+        if watch(position) {
+            self.witnesses
+                .insert(position, IncrementalWitness::from_tree(&self.tree));
+        }
+        Ok(())
+    }
+
+    fn restore(&mut self, checkpoint: Checkpoint) {
+        self.tree = checkpoint.tree;
+        self.witnesses = checkpoint.witnesses;
+    }
+
+    /// Roll back to the end of `block_number`, undoing all later blocks.
+    pub fn rollback_to(&mut self, block_number: i64) -> Result<(), Error> {
+        if block_number < self.rollback_limit {
+            return Err(Error::RollbackTooDeep {
+                limit: self.rollback_limit,
+                found: block_number,
+            });
+        }
+        while self
+            .checkpoints
+            .back()
+            .map(|cp| cp.block_number > block_number)
+            .unwrap_or(false)
+        {
+            let checkpoint = self.checkpoints.pop_back().unwrap();
+            self.restore(checkpoint);
+        }
+        if block_number < self.latest_block_number {
+            self.latest_block_number = block_number;
+        }
+        Ok(())
+    }
+
+    /// Stop witnessing a position, e.g. when the note is spent.
+    pub fn unwatch(&mut self, position: u64) {
+        self.witnesses.remove(&position);
+    }
+
+    /// Witnessed positions.
+    pub fn watched_positions(&self) -> impl Iterator<Item = u64> + '_ {
+        self.witnesses.keys().copied()
+    }
+
+    /// Authentication path of a witnessed note, against the current anchor.
+    pub fn merkle_path(&self, position: u64) -> Option<MerklePath<Node>> {
+        self.witnesses.get(&position).and_then(|witness| witness.path())
+    }
+
+    /// Load a tree saved by `to_bytes`, with witnesses and checkpoints.
+    pub fn from_bytes(mut raw: &[u8]) -> io::Result<Self> {
+        let reader = &mut raw;
+        let max_rollback = read_u64(reader)? as usize;
+        let rollback_limit = read_u64(reader)? as i64;
+        let latest_block_number = read_u64(reader)? as i64;
+        let tree = CommitmentTree::read(&mut *reader)?;
+        let witnesses = read_witnesses(reader)?;
+        let num_of_checkpoints = read_u64(reader)?;
+        let mut checkpoints = VecDeque::new();
+        for _ in 0..num_of_checkpoints {
+            checkpoints.push_back(Checkpoint {
+                block_number: read_u64(reader)? as i64,
+                tree: CommitmentTree::read(&mut *reader)?,
+                witnesses: read_witnesses(reader)?,
+            });
+        }
+        Ok(IncrementalNoteTree {
+            tree,
+            witnesses,
+            latest_block_number,
+            checkpoints,
+            max_rollback,
+            rollback_limit,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut raw = Vec::with_capacity(4096);
+        self.write(&mut raw).expect("write to vec");
+        raw
+    }
+
+    fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&(self.max_rollback as u64).to_le_bytes())?;
+        writer.write_all(&self.rollback_limit.to_le_bytes())?;
+        writer.write_all(&self.latest_block_number.to_le_bytes())?;
+        self.tree.write(&mut *writer)?;
+        write_witnesses(writer, &self.witnesses)?;
+        writer.write_all(&(self.checkpoints.len() as u64).to_le_bytes())?;
+        for checkpoint in &self.checkpoints {
+            writer.write_all(&checkpoint.block_number.to_le_bytes())?;
+            checkpoint.tree.write(&mut *writer)?;
+            write_witnesses(writer, &checkpoint.witnesses)?;
+        }
+        Ok(())
+    }
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut raw = [0u8; 8];
+    reader.read_exact(&mut raw)?;
+    Ok(u64::from_le_bytes(raw))
+}
+
+fn read_witnesses<R: Read>(reader: &mut R) -> io::Result<BTreeMap<u64, IncrementalWitness<Node>>> {
+    let len = read_u64(reader)?;
+    let mut witnesses = BTreeMap::new();
+    for _ in 0..len {
+        let position = read_u64(reader)?;
+        witnesses.insert(position, IncrementalWitness::read(&mut *reader)?);
+    }
+    Ok(witnesses)
+}
+
+fn write_witnesses<W: Write>(writer: &mut W, witnesses: &BTreeMap<u64, IncrementalWitness<Node>>) -> io::Result<()> {
+    writer.write_all(&(witnesses.len() as u64).to_le_bytes())?;
+    for (position, witness) in witnesses {
+        writer.write_all(&position.to_le_bytes())?;
+        witness.write(&mut *writer)?;
+    }
+    Ok(())
+}
+
+fn node_to_bytes(node: &Node) -> [u8; 32] {
+    let mut raw = [0u8; 32];
+    node.write(&mut raw[..]).expect("length is 32 bytes");
+    raw
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf(i: u8) -> [u8; 32] {
+        let mut cm = [0u8; 32];
+        cm[0] = i;
+        cm
+    }
+
+    #[test]
+    fn test_witness_and_rollback() {
+        let mut tree = IncrementalNoteTree::new(10);
+        tree.append_block(1, &[(0, leaf(0)), (1, leaf(1))], |pos| pos == 1)
+            .unwrap();
+        tree.append_block(2, &[], |_| false).unwrap();
+        let anchor_at_2 = tree.anchor();
+        tree.append_block(3, &[(2, leaf(2)), (3, leaf(3))], |_| false).unwrap();
+
+        assert_eq!(tree.watched_positions().collect::<Vec<_>>(), vec![1]);
+        let path = tree.merkle_path(1).unwrap();
+        assert_eq!(path.position, 1);
+        assert_eq!(node_to_bytes(&path.root(Node::new(leaf(1)))), tree.anchor());
+
+        assert_eq!(
+            tree.append_block(4, &[(5, leaf(5))], |_| false),
+            Err(Error::PositionMismatch { expected: 4, found: 5 })
+        );
+        assert_eq!(tree.size(), 4);
+
+        tree.rollback_to(2).unwrap();
+        assert_eq!(tree.latest_block_number(), 2);
+        assert_eq!(tree.size(), 2);
+        assert_eq!(tree.anchor(), anchor_at_2);
+        assert_eq!(
+            node_to_bytes(&tree.merkle_path(1).unwrap().root(Node::new(leaf(1)))),
+            anchor_at_2
+        );
+
+        tree.rollback_to(0).unwrap();
+        assert_eq!(tree.size(), 0);
+        assert!(tree.merkle_path(1).is_none());
+        assert!(tree.rollback_to(-2).is_err());
+    }
+
+    #[test]
+    fn test_persisted_tree_rollback() {
+        let mut tree = IncrementalNoteTree::new(10);
+        tree.append_block(1, &[(0, leaf(0)), (1, leaf(1))], |pos| pos == 0)
+            .unwrap();
+        let anchor_at_1 = tree.anchor();
+        tree.append_block(2, &[(2, leaf(2))], |pos| pos == 2).unwrap();
+
+        let mut restored = IncrementalNoteTree::from_bytes(&tree.to_bytes()).unwrap();
+        assert_eq!(restored.to_bytes(), tree.to_bytes());
+        assert_eq!(restored.latest_block_number(), 2);
+        assert_eq!(restored.watched_positions().collect::<Vec<_>>(), vec![0, 2]);
+
+        // A forked block #2 replaces the original one.
+        restored.rollback_to(1).unwrap();
+        assert_eq!(restored.anchor(), anchor_at_1);
+        assert!(restored.merkle_path(2).is_none());
+        restored.append_block(2, &[(2, leaf(3))], |_| false).unwrap();
+        assert_ne!(restored.anchor(), tree.anchor());
+        assert_eq!(
+            node_to_bytes(&restored.merkle_path(0).unwrap().root(Node::new(leaf(0)))),
+            restored.anchor()
+        );
+    }
+}
@@ -0,0 +1,114 @@
+//! Verification of shielded TRC10 transfers, i.e. `ShieldedTransferContract` on nile testnet.
+
+use bellman::groth16::Proof;
+use bls12_381::Bls12;
+use zcash_primitives::redjubjub::{PublicKey, Signature};
+use zcash_primitives::transaction::components::Amount;
+use zcash_proofs::sapling::SaplingVerificationContext;
+
+use crate::params::sapling_parameters;
+use crate::precompiles::Error;
+
+/// A shielded input, fields of `SpendDescription`.
+pub struct SpendDescription<'a> {
+    pub value_commitment: &'a [u8],
+    pub anchor: &'a [u8],
+    pub nullifier: &'a [u8],
+    pub rk: &'a [u8],
+    pub zkproof: &'a [u8],
+    pub spend_authority_signature: &'a [u8],
+}
+
+/// A shielded output, fields of `ReceiveDescription`.
+pub struct ReceiveDescription<'a> {
+    pub value_commitment: &'a [u8],
+    pub note_commitment: &'a [u8],
+    pub epk: &'a [u8],
+    pub zkproof: &'a [u8],
+}
+
+fn to_bytes32(raw: &[u8]) -> Result<[u8; 32], Error> {
+    if raw.len() != 32 {
+        return Err(Error::InvalidValue);
+    }
+    let mut ret = [0u8; 32];
+    ret.copy_from_slice(raw);
+    Ok(ret)
+}
+
+fn read_proof(raw: &[u8]) -> Result<Proof<Bls12>, Error> {
+    if raw.len() != 192 {
+        return Err(Error::InvalidValue);
+    }
+    Ok(Proof::<Bls12>::read(raw)?)
+}
+
+fn read_signature(raw: &[u8]) -> Result<Signature, Error> {
+    if raw.len() != 64 {
+        return Err(Error::InvalidValue);
+    }
+    Ok(Signature::read(raw)?)
+}
+
+/// Verify spend proofs, output proofs and the binding signature of a shielded transfer.
+///
+/// `value_balance` is the net value leaving the shielded pool, including the shielded fee.
+pub fn verify_shielded_transfer(
+    spends: &[SpendDescription],
+    receives: &[ReceiveDescription],
+    binding_signature: &[u8],
+    value_balance: i64,
+    sighash: &[u8; 32],
+) -> Result<(), Error> {
+    let params = sapling_parameters()?;
+    let mut ctx = SaplingVerificationContext::new();
+
+    // librustzcashSaplingCheckSpend
+    for spend in spends {
+        let cv = Option::from(jubjub::ExtendedPoint::from_bytes(&to_bytes32(spend.value_commitment)?))
+            .ok_or(Error::InvalidValue)?;
+        let anchor =
+            Option::from(bls12_381::Scalar::from_bytes(&to_bytes32(spend.anchor)?)).ok_or(Error::InvalidValue)?;
+        let nullifier = to_bytes32(spend.nullifier)?;
+        let rk = PublicKey::read(&to_bytes32(spend.rk)?[..])?;
+        let spend_auth_sig = read_signature(spend.spend_authority_signature)?;
+        let zkproof = read_proof(spend.zkproof)?;
+
+        if !ctx.check_spend(
+            cv,
+            anchor,
+            &nullifier,
+            rk,
+            sighash,
+            spend_auth_sig,
+            zkproof,
+            &params.spend_vk,
+        ) {
+            return Err(Error::CheckSpend);
+        }
+    }
+
+    // librustzcashSaplingCheckOutput
+    for receive in receives {
+        let cv = Option::from(jubjub::ExtendedPoint::from_bytes(&to_bytes32(receive.value_commitment)?))
+            .ok_or(Error::InvalidValue)?;
+        let cm = Option::from(bls12_381::Scalar::from_bytes(&to_bytes32(receive.note_commitment)?))
+            .ok_or(Error::InvalidValue)?;
+        let epk =
+            Option::from(jubjub::ExtendedPoint::from_bytes(&to_bytes32(receive.epk)?)).ok_or(Error::InvalidValue)?;
+        let zkproof = read_proof(receive.zkproof)?;
+
+        if !ctx.check_output(cv, cm, epk, zkproof, &params.output_vk) {
+            return Err(Error::ChecknOutput);
+        }
+    }
+
+    // librustzcashSaplingFinalCheck
+    let value_balance = Amount::from_i64(value_balance).map_err(|_| Error::InvalidValue)?;
+    let binding_sig = read_signature(binding_signature)?;
+    if !ctx.final_check(value_balance, sighash, binding_sig) {
+        return Err(Error::FinalCheck);
+    }
+
+    Ok(())
+}